@@ -38,10 +38,10 @@ impl Text {
 
                     if let Some(selection) = Selection::from_access_selection(
                         selection,
-                        &text_box.inner.layout,
+                        text_box.inner.layout.single(),
                         &text_box.inner.layout_access
                     ) {
-                        text_box.set_selection(selection);
+                        text_box.set_selection(TextSelection::from_parley(selection));
                         return true;
                     }
                 }
@@ -0,0 +1,138 @@
+use crate::*;
+
+/// Background and border drawn behind the text of a box. See [`TextBox::set_background()`].
+///
+/// The background is drawn in the same instance buffer as the text, so it follows the transform, the depth and the screen-space clip rect of the box. It covers the whole size of the box, and it isn't scrolled or clipped with the text.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoxBackground {
+    /// Fill color. If `None`, only the border is drawn.
+    pub color: Option<ColorBrush>,
+    /// Color of the border.
+    pub border_color: ColorBrush,
+    /// Width of the border, measured inward from the edge of the box. Zero means no border.
+    pub border_width: f32,
+    /// Radius of the corners. It's limited to half of the shorter side of the box.
+    pub corner_radius: f32,
+}
+
+impl Default for BoxBackground {
+    fn default() -> Self {
+        Self {
+            color: None,
+            border_color: ColorBrush([0, 0, 0, 0]),
+            border_width: 0.0,
+            corner_radius: 0.0,
+        }
+    }
+}
+
+impl BoxBackground {
+    pub(crate) fn is_visible(&self) -> bool {
+        self.color.is_some() || (self.border_width > 0.0 && self.border_color.0[3] > 0)
+    }
+}
+
+/// Space between the edges of a box and its text.
+///
+/// Padding is measured from the outer edge of the box, so it should be at least as large as the border width, if there is one.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct Padding {
+    /// Space on the left.
+    pub left: f32,
+    /// Space on the top.
+    pub top: f32,
+    /// Space on the right.
+    pub right: f32,
+    /// Space on the bottom.
+    pub bottom: f32,
+}
+
+impl Padding {
+    /// The same padding on all sides.
+    pub fn all(padding: f32) -> Self {
+        Self { left: padding, top: padding, right: padding, bottom: padding }
+    }
+}
+
+impl TextBox {
+    /// Sets the background and the border of the box.
+    pub fn set_background(&mut self, background: BoxBackground) {
+        if self.background == background {
+            return;
+        }
+        self.background = background;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the background and the border of the box.
+    pub fn background(&self) -> BoxBackground {
+        self.background
+    }
+
+    /// Sets the padding of the box. The horizontal padding reduces the width available for the text.
+    pub fn set_padding(&mut self, padding: Padding) {
+        if self.padding == padding {
+            return;
+        }
+        self.padding = padding;
+        self.update_max_advance();
+        self.mark_hit_bounds_dirty();
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the padding of the box.
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    /// Recomputes the width available for the text from the size and the padding.
+    pub(crate) fn update_max_advance(&mut self) {
+        let width = self.shrink_to_fit.map_or(self.width, |(width, _)| width);
+        let max_advance = (width - self.padding.left - self.padding.right).max(0.0);
+        if self.max_advance != max_advance {
+            self.max_advance = max_advance;
+            self.needs_relayout = true;
+            self.render_data_info.cache_generation = 0;
+        }
+    }
+
+    /// Returns the position of the layout's origin in the local space of the box.
+    pub(crate) fn content_offset(&self) -> (f32, f32) {
+        (self.padding.left, self.padding.top + self.vertical_align_offset())
+    }
+
+    /// Returns how far down the layout is moved by the vertical alignment, from the top of the content area.
+    pub(crate) fn vertical_align_offset(&self) -> f32 {
+        let free_space = (self.content_height() - self.layout.height()).max(0.0);
+        match self.vertical_alignment {
+            VerticalAlignment::Top => 0.0,
+            VerticalAlignment::Center => (free_space / 2.0).round(),
+            VerticalAlignment::Bottom => free_space,
+            VerticalAlignment::Baseline(baseline) => {
+                let first_baseline = self.layout.lines().next().map(|line| line.metrics().baseline).unwrap_or(0.0);
+                baseline - first_baseline
+            }
+        }
+    }
+
+    /// Returns the height available for the text, inside the vertical padding.
+    pub(crate) fn content_height(&self) -> f32 {
+        (self.height - self.padding.top - self.padding.bottom).max(0.0)
+    }
+
+    /// Converts a position in the local space of the box to layout coordinates, accounting for the padding and the scroll.
+    pub(crate) fn local_to_layout(&self, local_pos: (f32, f32)) -> (f32, f32) {
+        let (offset_x, offset_y) = self.content_offset();
+        if let Some(path) = &self.flattened_path {
+            return self.path_point_to_layout(path, (local_pos.0 - offset_x, local_pos.1 - offset_y));
+        }
+        (local_pos.0 - offset_x + self.scroll_offset.0, local_pos.1 - offset_y + self.scroll_offset.1)
+    }
+
+    /// Converts a screen-space position to layout coordinates, undoing the transform, the padding and the scroll.
+    pub(crate) fn screen_to_layout(&self, screen_pos: (f32, f32)) -> (f32, f32) {
+        let inv_transform = self.frame_transform().inverse().unwrap_or(Transform2D::identity());
+        let local_pos = inv_transform.transform_point(euclid::Point2D::new(screen_pos.0, screen_pos.1));
+        self.local_to_layout((local_pos.x, local_pos.y))
+    }
+}
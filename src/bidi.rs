@@ -0,0 +1,384 @@
+use parley::Affinity;
+
+use crate::*;
+
+/// Paragraph direction of a box. See [`TextBox::set_base_direction()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BaseDirection {
+    /// Each paragraph takes the direction of its first strong character, as in the Unicode bidi algorithm.
+    #[default]
+    Auto,
+    /// All paragraphs are left-to-right.
+    Ltr,
+    /// All paragraphs are right-to-left.
+    Rtl,
+}
+
+/// What the left and right arrow keys do in mixed-direction text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CursorMovement {
+    /// The cursor moves on the screen in the direction of the arrow, even when that jumps around in the text.
+    #[default]
+    Visual,
+    /// The cursor moves to the next or previous character in the text. In a right-to-left paragraph the left arrow moves forward.
+    Logical,
+}
+
+const LEFT_TO_RIGHT_MARK: &str = "\u{200E}";
+const RIGHT_TO_LEFT_MARK: &str = "\u{200F}";
+/// Both marks are encoded with the same number of bytes.
+pub(crate) const DIRECTION_MARK_LEN: usize = LEFT_TO_RIGHT_MARK.len();
+
+/// Parley picks the direction of each paragraph from its first strong character, so a forced direction is implemented by starting every paragraph of the layout with an invisible direction mark.
+///
+/// Returns the positions where the marks go in `text`, which is the text before the marks are inserted.
+pub(crate) fn direction_mark_positions(text: &str, base_direction: BaseDirection) -> Vec<usize> {
+    match base_direction {
+        BaseDirection::Auto => Vec::new(),
+        BaseDirection::Ltr | BaseDirection::Rtl => paragraph_starts(text),
+    }
+}
+
+/// Returns `text` with a direction mark at each of the `positions`.
+pub(crate) fn insert_direction_marks(text: &str, positions: &[usize], base_direction: BaseDirection) -> String {
+    let mark = match base_direction {
+        BaseDirection::Rtl => RIGHT_TO_LEFT_MARK,
+        _ => LEFT_TO_RIGHT_MARK,
+    };
+    let mut result = String::with_capacity(text.len() + positions.len() * DIRECTION_MARK_LEN);
+    let mut last = 0;
+    for &position in positions {
+        result.push_str(&text[last..position]);
+        result.push_str(mark);
+        last = position;
+    }
+    result.push_str(&text[last..]);
+    return result;
+}
+
+/// Maps an index in the text without marks to the text with marks. An index at a paragraph start goes after the mark, so that text typed there is inside the paragraph.
+pub(crate) fn index_with_direction_marks(positions: &[usize], index: usize) -> usize {
+    index + DIRECTION_MARK_LEN * positions.partition_point(|&position| position <= index)
+}
+
+/// Maps an index in the text with marks back to the text without marks.
+pub(crate) fn index_without_direction_marks(positions: &[usize], index: usize) -> usize {
+    let marks_before = positions.iter()
+        .enumerate()
+        .take_while(|(i, position)| *position + DIRECTION_MARK_LEN * (i + 1) <= index)
+        .count();
+    index - DIRECTION_MARK_LEN * marks_before
+}
+
+impl TextBox {
+    /// Sets the paragraph direction of the box.
+    ///
+    /// This decides which side the lines start from and how runs of different directions are ordered inside a line. With [`BaseDirection::Auto`], a paragraph that starts with Hebrew or Arabic is laid out right-to-left even if most of it is Latin text.
+    pub fn set_base_direction(&mut self, base_direction: BaseDirection) {
+        if self.base_direction == base_direction {
+            return;
+        }
+        self.base_direction = base_direction;
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the paragraph direction of the box.
+    pub fn base_direction(&self) -> BaseDirection {
+        self.base_direction
+    }
+
+    /// Sets what the left and right arrow keys do in mixed-direction text.
+    pub fn set_cursor_movement(&mut self, cursor_movement: CursorMovement) {
+        self.cursor_movement = cursor_movement;
+    }
+
+    /// Returns what the left and right arrow keys do in mixed-direction text.
+    pub fn cursor_movement(&self) -> CursorMovement {
+        self.cursor_movement
+    }
+
+    /// Returns the positions of the direction marks for `text`, which is the text shown by the box, and the direction of the marks.
+    ///
+    /// A box of a text thread that starts in the middle of a paragraph keeps the direction that the paragraph has in the thread, so with [`BaseDirection::Auto`] its first paragraph gets a mark too.
+    pub(crate) fn direction_marks_for(&self, text: &str) -> (Vec<usize>, BaseDirection) {
+        match (self.base_direction, self.continued_paragraph) {
+            (BaseDirection::Auto, Some(continued)) => {
+                let direction = if continued.is_rtl { BaseDirection::Rtl } else { BaseDirection::Ltr };
+                (vec![0], direction)
+            }
+            (base_direction, _) => (direction_mark_positions(text, base_direction), base_direction),
+        }
+    }
+
+    /// Returns `true` if the paragraph containing the byte `index` of the text is laid out right-to-left.
+    pub(crate) fn is_rtl_at(&self, index: usize) -> bool {
+        match (self.base_direction, self.continued_paragraph) {
+            (BaseDirection::Ltr, _) => false,
+            (BaseDirection::Rtl, _) => true,
+            (BaseDirection::Auto, Some(continued)) if !self.text[..index].contains('\n') => continued.is_rtl,
+            (BaseDirection::Auto, _) => paragraph_is_rtl(&self.text, index),
+        }
+    }
+
+    /// Returns, for each paragraph of the text, `true` if it's laid out right-to-left. `starts` are the paragraph starts from [`paragraph_starts()`].
+    pub(crate) fn paragraph_directions(&self, starts: &[usize]) -> Vec<bool> {
+        (0..starts.len())
+            .map(|paragraph| match (self.base_direction, self.continued_paragraph) {
+                (BaseDirection::Ltr, _) => false,
+                (BaseDirection::Rtl, _) => true,
+                (BaseDirection::Auto, Some(continued)) if paragraph == 0 => continued.is_rtl,
+                (BaseDirection::Auto, _) => {
+                    let end = starts.get(paragraph + 1).copied().unwrap_or(self.text.len());
+                    self.text[starts[paragraph]..end].chars().find_map(strong_direction_is_rtl).unwrap_or(false)
+                }
+            })
+            .collect()
+    }
+
+    /// Maps a byte index in the text to the layout.
+    pub(crate) fn text_to_layout_index(&self, index: usize) -> usize {
+        self.text_to_layout_range(&(index..index)).start
+    }
+
+    /// Returns a cursor at a byte index in the text.
+    ///
+    /// Right after a tab with a tab stop, the cursor is always placed after the space of the tab. See [`TextBox::snap_selection_to_tab_boxes()`].
+    pub(crate) fn cursor_at(&self, index: usize, affinity: Affinity) -> TextCursor {
+        let affinity = if self.is_after_tab(index) { Affinity::Downstream } else { affinity };
+        TextCursor::from_byte_index(&self.layout, self.text_to_layout_index(index), affinity)
+    }
+
+    /// Returns the selected byte range in the text.
+    pub(crate) fn selection_text_range(&self) -> std::ops::Range<usize> {
+        self.layout_to_text_range(self.selection.text_range())
+    }
+
+    /// Moves the selection focus one cluster to the left or to the right, according to the cursor movement mode.
+    pub(crate) fn step_horizontally(&self, selection: TextSelection, right: bool, extend: bool) -> TextSelection {
+        match self.cursor_movement {
+            CursorMovement::Visual => {
+                if right {
+                    selection.next_visual(&self.layout, extend)
+                } else {
+                    selection.previous_visual(&self.layout, extend)
+                }
+            }
+            CursorMovement::Logical => {
+                let is_rtl = self.is_rtl_at(self.layout_to_text_index(selection.focus().index()));
+                let forward = right != is_rtl;
+                let clusters = selection.focus().logical_clusters(&self.layout);
+                let target = if forward {
+                    clusters[1].as_ref().map(|cluster| (cluster.text_range().end, Affinity::Upstream))
+                } else {
+                    clusters[0].as_ref().map(|cluster| (cluster.text_range().start, Affinity::Downstream))
+                };
+                let Some((index, affinity)) = target else {
+                    return selection;
+                };
+                let focus = TextCursor::from_byte_index(&self.layout, index, affinity);
+                if extend {
+                    TextSelection::new(selection.anchor(), focus)
+                } else {
+                    focus.into()
+                }
+            }
+        }
+    }
+
+    /// Returns the caret rects for the current selection focus.
+    ///
+    /// At a boundary between runs of different directions, the text before and after the cursor are in different places on the screen. In that case there are two rects: the caret where text typed now would go, on the top half of the line, and the other side of the boundary, on the bottom half.
+    pub(crate) fn caret_rects(&self, width: f32) -> ([BoundingBox; 2], bool) {
+        let focus = self.selection.focus();
+        let primary = focus.geometry(&self.layout, width);
+
+        let other_affinity = match focus.affinity() {
+            Affinity::Downstream => Affinity::Upstream,
+            Affinity::Upstream => Affinity::Downstream,
+        };
+        let secondary = TextCursor::from_byte_index(&self.layout, focus.index(), other_affinity).geometry(&self.layout, width);
+
+        let is_split = (primary.x0 - secondary.x0).abs() > 0.5 && (primary.y0 - secondary.y0).abs() < 0.5;
+        if !is_split {
+            return ([primary, primary], false);
+        }
+
+        let middle = (primary.y0 + primary.y1) / 2.0;
+        let top = BoundingBox { y1: middle, ..primary };
+        let bottom = BoundingBox { y0: middle, ..secondary };
+        return ([top, bottom], true);
+    }
+}
+
+/// Returns `true` if the paragraph containing the byte `index` of `text` is right-to-left, deciding it from the first strong character of the paragraph like the Unicode bidi algorithm. A paragraph without strong characters is left-to-right.
+pub(crate) fn paragraph_is_rtl(text: &str, index: usize) -> bool {
+    let starts = paragraph_starts(text);
+    let paragraph = starts.partition_point(|&start| start <= index) - 1;
+    let end = starts.get(paragraph + 1).copied().unwrap_or(text.len());
+    return text[starts[paragraph]..end].chars().find_map(strong_direction_is_rtl).unwrap_or(false);
+}
+
+/// Returns `Some(true)` for a strong right-to-left character, `Some(false)` for a strong left-to-right one, and `None` for neutral and weak characters.
+///
+/// This approximates the Bidi_Class property: letters in the right-to-left script blocks (Hebrew, Arabic, Syriac, Thaana, N'Ko and the presentation forms) are right-to-left, all other letters are left-to-right.
+fn strong_direction_is_rtl(c: char) -> Option<bool> {
+    match c as u32 {
+        0x200E => Some(false),
+        0x200F => Some(true),
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x10800..=0x10FFF | 0x1E800..=0x1EFFF => c.is_alphabetic().then_some(true),
+        _ => c.is_alphabetic().then_some(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::NonNull;
+
+    use super::*;
+
+    const MIXED: &str = "abc אבג def";
+
+    fn text_box(shared: &mut Shared, text: &str, base_direction: BaseDirection, cursor_movement: CursorMovement) -> TextBox {
+        let default_style_key = shared.default_style_key;
+        let mut text_box = TextBox::new(text.to_string(), (0.0, 0.0), (1000.0, 1000.0), 0.0, default_style_key, NonNull::from(shared));
+        text_box.set_base_direction(base_direction);
+        text_box.set_cursor_movement(cursor_movement);
+        text_box.refresh_layout();
+        return text_box;
+    }
+
+    fn step(text_box: &TextBox, index: usize, right: bool) -> usize {
+        let selection: TextSelection = text_box.cursor_at(index, Affinity::Downstream).into();
+        let selection = text_box.step_horizontally(selection, right, false);
+        return text_box.layout_to_text_index(selection.focus().index());
+    }
+
+    #[test]
+    fn marks_go_at_paragraph_starts() {
+        let positions = direction_mark_positions("ab\ncd", BaseDirection::Rtl);
+        assert_eq!(positions, vec![0, 3]);
+        assert_eq!(insert_direction_marks("ab\ncd", &positions, BaseDirection::Rtl), "\u{200F}ab\n\u{200F}cd");
+        assert_eq!(insert_direction_marks("ab\ncd", &positions, BaseDirection::Ltr), "\u{200E}ab\n\u{200E}cd");
+        assert!(direction_mark_positions("ab\ncd", BaseDirection::Auto).is_empty());
+    }
+
+    #[test]
+    fn indices_round_trip_through_marks() {
+        let text = "ab\n\ncd\n";
+        let positions = direction_mark_positions(text, BaseDirection::Ltr);
+        for index in 0..=text.len() {
+            let with_marks = index_with_direction_marks(&positions, index);
+            assert_eq!(index_without_direction_marks(&positions, with_marks), index);
+        }
+    }
+
+    #[test]
+    fn paragraph_start_maps_after_its_mark() {
+        // "|ab\n|cd" with marks at 0 and 3.
+        let positions = vec![0, 3];
+        assert_eq!(index_with_direction_marks(&positions, 0), DIRECTION_MARK_LEN);
+        assert_eq!(index_with_direction_marks(&positions, 3), 3 + 2 * DIRECTION_MARK_LEN);
+        // Both sides of the second mark map to the start of the second paragraph.
+        assert_eq!(index_without_direction_marks(&positions, 3 + DIRECTION_MARK_LEN), 3);
+        assert_eq!(index_without_direction_marks(&positions, 3 + 2 * DIRECTION_MARK_LEN), 3);
+    }
+
+    #[test]
+    fn paragraph_direction_comes_from_first_strong_character() {
+        let text = "abc\n123 שלום abc\n!!!";
+        assert!(!paragraph_is_rtl(text, 0));
+        assert!(paragraph_is_rtl(text, 4));
+        // The end of the Latin word is still in the right-to-left paragraph.
+        assert!(paragraph_is_rtl(text, "abc\n123 שלום ab".len()));
+        // No strong characters.
+        assert!(!paragraph_is_rtl(text, text.len()));
+        assert!(!paragraph_is_rtl("", 0));
+    }
+
+    #[test]
+    fn logical_and_visual_movement_differ_in_mixed_text() {
+        let mut shared = Shared::new();
+        let hebrew_start = "abc ".len();
+
+        let logical = text_box(&mut shared, MIXED, BaseDirection::Auto, CursorMovement::Logical);
+        assert_eq!(step(&logical, hebrew_start, true), hebrew_start + "א".len());
+        assert_eq!(step(&logical, hebrew_start + "א".len(), false), hebrew_start);
+
+        let visual = text_box(&mut shared, MIXED, BaseDirection::Auto, CursorMovement::Visual);
+        assert_ne!(step(&visual, hebrew_start, true), hebrew_start + "א".len());
+    }
+
+    #[test]
+    fn logical_movement_uses_paragraph_direction_inside_embedded_run() {
+        let mut shared = Shared::new();
+        let text = "אב abc";
+        let inside_latin = "אב a".len();
+
+        // The paragraph is right-to-left, so the right arrow moves backward even between two Latin letters.
+        let auto = text_box(&mut shared, text, BaseDirection::Auto, CursorMovement::Logical);
+        assert_eq!(step(&auto, inside_latin, true), inside_latin - 1);
+        assert_eq!(step(&auto, inside_latin, false), inside_latin + 1);
+
+        let ltr = text_box(&mut shared, text, BaseDirection::Ltr, CursorMovement::Logical);
+        assert_eq!(step(&ltr, inside_latin, true), inside_latin + 1);
+    }
+
+    #[test]
+    fn caret_splits_at_direction_boundary() {
+        let mut shared = Shared::new();
+        let mut text_box = text_box(&mut shared, MIXED, BaseDirection::Auto, CursorMovement::Visual);
+
+        text_box.set_selection(text_box.cursor_at("abc ".len(), Affinity::Downstream).into());
+        let ([top, bottom], is_split) = text_box.caret_rects(2.0);
+        assert!(is_split);
+        assert!(top.y1 <= bottom.y0 + 0.01);
+        assert!((top.x0 - bottom.x0).abs() > 0.5);
+
+        text_box.set_selection(text_box.cursor_at(1, Affinity::Downstream).into());
+        let ([top, bottom], is_split) = text_box.caret_rects(2.0);
+        assert!(!is_split);
+        assert_eq!(top, bottom);
+    }
+
+    #[test]
+    fn text_edit_selection_in_mixed_text() {
+        let mut shared = Shared::new();
+        let default_style_key = shared.default_style_key;
+        let mut text_edit = TextEdit::new(MIXED.to_string(), (0.0, 0.0), (1000.0, 1000.0), 0.0, default_style_key, NonNull::from(&mut shared));
+        text_edit.text_box.set_cursor_movement(CursorMovement::Logical);
+        text_edit.refresh_layout();
+
+        let mut selection: TextSelection = text_edit.text_box.cursor_at(0, Affinity::Downstream).into();
+        for _ in 0.."abc א".chars().count() {
+            selection = text_edit.text_box.step_horizontally(selection, true, true);
+        }
+        text_edit.text_box.set_selection(selection);
+        assert_eq!(text_edit.selected_text(), Some("abc א"));
+
+        text_edit.insert_or_replace_selection("x");
+        assert_eq!(text_edit.raw_text(), "xבג def");
+    }
+
+    #[test]
+    fn text_edit_selection_skips_direction_marks() {
+        let mut shared = Shared::new();
+        let default_style_key = shared.default_style_key;
+        let mut text_edit = TextEdit::new("abc\ndef".to_string(), (0.0, 0.0), (1000.0, 1000.0), 0.0, default_style_key, NonNull::from(&mut shared));
+        text_edit.set_base_direction(BaseDirection::Rtl);
+        text_edit.text_box.set_cursor_movement(CursorMovement::Logical);
+        text_edit.refresh_layout();
+
+        // In a right-to-left paragraph the right arrow moves backward, from the end of "def" to the start of the paragraph.
+        let mut selection: TextSelection = text_edit.text_box.cursor_at("abc\ndef".len(), Affinity::Downstream).into();
+        for _ in 0..3 {
+            selection = text_edit.text_box.step_horizontally(selection, true, true);
+        }
+        text_edit.text_box.set_selection(selection);
+        assert_eq!(text_edit.selected_text(), Some("def"));
+
+        text_edit.insert_or_replace_selection("x");
+        assert_eq!(text_edit.raw_text(), "abc\nx");
+    }
+}
@@ -0,0 +1,125 @@
+use crate::*;
+
+/// A shape that text flows around. See [`TextBox::set_exclusions()`].
+///
+/// Shapes are in the local space of the box, like the rects from [`TextBox::inline_box_rect()`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exclusion {
+    /// A rectangle.
+    Rect(BoundingBox),
+    /// A circle.
+    Circle {
+        center: (f32, f32),
+        radius: f32,
+    },
+}
+
+/// A line is moved down past the exclusions if the widest free space next to them is narrower than the line is tall, times this factor.
+const MIN_FREE_WIDTH_IN_LINE_HEIGHTS: f32 = 2.0;
+
+/// How many times a line can be moved down past exclusions before it's placed over them anyway.
+const MAX_EXCLUSION_STEPS: usize = 16;
+
+impl Exclusion {
+    /// Returns the horizontal range that the shape blocks between `y0` and `y1`, or `None` if it doesn't reach between them.
+    fn blocked_range(&self, y0: f32, y1: f32) -> Option<(f32, f32)> {
+        match *self {
+            Exclusion::Rect(rect) => {
+                let overlaps = (rect.y0 as f32) < y1 && (rect.y1 as f32) > y0;
+                overlaps.then_some((rect.x0 as f32, rect.x1 as f32))
+            }
+            Exclusion::Circle { center, radius } => {
+                // The widest part of the circle between y0 and y1 is at the y closest to the center.
+                let dy = center.1 - center.1.clamp(y0, y1);
+                let half_width_squared = radius * radius - dy * dy;
+                if half_width_squared <= 0.0 {
+                    return None;
+                }
+                let half_width = half_width_squared.sqrt();
+                Some((center.0 - half_width, center.0 + half_width))
+            }
+        }
+    }
+
+    fn bottom(&self) -> f32 {
+        match *self {
+            Exclusion::Rect(rect) => rect.y1 as f32,
+            Exclusion::Circle { center, radius } => center.1 + radius,
+        }
+    }
+}
+
+impl TextBox {
+    /// Sets the shapes that the text flows around.
+    ///
+    /// Each line is placed in the widest space left free by the shapes at its height. If that space is too narrow, the line is moved down below the shapes. The shapes aren't drawn.
+    ///
+    /// The shapes are fixed to the text, so they scroll with it.
+    pub fn set_exclusions(&mut self, exclusions: Vec<Exclusion>) {
+        if self.exclusions == exclusions {
+            return;
+        }
+        self.exclusions = exclusions;
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the shapes that the text flows around.
+    pub fn exclusions(&self) -> &[Exclusion] {
+        &self.exclusions
+    }
+
+    /// Finds where a line of the given height fits between `left` and `right`, starting at `y`. All values are in layout coordinates.
+    ///
+    /// Returns the x position, the available width and the y position of the line, which is moved down if there's no room at `y`.
+    pub(crate) fn exclusion_free_span(&self, mut y: f32, height: f32, left: f32, right: f32) -> (f32, f32, f32) {
+        let full_span = (left, (right - left).max(0.0), y);
+        if self.exclusions.is_empty() {
+            return full_span;
+        }
+        // The shapes are in local space, and the layout starts at the padding.
+        let (dx, dy) = (self.padding.left, self.padding.top);
+        let min_width = height * MIN_FREE_WIDTH_IN_LINE_HEIGHTS;
+
+        for _ in 0..MAX_EXCLUSION_STEPS {
+            let (y0, y1) = (y + dy, y + height + dy);
+            let mut blocked: Vec<(f32, f32)> = self.exclusions.iter()
+                .filter_map(|exclusion| exclusion.blocked_range(y0, y1))
+                .map(|(x0, x1)| (x0 - dx, x1 - dx))
+                .collect();
+            if blocked.is_empty() {
+                return (left, (right - left).max(0.0), y);
+            }
+            blocked.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            // Widest gap between the blocked ranges.
+            let mut best = (left, 0.0);
+            let mut cursor = left;
+            for (x0, x1) in blocked.iter().copied().chain(std::iter::once((right, right))) {
+                let gap_end = x0.min(right);
+                if gap_end - cursor > best.1 {
+                    best = (cursor, gap_end - cursor);
+                }
+                cursor = cursor.max(x1);
+            }
+
+            if best.1 >= min_width {
+                return (best.0, best.1, y);
+            }
+
+            // Not enough room here: move the line below the first shape that ends.
+            let next_y = self.exclusions.iter()
+                .filter(|exclusion| exclusion.blocked_range(y0, y1).is_some())
+                .map(|exclusion| exclusion.bottom() - dy)
+                .filter(|&bottom| bottom > y)
+                .min_by(f32::total_cmp);
+            match next_y {
+                Some(next_y) => y = next_y,
+                None => break,
+            }
+        }
+
+        return (full_span.0, full_span.1, y);
+    }
+}
@@ -0,0 +1,106 @@
+use parley::Layout;
+
+use crate::*;
+
+/// Range of font sizes for [`TextBox::set_font_size_fit()`].
+///
+/// The sizes are in the same units as the `font_size` of a [`TextStyle2`]. They apply to the style of the box, and the styles of the spans are scaled by the same amount.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FontSizeFit {
+    /// Smallest font size. If the text doesn't fit even at this size, it overflows normally.
+    pub min_size: f32,
+    /// Largest font size.
+    pub max_size: f32,
+}
+
+/// Font sizes closer than this are considered the same when searching.
+const FONT_SIZE_FIT_PRECISION: f32 = 0.25;
+
+impl TextBox {
+    /// Enables or disables automatic font sizing.
+    ///
+    /// When enabled, the font size is chosen as the largest size in the range for which the text fits in the box, both horizontally and vertically, and within the max lines, if set. The search is redone only when the layout needs to be rebuilt anyway, for example when the text, the style or the size changes.
+    pub fn set_font_size_fit(&mut self, fit: Option<FontSizeFit>) {
+        if self.font_size_fit == fit {
+            return;
+        }
+        self.font_size_fit = fit;
+        if fit.is_none() {
+            self.font_fit_scale = 1.0;
+        }
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the automatic font sizing range, if enabled.
+    pub fn font_size_fit(&self) -> Option<FontSizeFit> {
+        self.font_size_fit
+    }
+
+    /// Returns the font size chosen by [`TextBox::set_font_size_fit()`], or the size from the style if it's not enabled.
+    pub fn fitted_font_size(&mut self) -> f32 {
+        self.refresh_layout();
+        self.base_font_size() * self.font_fit_scale
+    }
+
+    fn base_font_size(&self) -> f32 {
+        self.shared().styles[self.style.key].text_style.font_size
+    }
+
+    /// Chooses `font_fit_scale` with a binary search over the font size, building a layout for each try.
+    ///
+    /// Returns the layout for the chosen size, so that it doesn't need to be built again.
+    pub(crate) fn fit_font_size(&mut self, color_override: Option<ColorBrush>, single_line: bool) -> Option<Layout<ColorBrush>> {
+        let fit = self.font_size_fit?;
+        let base_size = self.base_font_size();
+        if base_size <= 0.0 {
+            return None;
+        }
+
+        let max_lines = self.max_lines.unwrap_or(usize::MAX).max(1);
+        let max_advance = self.max_advance;
+        let content_height = self.content_height();
+        let fits = |layout: &Layout<ColorBrush>| {
+            layout.len() <= max_lines
+                && layout.width() <= max_advance + 0.5
+                && layout.height() <= content_height + 0.5
+        };
+
+        let try_size = |text_box: &mut Self, size: f32| {
+            text_box.font_fit_scale = size / base_size;
+            let layout = text_box.build_layout(None, color_override, single_line);
+            (fits(&layout), layout)
+        };
+
+        let min_size = fit.min_size.max(0.0);
+        let max_size = fit.max_size.max(min_size);
+
+        let (max_fits, layout) = try_size(self, max_size);
+        if max_fits {
+            return Some(layout);
+        }
+
+        let mut low = min_size;
+        let mut high = max_size;
+        let mut best = None;
+        while high - low > FONT_SIZE_FIT_PRECISION {
+            let mid = (low + high) / 2.0;
+            let (mid_fits, layout) = try_size(self, mid);
+            if mid_fits {
+                low = mid;
+                best = Some(layout);
+            } else {
+                high = mid;
+            }
+        }
+
+        if best.is_none() {
+            // Nothing fits: use the min size and let the text overflow.
+            let (_, layout) = try_size(self, low);
+            best = Some(layout);
+        }
+        self.font_fit_scale = low / base_size;
+        return best;
+    }
+}
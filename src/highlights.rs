@@ -0,0 +1,80 @@
+use std::ops::Range;
+
+use parley::Affinity;
+
+use crate::*;
+
+/// Handle for a highlight added with [`TextBox::add_highlight()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HighlightId(pub(crate) u64);
+
+/// A range of text painted with a background color, like a search hit.
+///
+/// Highlights are drawn behind the text, while the selection is drawn on top of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight {
+    /// Id returned by [`TextBox::add_highlight()`].
+    pub id: HighlightId,
+    /// Byte range in the text of the box. Kept in sync with the text when edited through a [`TextEdit`].
+    pub range: Range<usize>,
+    /// Color of the highlight rects.
+    pub color: ColorBrush,
+}
+
+impl TextBox {
+    /// Highlights a byte range of the text with a background color, and returns an id that can be used to remove it.
+    ///
+    /// When the text is edited, the range moves with it. If all of the highlighted text is deleted, the highlight stays around with an empty range, and it draws nothing.
+    pub fn add_highlight(&mut self, range: Range<usize>, color: ColorBrush) -> HighlightId {
+        let id = HighlightId(self.next_highlight_id);
+        self.next_highlight_id += 1;
+        self.highlights.push(Highlight { id, range, color });
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+        return id;
+    }
+
+    /// Removes a highlight.
+    pub fn remove_highlight(&mut self, id: HighlightId) {
+        let len = self.highlights.len();
+        self.highlights.retain(|h| h.id != id);
+        if self.highlights.len() != len {
+            self.shared_mut().rebuild_glyph_quad_buffer = true;
+        }
+    }
+
+    /// Removes all highlights.
+    pub fn clear_highlights(&mut self) {
+        if self.highlights.is_empty() {
+            return;
+        }
+        self.highlights.clear();
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the highlights of the text box, in the order they were added. Later highlights are drawn on top.
+    pub fn highlights(&self) -> &[Highlight] {
+        &self.highlights
+    }
+
+    /// Calls `f` with the rects covering a highlight, in layout coordinates.
+    pub(crate) fn highlight_geometry_with(&self, highlight: &Highlight, mut f: impl FnMut(BoundingBox)) {
+        let range = &highlight.range;
+        let is_valid = range.start < range.end
+            && range.end <= self.text.len()
+            && self.text.is_char_boundary(range.start)
+            && self.text.is_char_boundary(range.end);
+        if !is_valid {
+            return;
+        }
+        let range = self.text_to_layout_range(range);
+        if range.is_empty() {
+            return;
+        }
+
+        let selection = TextSelection::new(
+            TextCursor::from_byte_index(&self.layout, range.start, Affinity::Downstream),
+            TextCursor::from_byte_index(&self.layout, range.end, Affinity::Upstream),
+        );
+        selection.geometry_with(&self.layout, |rect, _line_i| f(rect));
+    }
+}
@@ -0,0 +1,273 @@
+use parley::{InlineBox, InlineBoxKind, LineHeight, PositionedLayoutItem};
+
+use crate::*;
+
+/// Inline box ids with this bit set are used internally for inline images. User ids for [`TextBox::add_inline_box()`] should leave it unset.
+pub const INLINE_IMAGE_ID_FLAG: u64 = 1 << 63;
+
+/// Handle for an image registered with [`Text::add_image()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ImageId(pub(crate) u32);
+
+/// An image placed inside the text of a box. See [`TextBox::add_inline_image()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineImage {
+    /// The image to draw.
+    pub image: ImageId,
+    /// Byte offset in the text where the image is placed.
+    pub index: usize,
+    /// Text that stands in for the image when the selection is copied.
+    pub alt_text: Cow<'static, str>,
+}
+
+/// A rectangle of space reserved inside the flow of the text, for embedding external content like buttons or icons.
+///
+/// The text flows around the box as if it was a single big glyph. After the layout is built, the position of the box can be queried with [`TextBox::inline_box_rect()`] or [`TextBox::inline_box_screen_rect()`], and the content can be drawn there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InlineBoxSlot {
+    /// User-chosen id, used to query the position of the box.
+    pub id: u64,
+    /// Byte offset in the text where the box is placed. It must be on a char boundary.
+    pub index: usize,
+    /// Width of the box, in the same units as the size of the text box.
+    pub width: f32,
+    /// Height of the box, in the same units as the size of the text box.
+    pub height: f32,
+}
+
+impl TextBox {
+    /// Inserts an inline box at the byte offset `index`, or moves and resizes the existing one with the same `id`.
+    pub fn add_inline_box(&mut self, id: u64, index: usize, size: (f32, f32)) {
+        let slot = InlineBoxSlot { id, index, width: size.0, height: size.1 };
+        if let Some(existing) = self.inline_boxes.iter_mut().find(|b| b.id == id) {
+            if *existing == slot {
+                return;
+            }
+            *existing = slot;
+        } else {
+            self.inline_boxes.push(slot);
+        }
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Removes the inline box with the given id.
+    pub fn remove_inline_box(&mut self, id: u64) {
+        let len = self.inline_boxes.len();
+        self.inline_boxes.retain(|b| b.id != id);
+        if self.inline_boxes.len() == len {
+            return;
+        }
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Removes all inline boxes.
+    pub fn clear_inline_boxes(&mut self) {
+        if self.inline_boxes.is_empty() {
+            return;
+        }
+        self.inline_boxes.clear();
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the inline boxes of the text box.
+    pub fn inline_boxes(&self) -> &[InlineBoxSlot] {
+        &self.inline_boxes
+    }
+
+    /// Returns the rect of an inline box in the local space of the text box, after scrolling and padding but before the transform.
+    ///
+    /// The position is resolved when the layout is rebuilt, so this should be called after [`Text::prepare_all()`]. Returns `None` if there is no box with this id, or if it wasn't placed.
+    pub fn inline_box_rect(&self, id: u64) -> Option<BoundingBox> {
+        let (_, rect) = self.inline_box_rects.iter().find(|(box_id, _)| *box_id == id)?;
+        let (offset_x, offset_y) = self.content_offset();
+        let dx = (offset_x - self.scroll_offset.0) as f64;
+        let dy = (offset_y - self.scroll_offset.1) as f64;
+        return Some(BoundingBox {
+            x0: rect.x0 + dx,
+            y0: rect.y0 + dy,
+            x1: rect.x1 + dx,
+            y1: rect.y1 + dy,
+        });
+    }
+
+    /// Returns the screen-space bounding box of an inline box.
+    ///
+    /// If the text box is rotated, this is the axis-aligned box containing the rotated rect.
+    pub fn inline_box_screen_rect(&self, id: u64) -> Option<BoundingBox> {
+        let rect = self.inline_box_rect(id)?;
+        return Some(self.local_rect_to_screen(rect));
+    }
+
+    /// Returns the axis-aligned screen-space bounds of a rect in the local space of the box.
+    pub(crate) fn local_rect_to_screen(&self, rect: BoundingBox) -> BoundingBox {
+        let transform = self.frame_transform();
+        let corners = [
+            (rect.x0, rect.y0),
+            (rect.x1, rect.y0),
+            (rect.x0, rect.y1),
+            (rect.x1, rect.y1),
+        ].map(|(x, y)| transform.transform_point(euclid::Point2D::new(x as f32, y as f32)));
+
+        let mut bounds = BoundingBox { x0: f64::MAX, y0: f64::MAX, x1: f64::MIN, y1: f64::MIN };
+        for corner in corners {
+            bounds.x0 = bounds.x0.min(corner.x as f64);
+            bounds.y0 = bounds.y0.min(corner.y as f64);
+            bounds.x1 = bounds.x1.max(corner.x as f64);
+            bounds.y1 = bounds.y1.max(corner.y as f64);
+        }
+        return bounds;
+    }
+
+    /// Places an image at the byte offset `index`. The image is scaled to the line height, keeping its aspect ratio.
+    /// 
+    /// When a selection containing the image is copied, `alt_text` is copied in its place.
+    pub fn add_inline_image(&mut self, index: usize, image: ImageId, alt_text: impl Into<Cow<'static, str>>) {
+        self.inline_images.push(InlineImage { image, index, alt_text: alt_text.into() });
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Removes all inline images.
+    pub fn clear_inline_images(&mut self) {
+        if self.inline_images.is_empty() {
+            return;
+        }
+        self.inline_images.clear();
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the inline images of the text box.
+    pub fn inline_images(&self) -> &[InlineImage] {
+        &self.inline_images
+    }
+
+    pub(crate) fn inline_image_for_box_id(&self, id: u64) -> Option<ImageId> {
+        if id & INLINE_IMAGE_ID_FLAG == 0 {
+            return None;
+        }
+        let i = (id & !INLINE_IMAGE_ID_FLAG) as usize;
+        return self.inline_images.get(i).map(|image| image.image);
+    }
+
+    /// Returns the height of the line at a byte index, as far as it can be known before building the layout.
+    pub(crate) fn line_height_at(&self, index: usize) -> f32 {
+        let style = &self.shared().styles[self.style_key_at(index)].text_style;
+        let line_height = match style.line_height {
+            LineHeight::Absolute(height) => height,
+            LineHeight::FontSizeRelative(factor) => factor * style.font_size,
+            // The real metrics are only known after shaping. The font size is close enough, and it's what emoji use.
+            LineHeight::MetricsRelative(factor) => factor * style.font_size,
+        };
+        return line_height * self.get_scale_factor() as f32 * self.font_fit_scale;
+    }
+
+    /// Returns the selected text, with the alt text of the selected inline images in place of the images.
+    /// 
+    /// An image counts as selected if the text on both sides of it is selected, or if the selection reaches the edge of the text on its side.
+    pub fn selected_text_for_copy(&self) -> Option<Cow<'_, str>> {
+        if self.virtual_lines.is_some() {
+            return self.virtual_selected_text().map(Cow::Owned);
+        }
+        let selected = self.selected_text()?;
+        if self.inline_images.is_empty() {
+            return Some(Cow::Borrowed(selected));
+        }
+
+        let range = self.layout_to_text_range(self.selection.text_range());
+        let text_len = self.text.len();
+        let mut images: Vec<&InlineImage> = self.inline_images.iter()
+            .filter(|image| {
+                (range.start < image.index && image.index < range.end)
+                    || (image.index == range.start && range.start == 0)
+                    || (image.index == range.end && range.end == text_len)
+            })
+            .collect();
+        if images.is_empty() {
+            return Some(Cow::Borrowed(selected));
+        }
+        images.sort_by_key(|image| image.index);
+
+        let mut result = String::with_capacity(selected.len());
+        let mut last = range.start;
+        for image in images {
+            result.push_str(&self.text[last..image.index]);
+            result.push_str(&image.alt_text);
+            last = image.index;
+        }
+        result.push_str(&self.text[last..range.end]);
+        return Some(Cow::Owned(result));
+    }
+
+    /// Returns the inline boxes in the form that parley expects, sorted by index and with invalid indices removed.
+    pub(crate) fn parley_inline_boxes(&self) -> Vec<InlineBox> {
+        let is_valid_index = |index: usize| index <= self.text.len() && self.text.is_char_boundary(index);
+
+        let mut boxes: Vec<InlineBox> = self.inline_boxes.iter()
+            .filter(|b| is_valid_index(b.index))
+            .map(|b| InlineBox {
+                id: b.id,
+                kind: InlineBoxKind::InFlow,
+                index: b.index,
+                width: b.width,
+                height: b.height,
+            })
+            .collect();
+
+        for (i, image) in self.inline_images.iter().enumerate() {
+            if !is_valid_index(image.index) {
+                continue;
+            }
+            let Some(Some(source)) = self.shared().images.get(image.image.0 as usize) else {
+                continue;
+            };
+            let height = self.line_height_at(image.index);
+            let width = height * source.width() as f32 / source.height().max(1) as f32;
+            boxes.push(InlineBox {
+                id: INLINE_IMAGE_ID_FLAG | i as u64,
+                kind: InlineBoxKind::InFlow,
+                index: image.index,
+                width,
+                height,
+            });
+        }
+
+        boxes.extend(self.tab_inline_boxes());
+
+        boxes.sort_by_key(|b| b.index);
+        return boxes;
+    }
+
+    /// Reads back the positions that the layout assigned to the inline boxes.
+    pub(crate) fn collect_inline_box_rects(&mut self) {
+        self.inline_box_rects.clear();
+        if self.inline_boxes.is_empty() {
+            return;
+        }
+        for line in self.layout.lines() {
+            for item in line.items() {
+                if let PositionedLayoutItem::InlineBox(inline_box) = item {
+                    if inline_box.id & (INLINE_IMAGE_ID_FLAG | TAB_BOX_ID_FLAG) != 0 {
+                        continue;
+                    }
+                    // The position is in the layout of the paragraph of the line.
+                    let y = inline_box.y + line.y;
+                    self.inline_box_rects.push((inline_box.id, BoundingBox {
+                        x0: inline_box.x as f64,
+                        y0: y as f64,
+                        x1: (inline_box.x + inline_box.width) as f64,
+                        y1: (y + inline_box.height) as f64,
+                    }));
+                }
+            }
+        }
+    }
+}
@@ -143,9 +143,75 @@ pub use text_box::*;
 mod text_edit;
 pub use text_edit::*;
 
+mod text_storage;
+pub use text_storage::*;
+
+mod spans;
+pub use spans::*;
+
+mod inline_boxes;
+pub use inline_boxes::*;
+
+mod markdown;
+pub use markdown::*;
+
+mod background;
+pub use background::*;
+
+mod highlights;
+pub use highlights::*;
+
+mod truncation;
+pub use truncation::*;
+
+mod measure;
+pub use measure::*;
+
+mod font_fit;
+pub use font_fit::*;
+
+mod paragraphs;
+pub use paragraphs::*;
+
+mod tabs;
+pub use tabs::*;
+
+mod bidi;
+pub use bidi::*;
+
+mod locale;
+pub use locale::*;
+
+mod vertical;
+pub use vertical::*;
+
+mod text_path;
+pub use text_path::*;
+
+mod exclusions;
+pub use exclusions::*;
+
+mod threading;
+pub use threading::*;
+
+mod pagination;
+pub use pagination::*;
+
+mod paragraph_cache;
+pub(crate) use paragraph_cache::*;
+
+mod text_layout;
+pub use text_layout::*;
+
+mod virtual_lines;
+pub use virtual_lines::*;
+
 mod gpu_slab;
 pub(crate) use gpu_slab::*;
 
+mod spatial_index;
+pub(crate) use spatial_index::*;
+
 
 #[cfg(feature = "accessibility")]
 mod accessibility;
@@ -179,6 +245,72 @@ impl Default for TextEditStyle {
     }
 }
 
+/// Line style for text decorations (underline, strikethrough and overline).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DecorationLineStyle {
+    /// A continuous line.
+    #[default]
+    Solid,
+    /// A line of square dots.
+    Dotted,
+    /// A line of short dashes.
+    Dashed,
+    /// A wavy line, as used for spell checking.
+    Wavy,
+}
+
+/// Decoration settings for a style.
+/// 
+/// Underline and strikethrough are enabled, colored and sized through the [`TextStyle2`] itself (`has_underline`, `underline_brush`, etc.). This struct adds the things that parley doesn't know about: the overline and the line style.
+/// 
+/// Offsets and sizes are in logical pixels. When not set, they are taken from the font metrics.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct DecorationStyle {
+    /// Line style used for all the decorations of the style.
+    pub line_style: DecorationLineStyle,
+    /// Whether to draw a line over the text.
+    pub has_overline: bool,
+    /// Color of the overline. If `None`, the text color is used.
+    pub overline_brush: Option<ColorBrush>,
+    /// Distance of the overline from the top of the ascent, going down.
+    pub overline_offset: Option<f32>,
+    /// Thickness of the overline.
+    pub overline_size: Option<f32>,
+}
+
+/// An outline drawn around the glyphs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextOutline {
+    /// Width of the outline outside of the glyph, in logical pixels.
+    pub width: f32,
+    /// Color of the outline.
+    pub color: ColorBrush,
+}
+
+/// A shadow drawn behind the glyphs. With a zero offset and a bright color, it works as a glow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextShadow {
+    /// Offset of the shadow from the glyphs, in logical pixels.
+    pub offset: (f32, f32),
+    /// Color of the shadow.
+    pub color: ColorBrush,
+    /// Blur radius, in logical pixels. Zero gives a hard shadow.
+    pub blur_radius: f32,
+}
+
+/// Effects that make text readable over busy backgrounds.
+/// 
+/// The effects are drawn behind all the glyphs of the box: first the shadows, then the outlines. If the style has an outline, the shadow includes it.
+/// 
+/// Color glyphs like emoji don't get effects.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct TextEffectStyle {
+    /// Outline around the glyphs.
+    pub outline: Option<TextOutline>,
+    /// Shadow or glow behind the glyphs.
+    pub shadow: Option<TextShadow>,
+}
+
 use bytemuck::{Pod, Zeroable};
 use etagere::euclid::{Size2D, UnknownUnit};
 use etagere::{size2, Allocation, BucketedAtlasAllocator};
@@ -190,7 +322,7 @@ use wgpu::*;
 
 use image::{GrayImage, Luma, Rgba, RgbaImage};
 use parley::{
-    Glyph, GlyphRun, PositionedLayoutItem,
+    Glyph, GlyphRun, PositionedInlineBox, PositionedLayoutItem,
 };
 use std::borrow::Cow;
 use std::hash::BuildHasherDefault;
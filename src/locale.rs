@@ -0,0 +1,181 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use parley::StyleProperty;
+
+use crate::*;
+
+/// Error returned by [`TextBox::set_locale()`] for a string that isn't a valid language tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidLocale(pub String);
+
+impl fmt::Display for InvalidLocale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid language tag: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidLocale {}
+
+impl TextBox {
+    /// Sets the language of the text in the box, as a BCP 47 tag like `"ja"`, `"th"` or `"sr-Cyrl"`. With `None`, the `locale` of the [`TextStyle2`] of the box is used.
+    ///
+    /// The language is used for shaping, for example to pick the Serbian forms of Cyrillic letters, and for line breaking and word boundaries. Since word selection, word movement and word deletion all use the word boundaries of the layout, they follow the language too.
+    ///
+    /// Spans with a style that has its own `locale` keep it. If the tag can't be parsed, the locale of the box is left unchanged and an error is returned.
+    pub fn set_locale(&mut self, locale: Option<&str>) -> Result<(), InvalidLocale> {
+        if self.locale.as_deref() == locale {
+            return Ok(());
+        }
+        let property = match locale {
+            Some(tag) => {
+                let language = tag.parse().map_err(|_| InvalidLocale(tag.to_owned()))?;
+                Some(StyleProperty::Locale(Some(language)))
+            }
+            None => None,
+        };
+        self.locale = locale.map(str::to_owned);
+        self.locale_property = property;
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+        return Ok(());
+    }
+
+    /// Returns the language tag set with [`TextBox::set_locale()`].
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    /// Returns `style` with the locale of the box, if one is set.
+    pub(crate) fn localized_style<'a>(&self, style: &'a TextStyle2) -> Cow<'a, TextStyle2> {
+        match &self.locale_property {
+            Some(StyleProperty::Locale(locale)) => Cow::Owned(TextStyle2 { locale: locale.clone(), ..style.clone() }),
+            _ => Cow::Borrowed(style),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::NonNull;
+
+    use parley::Affinity;
+
+    use super::*;
+
+    const JAPANESE: &str = "チョコレートとコーヒーをショッピングモールでちょっと買ったのである。";
+    const THAI: &str = "สวัสดีครับ";
+
+    fn text_box(shared: &mut Shared, text: &str, locale: Option<&str>, width: f32) -> TextBox {
+        let default_style_key = shared.default_style_key;
+        let mut text_box = TextBox::new(text.to_string(), (0.0, 0.0), (width, 1000.0), 0.0, default_style_key, NonNull::from(shared));
+        text_box.set_locale(locale).unwrap();
+        text_box.refresh_layout();
+        return text_box;
+    }
+
+    fn line_starts(text_box: &TextBox) -> Vec<usize> {
+        text_box.layout.lines().map(|line| line.text_range().start).collect()
+    }
+
+    /// The text indices where moving right by words stops, from the start of the text.
+    fn word_stops(text_box: &mut TextBox) -> Vec<usize> {
+        text_box.move_to_text_start();
+        let mut stops = vec![0];
+        loop {
+            text_box.move_word_right();
+            let stop = text_box.layout_to_text_index(text_box.selection().focus().index());
+            if stop == *stops.last().unwrap() {
+                return stops;
+            }
+            stops.push(stop);
+        }
+    }
+
+    /// A point in the middle of the first glyph of the text.
+    fn first_glyph_center(layout: &TextLayout) -> (f32, f32) {
+        let caret = TextCursor::from_byte_index(layout, 0, Affinity::Downstream).geometry(layout, 1.0);
+        (caret.x0 as f32 + 1.0, (caret.y0 + caret.y1) as f32 / 2.0)
+    }
+
+    #[test]
+    fn invalid_tag_is_rejected() {
+        let mut shared = Shared::new();
+        let mut text_box = text_box(&mut shared, "text", Some("sr-Cyrl"), 1000.0);
+
+        assert_eq!(text_box.set_locale(Some("not a tag")), Err(InvalidLocale("not a tag".to_string())));
+        assert_eq!(text_box.locale(), Some("sr-Cyrl"));
+
+        assert_eq!(text_box.set_locale(None), Ok(()));
+        assert_eq!(text_box.locale(), None);
+        assert!(text_box.locale_property.is_none());
+    }
+
+    #[test]
+    fn japanese_line_breaks_follow_the_locale() {
+        let mut shared = Shared::new();
+
+        // With the Japanese rules, lines can also start with small kana and the long vowel mark, so some widths break differently.
+        let mut differs = false;
+        for width in (40..=200).step_by(4) {
+            let default_breaks = line_starts(&text_box(&mut shared, JAPANESE, None, width as f32));
+            let japanese = text_box(&mut shared, JAPANESE, Some("ja"), width as f32);
+            let japanese_breaks = line_starts(&japanese);
+            differs |= default_breaks != japanese_breaks;
+
+            for &start in &japanese_breaks[1..] {
+                // Closing punctuation never starts a line.
+                assert!(!JAPANESE[start..].starts_with('。'));
+            }
+        }
+        assert!(differs);
+    }
+
+    #[test]
+    fn thai_word_boundaries_follow_the_locale() {
+        let mut shared = Shared::new();
+        let default_stops = word_stops(&mut text_box(&mut shared, THAI, None, 1000.0));
+        let thai_stops = word_stops(&mut text_box(&mut shared, THAI, Some("th"), 1000.0));
+
+        assert_eq!(thai_stops, vec![0, "สวัสดี".len(), THAI.len()]);
+        assert_ne!(default_stops, thai_stops);
+    }
+
+    #[test]
+    fn thai_word_selection_in_text_box() {
+        let mut shared = Shared::new();
+        let mut text_box = text_box(&mut shared, THAI, Some("th"), 1000.0);
+
+        text_box.move_to_text_end();
+        text_box.move_word_left();
+        assert_eq!(text_box.layout_to_text_index(text_box.selection().focus().index()), "สวัสดี".len());
+
+        let (x, y) = first_glyph_center(&text_box.layout);
+        text_box.selection.select_word_at_point(&text_box.layout, x, y);
+        assert_eq!(text_box.selection_text_range(), 0.."สวัสดี".len());
+    }
+
+    #[test]
+    fn thai_word_selection_in_text_edit() {
+        let mut shared = Shared::new();
+        let default_style_key = shared.default_style_key;
+        let mut text_edit = TextEdit::new(THAI.to_string(), (0.0, 0.0), (1000.0, 1000.0), 0.0, default_style_key, NonNull::from(&mut shared));
+        text_edit.set_locale(Some("th")).unwrap();
+        text_edit.text_box.refresh_layout();
+
+        let text_box = &mut text_edit.text_box;
+        let (x, y) = first_glyph_center(&text_box.layout);
+        text_box.selection.select_word_at_point(&text_box.layout, x, y);
+        assert_eq!(text_edit.text_box.selection_text_range(), 0.."สวัสดี".len());
+
+        text_edit.text_box.move_to_text_end();
+        text_edit.text_box.move_word_left();
+        assert_eq!(text_edit.text_box.selection_text_range(), "สวัสดี".len().."สวัสดี".len());
+
+        // Word deletion uses the same boundaries.
+        text_edit.text_box.move_to_text_end();
+        text_edit.backdelete_word();
+        assert_eq!(text_edit.text_box.text_inner(), "สวัสดี");
+    }
+}
@@ -0,0 +1,359 @@
+use std::ops::Range;
+
+use parley::{FontFamily, FontStack, FontStyle, FontWeight, GenericFamily, StyleProperty};
+
+use crate::*;
+
+/// The styles used for the elements of Markdown text set with [`TextBox::set_markdown()`]. Set it with [`Text::set_markdown_theme()`].
+///
+/// Each style is used as a [`SpanStyle::Style`], so it replaces the style of the box for the text that it covers. Elements without a style get a small set of property overrides instead: italic for emphasis, bold for strong text and headings, a monospace font for code, and an underline for links.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MarkdownTheme {
+    /// Style for `*emphasis*` and `_emphasis_`.
+    pub emphasis: Option<StyleHandle>,
+    /// Style for `**strong**` and `__strong__`.
+    pub strong: Option<StyleHandle>,
+    /// Style for `` `inline code` ``.
+    pub code: Option<StyleHandle>,
+    /// Style for the text of `[links](url)`.
+    pub link: Option<StyleHandle>,
+    /// Styles for `#` to `######` headings.
+    pub headings: [Option<StyleHandle>; 6],
+}
+
+/// A link in Markdown text set with [`TextBox::set_markdown()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkdownLink {
+    /// Byte range of the link text, in the plain text of the box.
+    pub range: Range<usize>,
+    /// Destination of the link, as written in the source.
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum MarkdownElement {
+    Emphasis,
+    Strong,
+    Code,
+    Link,
+    Heading(usize),
+}
+
+/// Font size of the headings without a style in the theme, relative to the font size of the box.
+const HEADING_SCALES: [f32; 6] = [2.0, 1.5, 1.25, 1.1, 1.0, 0.9];
+
+const LINK_COLOR: ColorBrush = ColorBrush([90, 160, 255, 255]);
+
+impl MarkdownTheme {
+    fn span_style(&self, element: MarkdownElement, base_font_size: f32) -> SpanStyle {
+        let handle = match element {
+            MarkdownElement::Emphasis => self.emphasis,
+            MarkdownElement::Strong => self.strong,
+            MarkdownElement::Code => self.code,
+            MarkdownElement::Link => self.link,
+            MarkdownElement::Heading(level) => self.headings[level - 1],
+        };
+        if let Some(handle) = handle {
+            return SpanStyle::Style(handle);
+        }
+
+        let properties = match element {
+            MarkdownElement::Emphasis => vec![StyleProperty::FontStyle(FontStyle::Italic)],
+            MarkdownElement::Strong => vec![StyleProperty::FontWeight(FontWeight::BOLD)],
+            MarkdownElement::Code => vec![StyleProperty::FontStack(FontStack::Single(FontFamily::Generic(GenericFamily::Monospace)))],
+            MarkdownElement::Link => vec![StyleProperty::Underline(true), StyleProperty::Brush(LINK_COLOR)],
+            MarkdownElement::Heading(level) => vec![
+                StyleProperty::FontWeight(FontWeight::BOLD),
+                StyleProperty::FontSize(base_font_size * HEADING_SCALES[level - 1]),
+            ],
+        };
+        return SpanStyle::Properties(properties);
+    }
+}
+
+/// The result of parsing Markdown: the plain text, and the ranges of the elements in it.
+///
+/// Elements are ordered by their opening position, so nested elements come after the ones containing them.
+#[derive(Debug, Default)]
+pub(crate) struct ParsedMarkdown {
+    pub text: String,
+    pub elements: Vec<(Range<usize>, MarkdownElement)>,
+    pub links: Vec<MarkdownLink>,
+}
+
+/// Parses the subset of Markdown supported by [`TextBox::set_markdown()`].
+pub(crate) fn parse_markdown(source: &str) -> ParsedMarkdown {
+    let mut parsed = ParsedMarkdown::default();
+    parsed.text.reserve(source.len());
+
+    for line in source.split_inclusive('\n') {
+        let newline = if line.ends_with("\r\n") { "\r\n" } else if line.ends_with('\n') { "\n" } else { "" };
+        let content = &line[..line.len() - newline.len()];
+
+        let level = content.bytes().take_while(|&b| b == b'#').count();
+        let is_heading = (1..=6).contains(&level) && (content.len() == level || content.as_bytes()[level] == b' ');
+        if is_heading {
+            let heading = content[level..].trim();
+            let element = parsed.open(MarkdownElement::Heading(level));
+            parsed.parse_inline(heading);
+            parsed.close(element);
+        } else {
+            parsed.parse_inline(content);
+        }
+
+        parsed.text.push_str(newline);
+    }
+
+    return parsed;
+}
+
+impl ParsedMarkdown {
+    fn open(&mut self, element: MarkdownElement) -> usize {
+        let start = self.text.len();
+        self.elements.push((start..start, element));
+        return self.elements.len() - 1;
+    }
+
+    fn close(&mut self, element: usize) {
+        self.elements[element].0.end = self.text.len();
+    }
+
+    fn parse_inline(&mut self, s: &str) {
+        let mut i = 0;
+        while let Some(c) = s[i..].chars().next() {
+            let rest = &s[i..];
+            match c {
+                '\\' => {
+                    match rest[1..].chars().next() {
+                        Some(escaped) if escaped.is_ascii_punctuation() => {
+                            self.text.push(escaped);
+                            i += 2;
+                        }
+                        _ => {
+                            self.text.push('\\');
+                            i += 1;
+                        }
+                    }
+                }
+                '`' => {
+                    let ticks = rest.bytes().take_while(|&b| b == b'`').count();
+                    let delim = &rest[..ticks];
+                    match rest[ticks..].find(delim) {
+                        Some(len) => {
+                            let element = self.open(MarkdownElement::Code);
+                            self.text.push_str(&rest[ticks..ticks + len]);
+                            self.close(element);
+                            i += ticks * 2 + len;
+                        }
+                        None => {
+                            self.text.push_str(delim);
+                            i += ticks;
+                        }
+                    }
+                }
+                '*' | '_' => {
+                    let is_double = rest[1..].starts_with(c);
+                    let delim = &rest[..if is_double { 2 } else { 1 }];
+                    let inner = &rest[delim.len()..];
+
+                    // Underscores inside of words are just underscores.
+                    let intraword = c == '_' && s[..i].chars().next_back().is_some_and(|p| p.is_alphanumeric());
+                    let opens = !intraword && inner.chars().next().is_some_and(|n| !n.is_whitespace());
+                    let closer = find_closer(inner, delim)
+                        .filter(|&len| opens && len > 0 && !inner[..len].ends_with(char::is_whitespace));
+
+                    match closer {
+                        Some(len) => {
+                            let kind = if is_double { MarkdownElement::Strong } else { MarkdownElement::Emphasis };
+                            let element = self.open(kind);
+                            self.parse_inline(&inner[..len]);
+                            self.close(element);
+                            i += delim.len() * 2 + len;
+                        }
+                        None => {
+                            self.text.push_str(delim);
+                            i += delim.len();
+                        }
+                    }
+                }
+                '[' => {
+                    let link = find_closer(&rest[1..], "]").and_then(|label_len| {
+                        let after = &rest[1 + label_len + 1..];
+                        let url_len = after.strip_prefix('(')?.find(')')?;
+                        Some((label_len, url_len))
+                    });
+                    match link {
+                        Some((label_len, url_len)) => {
+                            let label = &rest[1..1 + label_len];
+                            let url_start = 1 + label_len + 2;
+                            let url = rest[url_start..url_start + url_len].trim();
+
+                            let element = self.open(MarkdownElement::Link);
+                            self.parse_inline(label);
+                            self.close(element);
+                            self.links.push(MarkdownLink { range: self.elements[element].0.clone(), url: url.to_string() });
+                            i += url_start + url_len + 1;
+                        }
+                        None => {
+                            self.text.push('[');
+                            i += 1;
+                        }
+                    }
+                }
+                _ => {
+                    self.text.push(c);
+                    i += c.len_utf8();
+                }
+            }
+        }
+    }
+}
+
+/// Finds the byte offset of the closing `delim` in `s`, skipping escaped characters and code spans.
+///
+/// A single `*` or `_` doesn't match against a doubled one.
+fn find_closer(s: &str, delim: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    let mut j = 0;
+    while j < bytes.len() {
+        match bytes[j] {
+            b'\\' => {
+                j += 2;
+                continue;
+            }
+            b'`' => {
+                let ticks = bytes[j..].iter().take_while(|&&b| b == b'`').count();
+                let code_delim = &s[j..j + ticks];
+                match s[j + ticks..].find(code_delim) {
+                    Some(len) => j += ticks * 2 + len,
+                    None => j += ticks,
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        if bytes[j..].starts_with(delim.as_bytes()) {
+            let doubled = delim.len() == 1 && bytes.get(j + 1) == Some(&bytes[j]);
+            if !doubled {
+                return Some(j);
+            }
+            j += 2;
+            continue;
+        }
+        j += 1;
+    }
+    return None;
+}
+
+impl TextBox {
+    /// Sets the text of the box from a small subset of Markdown: `*emphasis*`, `**strong**`, `` `inline code` ``, `[links](url)`, and `#` headings at the start of a line. Backslash escapes work as usual.
+    ///
+    /// The markup is removed from the text, and the elements become spans styled according to the [`MarkdownTheme`] set on [`Text`]. This replaces all the existing spans of the box. Line breaks are kept as they are, and everything else is plain text.
+    ///
+    /// [`TextBox::text()`], [`TextBox::selected_text()`] and copying return the plain text.
+    pub fn set_markdown(&mut self, markdown: &str) {
+        let parsed = parse_markdown(markdown);
+
+        let theme = self.shared().markdown_theme;
+        let base_font_size = self.shared().styles[self.style.key].text_style.font_size;
+        let spans = parsed.elements.into_iter()
+            .filter(|(range, _)| !range.is_empty())
+            .map(|(range, element)| TextSpan { range, style: theme.span_style(element, base_font_size) })
+            .collect();
+
+        self.set_text(&parsed.text);
+        self.set_spans(spans);
+        self.markdown_links = parsed.links;
+    }
+
+    /// Returns the links in the text set with [`TextBox::set_markdown()`].
+    pub fn markdown_links(&self) -> &[MarkdownLink] {
+        &self.markdown_links
+    }
+
+    /// Returns the link covering the byte offset `index`, if any.
+    pub fn markdown_link_at(&self, index: usize) -> Option<&MarkdownLink> {
+        self.markdown_links.iter().find(|link| link.range.contains(&index))
+    }
+
+    /// Returns the link under a screen-space cursor position, if any. The layout must be up to date.
+    pub fn markdown_link_at_pos(&self, cursor_pos: (f64, f64)) -> Option<&MarkdownLink> {
+        if self.markdown_links.is_empty() {
+            return None;
+        }
+        let (x, y) = self.screen_to_layout((cursor_pos.0 as f32, cursor_pos.1 as f32));
+
+        let range = self.layout_to_text_range(self.layout.cluster_range_at_point(x, y)?);
+        return self.markdown_links.iter().find(|link| link.range.start <= range.start && range.end <= link.range.end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> (String, Vec<(Range<usize>, MarkdownElement)>) {
+        let parsed = parse_markdown(source);
+        (parsed.text, parsed.elements)
+    }
+
+    #[test]
+    fn emphasis_and_strong() {
+        assert_eq!(parse("a *b* c"), ("a b c".to_string(), vec![(2..3, MarkdownElement::Emphasis)]));
+        assert_eq!(parse("a __b__ c"), ("a b c".to_string(), vec![(2..3, MarkdownElement::Strong)]));
+    }
+
+    #[test]
+    fn nested_emphasis_inside_strong() {
+        assert_eq!(
+            parse("**bold *both* done**"),
+            ("bold both done".to_string(), vec![(0..14, MarkdownElement::Strong), (5..9, MarkdownElement::Emphasis)]),
+        );
+    }
+
+    #[test]
+    fn unclosed_markers_stay_as_text() {
+        assert_eq!(parse("a *b"), ("a *b".to_string(), vec![]));
+        assert_eq!(parse("**x"), ("**x".to_string(), vec![]));
+        assert_eq!(parse("`code"), ("`code".to_string(), vec![]));
+        assert_eq!(parse("[label](url"), ("[label](url".to_string(), vec![]));
+    }
+
+    #[test]
+    fn markers_next_to_whitespace_dont_open() {
+        assert_eq!(parse("a * b *"), ("a * b *".to_string(), vec![]));
+    }
+
+    #[test]
+    fn underscores_inside_words() {
+        assert_eq!(parse("snake_case_name"), ("snake_case_name".to_string(), vec![]));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(parse(r"\*not\* em"), ("*not* em".to_string(), vec![]));
+        assert_eq!(parse(r"a\b"), (r"a\b".to_string(), vec![]));
+    }
+
+    #[test]
+    fn code_spans_hide_markers() {
+        assert_eq!(parse("`a*b*`"), ("a*b*".to_string(), vec![(0..4, MarkdownElement::Code)]));
+        assert_eq!(parse("*a `*` b*"), ("a * b".to_string(), vec![(0..5, MarkdownElement::Emphasis), (2..3, MarkdownElement::Code)]));
+    }
+
+    #[test]
+    fn links() {
+        let parsed = parse_markdown("see [the *docs*](http://x) now");
+        assert_eq!(parsed.text, "see the docs now");
+        assert_eq!(parsed.elements, vec![(4..12, MarkdownElement::Link), (8..12, MarkdownElement::Emphasis)]);
+        assert_eq!(parsed.links, vec![MarkdownLink { range: 4..12, url: "http://x".to_string() }]);
+    }
+
+    #[test]
+    fn headings() {
+        assert_eq!(parse("# Title\nbody"), ("Title\nbody".to_string(), vec![(0..5, MarkdownElement::Heading(1))]));
+        assert_eq!(parse("#hashtag"), ("#hashtag".to_string(), vec![]));
+        assert_eq!(parse("####### seven"), ("####### seven".to_string(), vec![]));
+    }
+}
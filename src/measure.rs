@@ -0,0 +1,116 @@
+use crate::*;
+
+/// Size information about a piece of laid out text. See [`Text::measure()`].
+///
+/// All values are in the same units as the sizes of the text boxes.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct TextMetrics {
+    /// Width of the widest line, without trailing whitespace.
+    pub width: f32,
+    /// Total height of the lines.
+    pub height: f32,
+    /// Number of lines. Empty text still has one line.
+    pub line_count: usize,
+    /// Distance from the top to the baseline of the first line.
+    pub first_baseline: f32,
+    /// Distance from the top to the baseline of the last line.
+    pub last_baseline: f32,
+    /// Width of the widest unbreakable piece of the text. Boxes narrower than this will overflow.
+    pub min_intrinsic_width: f32,
+    /// Width of the text with no line wrapping at all.
+    pub max_intrinsic_width: f32,
+}
+
+impl TextMetrics {
+    pub(crate) fn from_layout(layout: &TextLayout) -> Self {
+        let (min_intrinsic_width, max_intrinsic_width) = layout.content_widths();
+        let baseline = |line: Option<TextLine>| line.map(|l| l.metrics().baseline).unwrap_or(0.0);
+        Self {
+            width: layout.width(),
+            height: layout.height(),
+            line_count: layout.len(),
+            first_baseline: baseline(layout.lines().next()),
+            last_baseline: baseline(layout.lines().last()),
+            min_intrinsic_width,
+            max_intrinsic_width,
+        }
+    }
+}
+
+impl Text {
+    /// Measures `text` laid out with `style`, wrapped at `max_width` if it's `Some`.
+    ///
+    /// This uses the same shaping and layout as a text box, but it doesn't create one, so it doesn't touch any of the render state. The layout is done at the scale factor of the first window, like a text box without an explicit window.
+    pub fn measure(&mut self, text: &str, style: &StyleHandle, max_width: Option<f32>) -> TextMetrics {
+        let scale_factor = self.shared.windows.first().map(|w| w.scale_factor).unwrap_or(1.0);
+
+        let shared = &mut *self.shared;
+        let style = &shared.styles[style.key].text_style;
+        let mut builder = shared.layout_cx.tree_builder(&mut shared.font_cx, scale_factor as f32, true, style);
+        builder.push_text(text);
+        let (mut layout, _) = builder.build();
+        layout.break_all_lines(max_width);
+
+        return TextMetrics::from_layout(&TextLayout::from_layout(layout));
+    }
+}
+
+impl TextBox {
+    /// Enables or disables shrink-to-fit.
+    ///
+    /// When enabled, the size set with [`TextBox::set_size()`] becomes the maximum size: the text is wrapped at its width, and then the box is shrunk to the size of the text plus the padding. [`TextBox::size()`] returns the shrunk size.
+    pub fn set_shrink_to_fit(&mut self, shrink_to_fit: bool) {
+        if self.shrink_to_fit.is_some() == shrink_to_fit {
+            return;
+        }
+        if shrink_to_fit {
+            self.shrink_to_fit = Some((self.width, self.height));
+        } else if let Some((width, height)) = self.shrink_to_fit.take() {
+            self.width = width;
+            self.height = height;
+        }
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns `true` if shrink-to-fit is enabled.
+    pub fn shrink_to_fit(&self) -> bool {
+        self.shrink_to_fit.is_some()
+    }
+
+    /// Returns the metrics of the current layout, refreshing it if needed.
+    pub fn metrics(&mut self) -> TextMetrics {
+        self.refresh_layout();
+        TextMetrics::from_layout(&self.layout)
+    }
+
+    /// Returns the width that the lines are aligned in when the box shrinks to fit a layout of width `layout_width`, if shrink-to-fit is enabled.
+    pub(crate) fn shrunk_align_width(&self, layout_width: f32) -> Option<f32> {
+        let (max_width, _) = self.shrink_to_fit?;
+        let horizontal_padding = self.padding.left + self.padding.right;
+        return Some(((layout_width + horizontal_padding).min(max_width) - horizontal_padding).max(0.0));
+    }
+
+    /// Shrinks the box to the size of the layout, if shrink-to-fit is enabled.
+    pub(crate) fn apply_shrink_to_fit(&mut self, single_line: bool) {
+        let Some((max_width, max_height)) = self.shrink_to_fit else {
+            return;
+        };
+        let horizontal_padding = self.padding.left + self.padding.right;
+        let vertical_padding = self.padding.top + self.padding.bottom;
+        let width = (self.layout.width() + horizontal_padding).min(max_width);
+        let height = (self.layout.height() + vertical_padding).min(max_height);
+
+        if !single_line {
+            // Lines were aligned in the full width, align them again in the shrunk one. Paragraphs that were laid out on their own are already aligned in it.
+            self.layout.align((width - horizontal_padding).max(0.0), self.alignment);
+        }
+
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.shared_mut().rebuild_glyph_quad_buffer = true;
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use std::ops::Range;
+
+use crate::*;
+
+/// Widow and orphan control for [`Text::paginate()`].
+///
+/// Paragraphs are separated by hard line breaks. Values of 0 and 1 don't change anything, since every page has at least one line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct PaginationOptions {
+    /// Minimum number of lines of a paragraph left at the bottom of a page when the paragraph continues on the next page.
+    pub orphans: usize,
+    /// Minimum number of lines of a paragraph carried over to the top of the next page.
+    pub widows: usize,
+}
+
+/// A line on a [`Page`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageLine {
+    /// Byte range of the line in the whole text.
+    pub text_range: Range<usize>,
+    /// Distance from the top of the page to the top of the line.
+    pub top: f32,
+    /// Distance from the top of the page to the baseline of the line.
+    pub baseline: f32,
+    /// Height of the line.
+    pub height: f32,
+    /// Width of the line, without trailing whitespace.
+    pub width: f32,
+}
+
+/// A page of text. See [`Text::paginate()`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    /// Byte range of the page in the whole text.
+    pub text_range: Range<usize>,
+    /// The lines on the page.
+    pub lines: Vec<PageLine>,
+    /// Total height of the lines on the page.
+    pub height: f32,
+}
+
+/// A line of the full layout, with its position from the top of the text.
+struct LayoutLine {
+    text_range: Range<usize>,
+    top: f32,
+    baseline: f32,
+    height: f32,
+    width: f32,
+    ends_paragraph: bool,
+}
+
+impl Text {
+    /// Splits `text` laid out with `style` into pages of size `page_size`.
+    ///
+    /// The text is laid out once, wrapped at the width of the page, and then the lines are split between pages, so pages always break at line boundaries. A line taller than a page still gets a page of its own.
+    ///
+    /// Like [`Text::measure()`], this doesn't create any text boxes. To show the pages, use [`Text::add_paginated_text_boxes()`] or put the text ranges of the pages in text boxes of size `page_size` with the same style.
+    pub fn paginate(&mut self, text: &str, style: &StyleHandle, page_size: (f32, f32), options: PaginationOptions) -> Vec<Page> {
+        let scale_factor = self.shared.windows.first().map(|w| w.scale_factor).unwrap_or(1.0);
+
+        let shared = &mut *self.shared;
+        let style = &shared.styles[style.key].text_style;
+        let mut builder = shared.layout_cx.tree_builder(&mut shared.font_cx, scale_factor as f32, true, style);
+        builder.push_text(text);
+        let (mut layout, _) = builder.build();
+        layout.break_all_lines(Some(page_size.0));
+
+        let mut lines = Vec::with_capacity(layout.len());
+        let mut top = 0.0;
+        for line in layout.lines() {
+            let metrics = line.metrics();
+            let text_range = line.text_range();
+            lines.push(LayoutLine {
+                ends_paragraph: text[text_range.clone()].ends_with('\n'),
+                text_range,
+                top,
+                baseline: metrics.baseline,
+                height: metrics.line_height,
+                width: metrics.advance - metrics.trailing_whitespace,
+            });
+            top += metrics.line_height;
+        }
+
+        return split_into_pages(&lines, page_size.1, options).into_iter().map(|page| {
+            let page_lines = &lines[page.clone()];
+            let page_top = lines[page.start].top;
+            let text_end = if page.end == lines.len() { text.len() } else { lines[page.end].text_range.start };
+            Page {
+                text_range: lines[page.start].text_range.start..text_end,
+                lines: page_lines.iter().map(|line| PageLine {
+                    text_range: line.text_range.clone(),
+                    top: line.top - page_top,
+                    baseline: line.baseline - page_top,
+                    height: line.height,
+                    width: line.width,
+                }).collect(),
+                height: page_lines.iter().map(|line| line.height).sum(),
+            }
+        }).collect();
+    }
+
+    /// Splits `text` into pages with [`Text::paginate()`] and adds a text box for each page, with the text of the page, `style` and size `page_size`.
+    ///
+    /// `page_pos` is called with the index of each page and returns its position.
+    pub fn add_paginated_text_boxes(
+        &mut self,
+        text: &str,
+        style: &StyleHandle,
+        page_size: (f32, f32),
+        options: PaginationOptions,
+        depth: f32,
+        mut page_pos: impl FnMut(usize) -> (f64, f64),
+    ) -> Vec<TextBoxHandle> {
+        let pages = self.paginate(text, style, page_size, options);
+        let mut handles = Vec::with_capacity(pages.len());
+        for (i, page) in pages.into_iter().enumerate() {
+            let handle = self.add_text_box(text[page.text_range].to_owned(), page_pos(i), page_size, depth);
+            self.get_text_box_mut(&handle).set_style(style);
+            handles.push(handle);
+        }
+        return handles;
+    }
+}
+
+/// Splits the lines between pages of height `page_height`. Returns the range of lines on each page.
+fn split_into_pages(lines: &[LayoutLine], page_height: f32, options: PaginationOptions) -> Vec<Range<usize>> {
+    let mut pages = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let page_top = lines[start].top;
+        let mut end = start + 1;
+        while end < lines.len() && lines[end].top + lines[end].height - page_top <= page_height {
+            end += 1;
+        }
+        if end < lines.len() {
+            end = break_with_widows_and_orphans(lines, start, end, options);
+        }
+        pages.push(start..end);
+        start = end;
+    }
+    return pages;
+}
+
+/// Moves the end of a page, which is the index of the first line of the next page, so that the paragraph that it splits keeps enough lines on both pages. If that's not possible, the end is left where it was.
+fn break_with_widows_and_orphans(lines: &[LayoutLine], start: usize, end: usize, options: PaginationOptions) -> usize {
+    let paragraph_start = (0..end).rev()
+        .find(|&i| lines[i].ends_paragraph)
+        .map_or(0, |i| i + 1);
+    if paragraph_start == end {
+        return end;
+    }
+    let paragraph_end = (end..lines.len())
+        .find(|&i| lines[i].ends_paragraph)
+        .map_or(lines.len(), |i| i + 1);
+
+    let mut new_end = end;
+    if paragraph_end - new_end < options.widows {
+        new_end = paragraph_end.saturating_sub(options.widows);
+    }
+    if new_end <= paragraph_start || new_end - paragraph_start < options.orphans {
+        new_end = paragraph_start;
+    }
+
+    if new_end <= start {
+        return end;
+    }
+    return new_end;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines with the given heights, stacked from the top. The lines at the indices in `paragraph_ends` end a paragraph.
+    fn layout_lines(heights: &[f32], paragraph_ends: &[usize]) -> Vec<LayoutLine> {
+        let mut top = 0.0;
+        heights.iter().enumerate().map(|(i, &height)| {
+            let line = LayoutLine {
+                text_range: i..i + 1,
+                top,
+                baseline: top + height * 0.8,
+                height,
+                width: 0.0,
+                ends_paragraph: paragraph_ends.contains(&i),
+            };
+            top += height;
+            line
+        }).collect()
+    }
+
+    fn options(orphans: usize, widows: usize) -> PaginationOptions {
+        PaginationOptions { orphans, widows }
+    }
+
+    #[test]
+    fn fills_pages_and_keeps_a_short_last_page() {
+        let lines = layout_lines(&[10.0; 10], &[9]);
+        assert_eq!(split_into_pages(&lines, 35.0, options(0, 0)), vec![0..3, 3..6, 6..9, 9..10]);
+    }
+
+    #[test]
+    fn orphans_move_the_start_of_a_paragraph_to_the_next_page() {
+        // Paragraphs of 2 and 4 lines, 3 lines per page.
+        let lines = layout_lines(&[10.0; 6], &[1, 5]);
+        assert_eq!(split_into_pages(&lines, 30.0, options(0, 0)), vec![0..3, 3..6]);
+        assert_eq!(split_into_pages(&lines, 30.0, options(2, 0)), vec![0..2, 2..5, 5..6]);
+    }
+
+    #[test]
+    fn widows_carry_lines_over_to_the_next_page() {
+        let lines = layout_lines(&[10.0; 6], &[1, 5]);
+        assert_eq!(split_into_pages(&lines, 30.0, options(2, 2)), vec![0..2, 2..4, 4..6]);
+    }
+
+    #[test]
+    fn options_larger_than_the_paragraph_are_ignored() {
+        let lines = layout_lines(&[10.0; 5], &[4]);
+        assert_eq!(split_into_pages(&lines, 30.0, options(10, 0)), vec![0..3, 3..5]);
+        assert_eq!(split_into_pages(&lines, 30.0, options(0, 10)), vec![0..3, 3..5]);
+        assert_eq!(split_into_pages(&lines, 30.0, options(10, 10)), vec![0..3, 3..5]);
+    }
+
+    #[test]
+    fn line_taller_than_the_page_gets_its_own_page() {
+        let lines = layout_lines(&[10.0, 50.0, 10.0], &[2]);
+        assert_eq!(split_into_pages(&lines, 30.0, options(0, 0)), vec![0..1, 1..2, 2..3]);
+        assert_eq!(split_into_pages(&lines, 30.0, options(2, 2)), vec![0..1, 1..2, 2..3]);
+    }
+
+    #[test]
+    fn break_at_a_paragraph_boundary_is_kept() {
+        let lines = layout_lines(&[10.0; 6], &[2, 5]);
+        assert_eq!(break_with_widows_and_orphans(&lines, 0, 3, options(3, 3)), 3);
+    }
+}
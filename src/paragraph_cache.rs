@@ -0,0 +1,160 @@
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use ahash::AHasher;
+
+use crate::*;
+
+/// Cached quads for the lines of one paragraph of a text box.
+///
+/// When a box is prepared again after an edit, the paragraphs that didn't change keep their quads, moved to their new position, instead of going through the glyph runs again.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ParagraphQuads {
+    /// Hash of the glyphs and the line positions relative to the top of the paragraph. See [`TextBox::paragraph_key()`].
+    pub key: u64,
+    /// Top of the first line, in layout coordinates.
+    pub top: f32,
+    /// `false` if some of the lines were culled.
+    pub complete: bool,
+    pub generation: u64,
+    pub glyphs: Vec<GlyphQuad>,
+    pub shadows: Vec<GlyphQuad>,
+    pub outlines: Vec<GlyphQuad>,
+}
+
+impl ParagraphQuads {
+    pub(crate) fn new(key: u64, top: f32) -> Self {
+        Self { key, top, ..Default::default() }
+    }
+
+    /// Moves the quads so that the paragraph starts at `top`. Returns `false` if they can't be moved by a whole number of pixels, in which case they have to be prepared again.
+    fn move_to(&mut self, top: f32) -> bool {
+        let dy = top - self.top;
+        if (dy - dy.round()).abs() > 0.01 {
+            return false;
+        }
+        let dy = dy.round() as i32;
+        for quads in [&mut self.glyphs, &mut self.shadows, &mut self.outlines] {
+            offset_glyph_quads(quads, 0, dy);
+        }
+        self.top = top;
+        return true;
+    }
+}
+
+/// A paragraph of the current layout of a text box.
+#[derive(Debug, Clone)]
+pub(crate) struct LayoutParagraph {
+    /// The layout lines of the paragraph.
+    pub lines: Range<usize>,
+    /// The key of the paragraph. For a paragraph with its own layout, it's the version of the layout. Otherwise, it's hashed the first time the paragraph is prepared with this layout.
+    pub key: Option<u64>,
+}
+
+/// Finds a complete paragraph with `key` in `old` and takes it, moved to `top`.
+///
+/// Paragraphs are searched in order starting from `next`, since the paragraphs before an edit are usually found right away and the ones after it come in the same order.
+pub(crate) fn take_cached_paragraph(old: &mut [Option<ParagraphQuads>], next: &mut usize, key: u64, top: f32, generation: u64) -> Option<ParagraphQuads> {
+    let offset = old[*next..].iter().position(|paragraph| {
+        paragraph.as_ref().is_some_and(|p| p.key == key && p.complete && p.generation == generation)
+    })?;
+    let i = *next + offset;
+    *next = i + 1;
+    let mut paragraph = old[i].take()?;
+    paragraph.move_to(top).then_some(paragraph)
+}
+
+impl TextBox {
+    /// Splits the current layout into paragraphs, if it wasn't done since the layout was built.
+    pub(crate) fn update_layout_paragraphs(&mut self) {
+        if !self.render_data_info.layout_paragraphs.is_empty() {
+            return;
+        }
+        if self.layout.is_split() {
+            self.render_data_info.layout_paragraphs = self.layout.paragraphs().iter()
+                .map(|paragraph| LayoutParagraph {
+                    lines: paragraph.first_line..paragraph.first_line + paragraph.layout.len(),
+                    key: Some(paragraph.version),
+                })
+                .collect();
+            return;
+        }
+        self.render_data_info.layout_paragraphs = self.paragraph_line_ranges().into_iter()
+            .map(|lines| LayoutParagraph { lines, key: None })
+            .collect();
+    }
+
+    /// Returns the key of a paragraph of the current layout, hashing it if it's the first time.
+    pub(crate) fn layout_paragraph_key(&mut self, paragraph: usize, top: f32) -> u64 {
+        if let Some(key) = self.render_data_info.layout_paragraphs[paragraph].key {
+            return key;
+        }
+        let key = self.paragraph_key(self.render_data_info.layout_paragraphs[paragraph].lines.clone(), top);
+        self.render_data_info.layout_paragraphs[paragraph].key = Some(key);
+        return key;
+    }
+
+    /// Returns the ranges of layout lines that make up each paragraph, split on hard line breaks.
+    pub(crate) fn paragraph_line_ranges(&self) -> Vec<Range<usize>> {
+        let mut paragraphs = Vec::new();
+        let mut start = 0;
+        for (i, line) in self.layout.lines().enumerate() {
+            let text_range = self.layout_to_text_range(line.text_range());
+            if self.text.get(text_range).is_some_and(|text| text.ends_with('\n')) {
+                paragraphs.push(start..i + 1);
+                start = i + 1;
+            }
+        }
+        if start < self.layout.len() {
+            paragraphs.push(start..self.layout.len());
+        }
+        paragraphs
+    }
+
+    /// Hashes everything that the quads of the paragraph with these lines depend on: the glyphs with their fonts and positions, the colors, whether they're upright, the styles that decorations and effects come from, the inline images, the path, and the positions of the lines relative to `top`.
+    ///
+    /// The glyphs are hashed instead of the text, so that anything that changes the shaping without changing the text, like the locale or the font features of the writing mode, changes the key too. This is only used for boxes whose paragraphs share a layout: the ones with their own layout use its version.
+    fn paragraph_key(&self, lines: Range<usize>, top: f32) -> u64 {
+        let mut hasher = AHasher::default();
+        self.style_version.hash(&mut hasher);
+        self.writing_mode.hash(&mut hasher);
+        if let Some(path) = &self.flattened_path {
+            path.hash_points(&mut hasher);
+        }
+        for line in lines.filter_map(|i| self.layout.get(i)) {
+            let metrics = line.metrics();
+            for value in [metrics.offset, metrics.advance, metrics.baseline - top, metrics.ascent, metrics.descent] {
+                value.to_bits().hash(&mut hasher);
+            }
+            for item in line.items() {
+                let glyph_run = match item {
+                    PositionedLayoutItem::GlyphRun(glyph_run) => glyph_run,
+                    PositionedLayoutItem::InlineBox(inline_box) => {
+                        inline_box.id.hash(&mut hasher);
+                        self.inline_image_for_box_id(inline_box.id).hash(&mut hasher);
+                        for value in [inline_box.x, inline_box.y + line.y - top, inline_box.width, inline_box.height] {
+                            value.to_bits().hash(&mut hasher);
+                        }
+                        continue;
+                    }
+                };
+                let run = glyph_run.run();
+                self.style_key_at(self.layout_to_text_index(run.text_range().start + line.offset)).hash(&mut hasher);
+                run.font().data.id().hash(&mut hasher);
+                run.font_size().to_bits().hash(&mut hasher);
+                run.normalized_coords().hash(&mut hasher);
+                self.is_run_upright(run.text_range()).hash(&mut hasher);
+                glyph_run.style().brush.0.hash(&mut hasher);
+                glyph_run.offset().to_bits().hash(&mut hasher);
+                let mut x = 0.0;
+                for glyph in glyph_run.glyphs() {
+                    glyph.id.hash(&mut hasher);
+                    (x + glyph.x).to_bits().hash(&mut hasher);
+                    glyph.y.to_bits().hash(&mut hasher);
+                    x += glyph.advance;
+                }
+            }
+        }
+        hasher.finish()
+    }
+}
@@ -0,0 +1,399 @@
+use std::ops::Range;
+
+use parley::{Layout, PositionedLayoutItem, YieldData};
+
+use crate::*;
+
+/// Marker drawn before the first line of a list item paragraph.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListMarker {
+    /// A fixed string, like `"•"` or `"-"`.
+    Bullet(String),
+    /// A number followed by a dot. Consecutive numbered paragraphs count up from 1.
+    Numbered,
+}
+
+/// Layout properties of a paragraph. See [`TextBox::set_paragraph_style()`].
+///
+/// Paragraphs are the parts of the text separated by `\n`. All lengths are in logical pixels.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct ParagraphStyle {
+    /// Space before the start of all lines of the paragraph: on the left for left-to-right paragraphs, on the right for right-to-left ones.
+    pub indent: f32,
+    /// Extra space before the start of the first line, added to `indent`. A negative value with a positive `indent` gives a hanging indent.
+    pub first_line_indent: f32,
+    /// Extra space above the paragraph.
+    pub space_before: f32,
+    /// Extra space below the paragraph.
+    pub space_after: f32,
+    /// Marker drawn before the start of the first line. It isn't part of the text, so it can't be selected or copied.
+    ///
+    /// The marker is drawn in the space left by the indents, so they should leave enough room for it.
+    pub marker: Option<ListMarker>,
+    /// Space between the marker and the start of the first line.
+    pub marker_gap: f32,
+}
+
+/// A list marker laid out for the current layout of a box.
+pub(crate) struct ParagraphMarker {
+    pub layout: Layout<ColorBrush>,
+    /// Position of the left edge and of the baseline of the marker, in layout coordinates. In a right-to-left paragraph the marker is on the right of the line.
+    pub x: f32,
+    pub baseline: f32,
+}
+
+/// Lines are broken again until the indents and the heights used for each line match the paragraph where it actually starts and its real height. This is how many times it's tried before giving up.
+const MAX_LINE_BREAK_PASSES: usize = 4;
+
+/// Returns the byte index where each paragraph of `text` starts.
+pub(crate) fn paragraph_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+impl TextBox {
+    /// Sets the paragraph style used for all paragraphs that don't have their own with [`TextBox::set_paragraph_style_for()`].
+    pub fn set_paragraph_style(&mut self, style: Option<ParagraphStyle>) {
+        if self.paragraph_style == style {
+            return;
+        }
+        self.paragraph_style = style;
+        self.paragraph_styles_changed();
+    }
+
+    /// Returns the paragraph style used for paragraphs without their own style.
+    pub fn paragraph_style(&self) -> Option<&ParagraphStyle> {
+        self.paragraph_style.as_ref()
+    }
+
+    /// Sets the style of a single paragraph, by its index. `None` goes back to the style from [`TextBox::set_paragraph_style()`].
+    ///
+    /// When the text is edited through a [`TextEdit`], the style follows its paragraph as paragraphs are added or removed before it. [`TextBox::set_text()`] keeps the indices as they are.
+    pub fn set_paragraph_style_for(&mut self, paragraph: usize, style: Option<ParagraphStyle>) {
+        let existing = self.paragraph_style_overrides.iter().position(|(i, _)| *i == paragraph);
+        match (existing, style) {
+            (Some(i), Some(style)) => {
+                if self.paragraph_style_overrides[i].1 == style {
+                    return;
+                }
+                self.paragraph_style_overrides[i].1 = style;
+            }
+            (Some(i), None) => {
+                self.paragraph_style_overrides.remove(i);
+            }
+            (None, Some(style)) => self.paragraph_style_overrides.push((paragraph, style)),
+            (None, None) => return,
+        }
+        self.paragraph_styles_changed();
+    }
+
+    /// Removes all the styles set with [`TextBox::set_paragraph_style_for()`].
+    pub fn clear_paragraph_styles(&mut self) {
+        if self.paragraph_style_overrides.is_empty() {
+            return;
+        }
+        self.paragraph_style_overrides.clear();
+        self.paragraph_styles_changed();
+    }
+
+    fn paragraph_styles_changed(&mut self) {
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Moves the paragraph styles to the new indices of their paragraphs, before `range` is replaced with `new_text`.
+    ///
+    /// Paragraphs that are merged into the paragraph where the edit starts lose their style. If the edit only inserts text at the start of a paragraph, the style moves with the paragraph, so pressing Enter at the start of a list item leaves the marker on the item.
+    pub(crate) fn map_paragraph_styles_for_replace(&mut self, range: &Range<usize>, new_text: &str) {
+        if self.paragraph_style_overrides.is_empty() {
+            return;
+        }
+        let starts = paragraph_starts(&self.text);
+        let first = starts.partition_point(|&start| start <= range.start) - 1;
+        let removed = self.text[range.clone()].matches('\n').count();
+        let inserted = new_text.matches('\n').count();
+        if removed == 0 && inserted == 0 {
+            return;
+        }
+        let first_moved = if range.start == starts[first] && removed == 0 { first } else { first + 1 };
+
+        self.paragraph_style_overrides.retain_mut(|(paragraph, _)| {
+            if *paragraph < first_moved {
+                true
+            } else if *paragraph <= first + removed {
+                false
+            } else {
+                *paragraph = *paragraph - removed + inserted;
+                true
+            }
+        });
+    }
+
+    pub(crate) fn has_paragraph_styles(&self) -> bool {
+        self.paragraph_style.is_some() || !self.paragraph_style_overrides.is_empty()
+    }
+
+    /// Returns the style of the paragraph with this index.
+    pub(crate) fn paragraph_style_at(&self, paragraph: usize) -> Option<&ParagraphStyle> {
+        self.paragraph_style_overrides.iter()
+            .find(|(i, _)| *i == paragraph)
+            .map(|(_, style)| style)
+            .or(self.paragraph_style.as_ref())
+    }
+
+    /// Breaks the lines of `layout`, applying the indents and the spacing of each paragraph, and moving the lines out of the way of the exclusions.
+    ///
+    /// In a box that continues a paragraph from the previous box of its thread, the first line isn't the first line of its paragraph, so it gets no first line indent and no space before.
+    ///
+    /// `truncation` and `marks` are the ones the layout was built with, which are used to find the paragraph of the text where each line starts. Parley doesn't know about paragraphs or exclusions, so lines are broken one by one with their own position and width. Which paragraph a line starts in and how tall it is are only known after breaking, so the lines are broken again if the guess from the previous pass was wrong.
+    pub(crate) fn break_lines_with_geometry(&self, layout: &mut Layout<ColorBrush>, truncation: Option<&Truncation>, marks: &[usize]) {
+        let scale = layout.scale();
+        let starts = paragraph_starts(&self.text);
+        let is_rtl = self.paragraph_directions(&starts);
+        let default_line_height = self.line_height_at(0);
+
+        // For each line, the paragraph it starts, whether it's the first line of it, and its height.
+        let mut line_info: Vec<(usize, bool, f32)> = Vec::new();
+
+        for _ in 0..MAX_LINE_BREAK_PASSES {
+            // Returns the x, y and width of a line, given where the previous line ended.
+            let line_geometry = |line_i: usize, y: f32| {
+                let (paragraph, is_first, height) = line_info.get(line_i).copied()
+                    .unwrap_or_else(|| match line_info.last() {
+                        Some(&(paragraph, _, height)) => (paragraph, false, height),
+                        None => (0, self.continued_paragraph.is_none(), default_line_height),
+                    });
+                let style = self.paragraph_style_at(paragraph);
+                let indent = style.map_or(0.0, |s| s.indent + if is_first { s.first_line_indent } else { 0.0 }) * scale;
+                let space_before = match style {
+                    Some(style) if is_first => style.space_before * scale,
+                    _ => 0.0,
+                };
+                // The space after a paragraph is added before the first line of the next one.
+                let space_after_previous = match (is_first, paragraph.checked_sub(1)) {
+                    (true, Some(previous)) => self.paragraph_style_at(previous).map_or(0.0, |s| s.space_after) * scale,
+                    _ => 0.0,
+                };
+                let y = y + space_before + space_after_previous;
+                let (left, right) = if is_rtl[paragraph] { (0.0, self.max_advance - indent) } else { (indent, self.max_advance) };
+                let (x, width, y) = self.exclusion_free_span(y, height, left, right);
+                (x, y, width)
+            };
+
+            let mut breaker = layout.break_lines();
+            breaker.state_mut().set_layout_max_advance(self.max_advance);
+
+            let mut line_i = 0;
+            let (x, mut y, width) = line_geometry(line_i, 0.0);
+            breaker.state_mut().set_line_x(x);
+            breaker.state_mut().set_line_y(y);
+            breaker.state_mut().set_line_max_advance(width);
+
+            while let Some(yield_data) = breaker.break_next() {
+                if let YieldData::LineBreak(line_break) = yield_data {
+                    line_i += 1;
+                    let (x, next_y, width) = line_geometry(line_i, y + line_break.line_height);
+                    y = next_y;
+                    breaker.state_mut().set_line_x(x);
+                    breaker.state_mut().set_line_y(y);
+                    breaker.state_mut().set_line_max_advance(width);
+                }
+            }
+            breaker.finish();
+
+            let actual: Vec<(usize, bool, f32)> = layout.lines()
+                .map(|line| {
+                    let (paragraph, is_first) = self.line_start_paragraph(&starts, line.text_range().start, truncation, marks);
+                    (paragraph, is_first, line.metrics().line_height)
+                })
+                .collect();
+
+            if actual == line_info {
+                return;
+            }
+            line_info = actual;
+        }
+    }
+
+    /// Lays out the list markers for the paragraphs that start in the current layout.
+    pub(crate) fn collect_paragraph_markers(&mut self) {
+        self.paragraph_markers.clear();
+        if !self.has_paragraph_styles() {
+            return;
+        }
+
+        let starts = paragraph_starts(&self.text);
+        let is_rtl = self.paragraph_directions(&starts);
+
+        // Numbers count up through consecutive numbered paragraphs.
+        let mut numbers = Vec::with_capacity(starts.len());
+        let mut count = 0;
+        for paragraph in 0..starts.len() {
+            let numbered = matches!(self.paragraph_style_at(paragraph).and_then(|s| s.marker.as_ref()), Some(ListMarker::Numbered));
+            count = if numbered { count + 1 } else { 0 };
+            numbers.push(count);
+        }
+
+        let scale = self.layout.scale();
+        let mut markers = Vec::new();
+
+        for line in self.layout.lines() {
+            let (paragraph, is_first) = self.line_start_paragraph(&starts, line.text_range().start, self.truncation.as_ref(), &self.direction_marks);
+            if !is_first {
+                continue;
+            }
+            let Some(style) = self.paragraph_style_at(paragraph) else {
+                continue;
+            };
+            let marker_text = match &style.marker {
+                Some(ListMarker::Bullet(bullet)) => bullet.clone(),
+                Some(ListMarker::Numbered) => format!("{}.", numbers[paragraph]),
+                None => continue,
+            };
+            // The left and right edges of the line.
+            let Some((line_x0, line_x1)) = line.items()
+                .map(|item| match item {
+                    PositionedLayoutItem::GlyphRun(glyph_run) => (glyph_run.offset(), glyph_run.offset() + glyph_run.advance()),
+                    PositionedLayoutItem::InlineBox(inline_box) => (inline_box.x, inline_box.x + inline_box.width),
+                })
+                .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+            else {
+                continue;
+            };
+
+            let marker_layout = self.build_plain_layout(&marker_text, self.style_key_at(starts[paragraph]));
+            let gap = style.marker_gap * scale;
+
+            markers.push(ParagraphMarker {
+                x: if is_rtl[paragraph] { line_x1 + gap } else { line_x0 - gap - marker_layout.width() },
+                baseline: line.metrics().baseline,
+                layout: marker_layout,
+            });
+        }
+
+        self.paragraph_markers = markers;
+    }
+
+    /// Returns the paragraph of the text where the line starting at `layout_start` starts, and whether it's the first line of that paragraph.
+    ///
+    /// `starts` are the paragraph starts of the full text. A line that starts on the ellipsis belongs to the paragraph of the text after it, and it's never a first line. Neither is the first line of a box that continues a paragraph from the previous box of its thread.
+    fn line_start_paragraph(&self, starts: &[usize], layout_start: usize, truncation: Option<&Truncation>, marks: &[usize]) -> (usize, bool) {
+        let shown = index_without_direction_marks(marks, layout_start);
+        let (index, on_ellipsis) = match truncation {
+            Some(t) if shown >= t.removed.start + t.inserted.len() => (shown - t.removed.start - t.inserted.len() + t.removed.end, false),
+            Some(t) if shown >= t.removed.start => (t.removed.end, true),
+            _ => (shown, false),
+        };
+        let paragraph = starts.partition_point(|&start| start <= index) - 1;
+        let continued = paragraph == 0 && self.continued_paragraph.is_some();
+        return (paragraph, starts[paragraph] == index && !on_ellipsis && !continued);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::NonNull;
+
+    use super::*;
+
+    const WRAPPING: &str = "one two three four five six seven eight nine ten eleven twelve thirteen fourteen";
+
+    fn text_box(shared: &mut Shared, text: &str, style: ParagraphStyle) -> TextBox {
+        let default_style_key = shared.default_style_key;
+        let mut text_box = TextBox::new(text.to_string(), (0.0, 0.0), (200.0, 1000.0), 0.0, default_style_key, NonNull::from(shared));
+        text_box.set_paragraph_style(Some(style));
+        text_box.refresh_layout();
+        return text_box;
+    }
+
+    /// The left and right edges of each line of the layout.
+    fn line_edges(text_box: &TextBox) -> Vec<(f32, f32)> {
+        text_box.layout.lines()
+            .map(|line| line.items()
+                .map(|item| match item {
+                    PositionedLayoutItem::GlyphRun(glyph_run) => (glyph_run.offset(), glyph_run.offset() + glyph_run.advance()),
+                    PositionedLayoutItem::InlineBox(inline_box) => (inline_box.x, inline_box.x + inline_box.width),
+                })
+                .reduce(|a, b| (a.0.min(b.0), a.1.max(b.1)))
+                .unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn indent_moves_every_line() {
+        let mut shared = Shared::new();
+        let text_box = text_box(&mut shared, WRAPPING, ParagraphStyle { indent: 30.0, ..Default::default() });
+        let edges = line_edges(&text_box);
+        assert!(edges.len() > 1);
+        for (x0, x1) in edges {
+            assert!((x0 - 30.0).abs() < 0.01);
+            assert!(x1 <= text_box.max_advance + 0.5);
+        }
+    }
+
+    #[test]
+    fn hanging_indent_moves_the_first_line_back() {
+        let mut shared = Shared::new();
+        let text_box = text_box(&mut shared, WRAPPING, ParagraphStyle { indent: 30.0, first_line_indent: -20.0, ..Default::default() });
+        let edges = line_edges(&text_box);
+        assert!(edges.len() > 1);
+        assert!((edges[0].0 - 10.0).abs() < 0.01);
+        for (x0, _) in &edges[1..] {
+            assert!((x0 - 30.0).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn markers_go_before_the_first_line_of_each_paragraph() {
+        let mut shared = Shared::new();
+        let style = ParagraphStyle { indent: 30.0, marker: Some(ListMarker::Numbered), marker_gap: 5.0, ..Default::default() };
+        let text_box = text_box(&mut shared, &format!("{WRAPPING}\nlast"), style);
+        let edges = line_edges(&text_box);
+        let first_lines: Vec<usize> = text_box.layout.lines()
+            .enumerate()
+            .filter(|(_, line)| line.text_range().start == 0 || text_box.text[..line.text_range().start].ends_with('\n'))
+            .map(|(i, _)| i)
+            .collect();
+
+        assert_eq!(text_box.paragraph_markers.len(), 2);
+        for (marker, line_i) in text_box.paragraph_markers.iter().zip(first_lines) {
+            let line = text_box.layout.get(line_i).unwrap();
+            assert!((marker.x + marker.layout.width() + 5.0 - edges[line_i].0).abs() < 0.01);
+            assert_eq!(marker.baseline, line.metrics().baseline);
+        }
+    }
+
+    #[test]
+    fn right_to_left_paragraphs_are_indented_on_the_right() {
+        let mut shared = Shared::new();
+        let style = ParagraphStyle { indent: 30.0, marker: Some(ListMarker::Bullet("•".to_string())), marker_gap: 5.0, ..Default::default() };
+        let text_box = text_box(&mut shared, "שלום עולם", style);
+        let edges = line_edges(&text_box);
+        assert_eq!(edges.len(), 1);
+        let (_, x1) = edges[0];
+        assert!((x1 - (text_box.max_advance - 30.0)).abs() < 0.5);
+
+        assert_eq!(text_box.paragraph_markers.len(), 1);
+        assert!((text_box.paragraph_markers[0].x - (x1 + 5.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn start_ellipsis_uses_the_style_of_the_shown_paragraph() {
+        let mut shared = Shared::new();
+        let default_style_key = shared.default_style_key;
+        let mut text_box = TextBox::new("first\nsecond\nthird".to_string(), (0.0, 0.0), (200.0, 1000.0), 0.0, default_style_key, NonNull::from(&mut shared));
+        text_box.set_paragraph_style_for(2, Some(ParagraphStyle { indent: 40.0, first_line_indent: 20.0, marker: Some(ListMarker::Numbered), ..Default::default() }));
+        text_box.set_overflow(Overflow::EllipsisStart);
+        text_box.set_max_lines(Some(1));
+        text_box.refresh_layout();
+
+        assert!(text_box.truncation.is_some());
+        let edges = line_edges(&text_box);
+        assert_eq!(edges.len(), 1);
+        // The line starts with the ellipsis, in the middle of the hidden text, so it isn't the first line of the third paragraph.
+        assert!((edges[0].0 - 40.0).abs() < 0.01);
+        assert!(text_box.paragraph_markers.is_empty());
+    }
+}
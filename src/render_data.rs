@@ -1,4 +1,5 @@
 use crate::*;
+use swash::zeno::{Fill, Stroke, Style};
 
 /// Statistics about work done during a render cycle.
 ///
@@ -46,8 +47,8 @@ pub struct RenderData {
     pub(crate) needs_texture_array_rebuild: bool,
     pub(crate) needs_params_sync: bool,
 
-    /// Index of the cursor quad in glyph_quads, if any. Used for cursor-blink-only updates.
-    pub(crate) cursor_quad_index: Option<usize>,
+    /// Range of the cursor quads in glyph_quads, if any. Used for cursor-blink-only updates. A split caret in bidi text has two quads.
+    pub(crate) cursor_quad_range: Option<(usize, usize)>,
 
     /// Generation counter for cache invalidation. Incremented when glyphs are evicted.
     /// QuadStorage compares its cache_generation against this to check validity.
@@ -79,17 +80,53 @@ const SCROLL_TOLERANCE: f32 = 200.0;
 
 /// Key for building a glyph cache
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub(crate) struct GlyphKey {
-    /// Font ID
-    pub font_id: u64,
-    /// Glyph ID
-    pub glyph_id: GlyphId,
-    /// `f32` bits of font size
-    pub font_size_bits: u32,
-    /// Binning of fractional X offset
-    pub x_bin: SubpixelBin::<4>,
-    /// Binning of fractional Y offset
-    pub y_bin: SubpixelBin::<4>,
+pub(crate) enum GlyphKey {
+    /// A glyph from a font.
+    Glyph {
+        /// Font ID
+        font_id: u64,
+        /// Glyph ID
+        glyph_id: GlyphId,
+        /// `f32` bits of font size
+        font_size_bits: u32,
+        /// Binning of fractional X offset
+        x_bin: SubpixelBin::<4>,
+        /// Binning of fractional Y offset
+        y_bin: SubpixelBin::<4>,
+    },
+    /// The outline or the shadow of a glyph, from [`TextEffectStyle`].
+    Effect {
+        font_id: u64,
+        glyph_id: GlyphId,
+        font_size_bits: u32,
+        x_bin: SubpixelBin::<4>,
+        y_bin: SubpixelBin::<4>,
+        kind: EffectKind,
+        /// `f32` bits of the outline width in physical pixels. Shadows of outlined text include the outline.
+        outline_width_bits: u32,
+        /// `f32` bits of the blur radius in physical pixels.
+        blur_radius_bits: u32,
+        /// The effect of an upright glyph in a vertical box, rotated like the glyph.
+        upright: bool,
+    },
+    /// A glyph of a vertical box that stays upright, rasterized rotated by 90 degrees counterclockwise.
+    Upright {
+        font_id: u64,
+        glyph_id: GlyphId,
+        font_size_bits: u32,
+    },
+    /// A registered image, resampled to a specific size.
+    Image {
+        image_id: u32,
+        width: u32,
+        height: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum EffectKind {
+    Outline,
+    Shadow,
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
@@ -164,8 +201,8 @@ pub struct GlyphQuad {
     pub color: u32,                       // 4 bytes
     pub flags_and_page: u32,              // 4 bytes - flags (24 bits) + page_index (8 bits)
     pub box_index: u32,                   // 4 bytes - index into box_data array
-    pub _padding1: u32,               // 8 bytes - padding for alignment
-    pub _padding2: u32,               // 8 bytes - padding for alignment
+    pub rotation_bits: u32,               // 4 bytes - f32 bits of the rotation around the pivot, for text on a path
+    pub pivot_packed: u32,                // 4 bytes - pack pivot x,y as i16,i16
 }
 
 // Helper functions to pack/unpack u16 pairs into u32
@@ -185,11 +222,176 @@ fn pack_i32_pair_as_u16(a: i32, b: i32) -> u32 {
     (a_i16 as u16 as u32) | ((b_i16 as u16 as u32) << 16)
 }
 
+/// Moves quads that were already packed by a whole number of pixels.
+pub(crate) fn offset_glyph_quads(quads: &mut [GlyphQuad], dx: i32, dy: i32) {
+    for quad in quads {
+        let x = (quad.pos_packed & 0xFFFF) as u16 as i16 as i32;
+        let y = (quad.pos_packed >> 16) as u16 as i16 as i32;
+        quad.pos_packed = pack_i32_pair_as_u16(x + dx, y + dy);
+    }
+}
+
+/// Rotates a quad by `angle` radians clockwise around `pivot`, in the same coordinates as its position.
+pub(crate) fn set_quad_rotation(quad: &mut GlyphQuad, angle: f32, pivot: (i32, i32)) {
+    quad.rotation_bits = angle.to_bits();
+    quad.pivot_packed = pack_i32_pair_as_u16(pivot.0, pivot.1);
+}
+
 // Pack flags (24 bits) and page_index (8 bits) into u32
 fn pack_flags_and_page(flags: u32, page_index: u32) -> u32 {
     (flags & 0xFFFFFF) | ((page_index & 0xFF) << 24)
 }
 
+fn pack_color(color: ColorBrush) -> u32 {
+      ((color.0[0] as u32) << 24)
+    + ((color.0[1] as u32) << 16)
+    + ((color.0[2] as u32) << 8)
+    + ((color.0[3] as u32) << 0)
+}
+
+fn make_decoration_quad(x0: i32, y0: i32, x1: i32, y1: i32, color: u32, box_index: u32) -> GlyphQuad {
+    GlyphQuad {
+        pos_packed: pack_i32_pair_as_u16(x0, y0),
+        dim_packed: pack_u16_pair((x1 - x0).max(0) as u32, (y1 - y0).max(0) as u32),
+        uv_origin_packed: pack_u16_pair(0, 0),
+        color,
+        flags_and_page: pack_flags_and_page(CONTENT_TYPE_DECORATION, 0),
+        box_index,
+        rotation_bits: 0,
+        pivot_packed: 0,
+    }
+}
+
+/// Pushes the quads for a single decoration line going from `x0` to `x1`, with its top edge at `top`.
+fn push_decoration_line(
+    buffer: &mut Vec<GlyphQuad>,
+    (x0, x1): (f32, f32),
+    top: f32,
+    thickness: f32,
+    line_style: DecorationLineStyle,
+    color: u32,
+    box_index: u32,
+) {
+    let thickness = thickness.round().max(1.0);
+    let y0 = top.round() as i32;
+    let y1 = y0 + thickness as i32;
+
+    // Patterns are anchored at x = 0 rather than at the start of the run, so that they line up across runs.
+    let pattern = |dash: f32, period: f32, buffer: &mut Vec<GlyphQuad>| {
+        let mut x = (x0 / period).floor() * period;
+        while x < x1 {
+            let start = x.max(x0);
+            let end = (x + dash).min(x1);
+            if end > start {
+                buffer.push(make_decoration_quad(start.round() as i32, y0, end.round() as i32, y1, color, box_index));
+            }
+            x += period;
+        }
+    };
+
+    match line_style {
+        DecorationLineStyle::Solid => {
+            buffer.push(make_decoration_quad(x0.round() as i32, y0, x1.round() as i32, y1, color, box_index));
+        }
+        DecorationLineStyle::Dotted => pattern(thickness, thickness * 2.0, buffer),
+        DecorationLineStyle::Dashed => pattern(thickness * 3.0, thickness * 5.0, buffer),
+        DecorationLineStyle::Wavy => {
+            // Approximate the wave with thin columns. Each column is stretched to reach the previous one, so that steep parts don't leave gaps.
+            let amplitude = thickness * 1.5;
+            let wavelength = thickness * 6.0;
+            let step = thickness.max(2.0);
+            let wave_y = |x: f32| top + amplitude + amplitude * (x * std::f32::consts::TAU / wavelength).sin();
+
+            let mut x = x0;
+            let mut prev_y = wave_y(x0);
+            while x < x1 {
+                let end = (x + step).min(x1);
+                let y = wave_y(end);
+                let col_top = prev_y.min(y).round() as i32;
+                let col_bottom = (prev_y.max(y) + thickness).round() as i32;
+                buffer.push(make_decoration_quad(x.round() as i32, col_top, end.round() as i32, col_bottom, color, box_index));
+                prev_y = y;
+                x = end;
+            }
+        }
+    }
+}
+
+/// Pushes the quads for the background and the border of a box, in the local space of the box.
+/// 
+/// Rounded corners are approximated with one pixel tall strips.
+fn prepare_background_into(background: &BoxBackground, size: (f32, f32), box_index: u32, buffer: &mut Vec<GlyphQuad>) {
+    let (width, height) = size;
+    if width <= 0.0 || height <= 0.0 {
+        return;
+    }
+    let max_radius = width.min(height) / 2.0;
+    let radius = background.corner_radius.clamp(0.0, max_radius);
+    let border_width = background.border_width.clamp(0.0, max_radius);
+    let has_border = border_width > 0.0;
+    let inner_radius = (radius - border_width).max(0.0);
+    let border_color = pack_color(background.border_color);
+    let fill_color = background.color.map(pack_color);
+
+    // How far a corner of the given radius is from the vertical edge, at a distance `dy` from the horizontal edge.
+    let corner_inset = |radius: f32, dy: f32| -> f32 {
+        if dy >= radius {
+            return 0.0;
+        }
+        let d = radius - dy;
+        radius - (radius * radius - d * d).max(0.0).sqrt()
+    };
+
+    let mut push_span = |x0: f32, x1: f32, y0: f32, y1: f32, color: u32| {
+        let (x0, x1, y0, y1) = (x0.round() as i32, x1.round() as i32, y0.round() as i32, y1.round() as i32);
+        if x1 > x0 && y1 > y0 {
+            buffer.push(make_decoration_quad(x0, y0, x1, y1, color, box_index));
+        }
+    };
+
+    let mut push_strip = |y0: f32, y1: f32| {
+        let mid = (y0 + y1) / 2.0;
+        let dy = mid.min(height - mid);
+        let outer = corner_inset(radius, dy);
+
+        if has_border && dy < border_width {
+            push_span(outer, width - outer, y0, y1, border_color);
+            return;
+        }
+
+        let inner = if has_border {
+            border_width + corner_inset(inner_radius, dy - border_width)
+        } else {
+            outer
+        };
+        if has_border {
+            push_span(outer, inner, y0, y1, border_color);
+            push_span(width - inner, width - outer, y0, y1, border_color);
+        }
+        if let Some(fill_color) = fill_color {
+            push_span(inner, width - inner, y0, y1, fill_color);
+        }
+    };
+
+    // Curved parts and the horizontal borders go in thin strips, the straight middle part in a single one.
+    let curved = radius.max(border_width).ceil().min(height / 2.0);
+    let mut y = 0.0;
+    while y < curved {
+        let y1 = (y + 1.0).min(curved);
+        push_strip(y, y1);
+        y = y1;
+    }
+    if height - curved > curved {
+        push_strip(curved, height - curved);
+    }
+    let mut y = (height - curved).max(curved);
+    while y < height {
+        let y1 = (y + 1.0).min(height);
+        push_strip(y, y1);
+        y = y1;
+    }
+}
+
 fn create_box_data(clip_rect: Option<parley::BoundingBox>, scroll_offset: (f32, f32), transform: Transform2D, screen_clip: Option<(f32, f32, f32, f32)>, depth: f32) -> BoxGpu {
     // clip_rect from effective_clip_rect() is already in layout-local coordinates (includes scroll_offset)
     let (clip_rect_x, clip_rect_y) = if let Some(clip) = clip_rect {
@@ -243,8 +445,8 @@ fn make_glyph_quad(glyph: &GlyphWithContext, stored_glyph: &StoredGlyph, box_ind
         color,
         flags_and_page: pack_flags_and_page(flags, stored_glyph.page as u32),
         box_index,
-        _padding1: 0,
-        _padding2: 0,
+        rotation_bits: 0,
+        pivot_packed: 0,
     };
 }
 
@@ -305,6 +507,11 @@ const SOURCES: &[Source; 3] = &[
     Source::Outline,
 ];
 
+// Effects are stroked and filled, so they only work with outlines.
+const EFFECT_SOURCES: &[Source; 1] = &[
+    Source::Outline,
+];
+
 /// A glyph with the context in which it is being drawn
 struct GlyphWithContext {
     glyph: Glyph,
@@ -327,17 +534,13 @@ impl GlyphWithContext {
         let (quantized_pos_x, frac_pos_x, subpixel_bin_x) = quantize(glyph_x);
         let (quantized_pos_y, frac_pos_y, subpixel_bin_y) = quantize(glyph_y);
 
-        let color =
-          ((color.0[0] as u32) << 24)
-        + ((color.0[1] as u32) << 16)
-        + ((color.0[2] as u32) << 8)
-        + ((color.0[3] as u32) << 0);
+        let color = pack_color(color);
 
         Self { glyph, color, font_key, font_size, quantized_pos_x, quantized_pos_y, frac_pos_x, frac_pos_y, subpixel_bin_x, subpixel_bin_y,}
     }
 
     fn key(&self) -> GlyphKey {
-        GlyphKey {
+        GlyphKey::Glyph {
             font_id: self.font_key,
             glyph_id: self.glyph.id as u16,
             font_size_bits: self.font_size.to_bits(),
@@ -349,8 +552,79 @@ impl GlyphWithContext {
     fn frac_offset(&self) -> Vector {
         Vector::new(self.frac_pos_x, self.frac_pos_y)
     }
+
+    fn effect_key(&self, kind: EffectKind, outline_width: f32, blur_radius: f32, upright: bool) -> GlyphKey {
+        // Upright glyphs are rasterized without a subpixel offset.
+        let (x_bin, y_bin) = if upright { (SubpixelBin(0), SubpixelBin(0)) } else { (self.subpixel_bin_x, self.subpixel_bin_y) };
+        GlyphKey::Effect {
+            font_id: self.font_key,
+            glyph_id: self.glyph.id as u16,
+            font_size_bits: self.font_size.to_bits(),
+            x_bin,
+            y_bin,
+            kind,
+            outline_width_bits: outline_width.to_bits(),
+            blur_radius_bits: blur_radius.to_bits(),
+            upright,
+        }
+    }
+}
+
+/// How the mask of an outline or a shadow is rendered.
+#[derive(Clone, Copy, Debug)]
+struct EffectMask {
+    /// Width of the stroke out from the contour, in physical pixels.
+    outline_width: f32,
+    /// Fill the inside of the stroke, for shadows.
+    fill: bool,
+    /// Blur radius in physical pixels.
+    blur_radius: f32,
 }
 
+/// Returns the placement of a glyph image of an upright glyph after it's rotated by 90 degrees counterclockwise.
+///
+/// `left`, `top` and `width` are the placement and the width of the image before the rotation. The new placement is relative to the pen position along the column and to the center of the column: the top of the em box goes at the pen, and the middle of the horizontal advance goes at the center.
+fn upright_placement(left: i32, top: i32, width: u32, ascent: f32, advance: f32) -> (i32, i32) {
+    let placement_left = (ascent - top as f32).round() as i32;
+    let placement_top = (left as f32 + width as f32 - advance / 2.0).round() as i32;
+    return (placement_left, placement_top);
+}
+
+
+/// Pushes the underline, strikethrough and overline quads for a glyph run.
+fn prepare_decorations_into(
+    glyph_run: &GlyphRun<'_, ColorBrush>,
+    decoration_style: &DecorationStyle,
+    scale: f32,
+    box_index: u32,
+    buffer: &mut Vec<GlyphQuad>,
+) {
+    let style = glyph_run.style();
+    let metrics = glyph_run.run().metrics();
+    let x0 = glyph_run.offset();
+    let x1 = x0 + glyph_run.advance();
+    let baseline = glyph_run.baseline();
+    let line_style = decoration_style.line_style;
+
+    if let Some(underline) = &style.underline {
+        let offset = underline.offset.unwrap_or(metrics.underline_offset);
+        let size = underline.size.unwrap_or(metrics.underline_size);
+        push_decoration_line(buffer, (x0, x1), baseline - offset, size, line_style, pack_color(underline.brush), box_index);
+    }
+
+    if let Some(strikethrough) = &style.strikethrough {
+        let offset = strikethrough.offset.unwrap_or(metrics.strikethrough_offset);
+        let size = strikethrough.size.unwrap_or(metrics.strikethrough_size);
+        push_decoration_line(buffer, (x0, x1), baseline - offset, size, line_style, pack_color(strikethrough.brush), box_index);
+    }
+
+    if decoration_style.has_overline {
+        let offset = decoration_style.overline_offset.map(|o| o * scale).unwrap_or(0.0);
+        let size = decoration_style.overline_size.map(|s| s * scale).unwrap_or(metrics.underline_size);
+        let brush = decoration_style.overline_brush.unwrap_or(style.brush);
+        push_decoration_line(buffer, (x0, x1), baseline - metrics.ascent + offset, size, line_style, pack_color(brush), box_index);
+    }
+}
 
 trait UselessTrait2 {
     fn size(&self) -> Size2D<i32, UnknownUnit>;
@@ -405,7 +679,7 @@ impl RenderData {
             needs_box_data_sync: true,
             needs_texture_array_rebuild: false,
             needs_params_sync: true,
-            cursor_quad_index: None,
+            cursor_quad_range: None,
             glyph_cache_generation: 1, // Start at 1 so that default QuadStorage (generation 0) is invalid
             scale_cx: Some(ScaleContext::new()),
             stats: RenderStats::default(),
@@ -468,16 +742,39 @@ impl RenderData {
             color,
             flags_and_page: pack_flags_and_page(CONTENT_TYPE_DECORATION, 0),
             box_index,
-            _padding1: 0,
-            _padding2: 0,
+            rotation_bits: 0,
+            pivot_packed: 0,
         });
     }
 
+    /// Pushes a solid rect. With a path and the baseline that goes on it, the rect is split into narrow pieces that are each moved onto the path.
+    fn push_selection_rect(&mut self, rect: parley::BoundingBox, color: u32, path: Option<(&FlattenedPath, f32)>, box_index: u32) {
+        let Some((path, baseline)) = path else {
+            if let Some(quad) = self.make_selection_rect(rect, color, box_index) {
+                self.glyph_quads.push(quad);
+            }
+            return;
+        };
+
+        // Whole pixel pieces, so that there are no gaps between them when they're rounded.
+        let end = rect.x1.floor();
+        let mut x0 = rect.x0.floor();
+        while x0 < end {
+            let x1 = (x0 + PATH_RECT_PIECE_WIDTH as f64).min(end);
+            let piece = parley::BoundingBox { x0, x1, ..rect };
+            if let Some(mut quad) = self.make_selection_rect(piece, color, box_index) {
+                path.place_quad(&mut quad, ((x0 + x1) / 2.0) as f32, baseline);
+                self.glyph_quads.push(quad);
+            }
+            x0 = x1;
+        }
+    }
+
     /// Clear all render data for text and decorations from the renderer.
     pub fn clear(&mut self) {
         self.frame += 1;
         self.glyph_quads.clear();
-        self.cursor_quad_index = None;
+        self.cursor_quad_range = None;
     }
 
     /// Get the render stats from the last frame. Only available in debug builds.
@@ -544,17 +841,46 @@ impl RenderData {
         let clip_rect = text_box.effective_clip_rect();
         let screen_clip = text_box.screen_space_clip_rect;
         let scroll_offset = text_box.scroll_offset();
+        let (content_x, content_y) = text_box.content_offset();
 
-        // Update BoxGpu
+        // Update BoxGpu. The padding moves the text the opposite way of the scroll.
         let box_index = text_box.render_data_info.box_index;
         *self.box_data.get_mut(box_index) = create_box_data(
             clip_rect,
-            scroll_offset,
-            text_box.transform(),
+            (scroll_offset.0 - content_x, scroll_offset.1 - content_y),
+            text_box.frame_transform(),
             screen_clip,
             text_box.depth
         );
 
+        // The background uses its own BoxGpu without scroll and clip. It's cheap enough to not be cached.
+        // The BoxGpu is only allocated while the background is visible, so boxes without one don't use a second slot.
+        if text_box.background.is_visible() {
+            let background_box_index = *text_box.render_data_info.background_box_index
+                .get_or_insert_with(|| self.box_data.insert(BoxGpu::zeroed()));
+            *self.box_data.get_mut(background_box_index) = create_box_data(
+                None,
+                (0.0, 0.0),
+                text_box.frame_transform(),
+                screen_clip,
+                text_box.depth
+            );
+            prepare_background_into(&text_box.background, (text_box.width, text_box.height), background_box_index as u32, &mut self.glyph_quads);
+        } else if let Some(background_box_index) = text_box.render_data_info.background_box_index.take() {
+            self.box_data.remove(background_box_index);
+        }
+
+        // Rects for the selection, the cursor and the highlights bend with the text if it's on a path.
+        let path = text_box.flattened_path.as_ref().map(|path| (path, text_box.path_baseline()));
+
+        // Highlights are behind the glyphs, so they go before the cached quads. Like the selection, they're not cached.
+        for highlight in &text_box.highlights {
+            let color = pack_color(highlight.color);
+            text_box.highlight_geometry_with(highlight, |rect| {
+                self.push_selection_rect(rect, color, path, box_index as u32);
+            });
+        }
+
         // Rebuild cached quads if invalid (generation mismatch means either text changed or glyphs were evicted)
         if text_box.render_data_info.cache_generation != self.glyph_cache_generation {
             text_box.render_data_info.cached_glyph_quads.clear();
@@ -565,32 +891,89 @@ impl RenderData {
             } else {
                 (0.0, self.params.screen_resolution_height)
             };
-
-            for line in text_box.layout.lines() {
-                let metrics = line.metrics();
-                let line_y = metrics.baseline;
-
-                // Cull lines with tolerance to allow for scroll optimization
-                let line_top = line_y - metrics.ascent;
-                let line_bottom = line_y + metrics.descent;
-
-                if line_bottom < clip_top - SCROLL_TOLERANCE || line_top > clip_bottom + SCROLL_TOLERANCE {
+            // Cull lines with tolerance to allow for scroll optimization
+            let (cull_top, cull_bottom) = (clip_top - SCROLL_TOLERANCE, clip_bottom + SCROLL_TOLERANCE);
+
+            // Quads are prepared paragraph by paragraph. After an edit, the paragraphs that didn't change are taken from the last preparation and moved to their new position.
+            let mut old_paragraphs: Vec<Option<ParagraphQuads>> = mem::take(&mut text_box.render_data_info.paragraph_quads).into_iter().map(Some).collect();
+            let mut next_old = 0;
+            let mut paragraphs = Vec::new();
+
+            text_box.update_layout_paragraphs();
+            for paragraph_i in 0..text_box.render_data_info.layout_paragraphs.len() {
+                let lines = text_box.render_data_info.layout_paragraphs[paragraph_i].lines.clone();
+                let (Some(first_line), Some(last_line)) = (text_box.layout.get(lines.start), text_box.layout.get(lines.end - 1)) else {
+                    continue;
+                };
+                let top = first_line.metrics().baseline - first_line.metrics().ascent;
+                let bottom = last_line.metrics().baseline + last_line.metrics().descent;
+                if bottom < cull_top || top > cull_bottom {
                     continue;
                 }
 
-                for item in line.items() {
-                    match item {
-                        PositionedLayoutItem::GlyphRun(glyph_run) => {
-                            self.prepare_glyph_run_into(&glyph_run, box_index as u32, &mut text_box.render_data_info.cached_glyph_quads);
+                let key = text_box.layout_paragraph_key(paragraph_i, top);
+                let complete = top >= cull_top && bottom <= cull_bottom;
+                let cached = if complete {
+                    take_cached_paragraph(&mut old_paragraphs, &mut next_old, key, top, self.glyph_cache_generation)
+                } else {
+                    None
+                };
+
+                let paragraph = match cached {
+                    Some(paragraph) => paragraph,
+                    None => {
+                        let mut paragraph = ParagraphQuads::new(key, top);
+                        paragraph.complete = complete;
+                        for line in lines.filter_map(|i| text_box.layout.get(i)) {
+                            let metrics = line.metrics();
+                            let line_top = metrics.baseline - metrics.ascent;
+                            let line_bottom = metrics.baseline + metrics.descent;
+                            if line_bottom < cull_top || line_top > cull_bottom {
+                                continue;
+                            }
+                            self.prepare_line_into(text_box, &line, box_index as u32, &mut paragraph);
                         }
-                        PositionedLayoutItem::InlineBox(_inline_box) => {}
+                        paragraph
+                    }
+                };
+                paragraphs.push(paragraph);
+            }
+
+            // Effects go before all the glyphs of the box.
+            let cached_glyph_quads = &mut text_box.render_data_info.cached_glyph_quads;
+            for paragraph in &paragraphs {
+                cached_glyph_quads.extend_from_slice(&paragraph.shadows);
+            }
+            for paragraph in &paragraphs {
+                cached_glyph_quads.extend_from_slice(&paragraph.outlines);
+            }
+            for paragraph in &paragraphs {
+                cached_glyph_quads.extend_from_slice(&paragraph.glyphs);
+            }
+
+            // List markers have their own small layouts, which are moved next to their line after preparing the quads.
+            for marker in &text_box.paragraph_markers {
+                let Some(marker_line) = marker.layout.lines().next() else {
+                    continue;
+                };
+                let first_quad = text_box.render_data_info.cached_glyph_quads.len();
+                for item in marker_line.items() {
+                    if let PositionedLayoutItem::GlyphRun(glyph_run) = item {
+                        self.prepare_glyph_run_into(&glyph_run, None, box_index as u32, &mut text_box.render_data_info.cached_glyph_quads);
                     }
                 }
+                let dx = marker.x.round() as i32;
+                let dy = (marker.baseline - marker_line.metrics().baseline).round() as i32;
+                offset_glyph_quads(&mut text_box.render_data_info.cached_glyph_quads[first_quad..], dx, dy);
             }
 
             text_box.render_data_info.base_scroll = scroll_offset;
             text_box.render_data_info.last_scroll = scroll_offset;
 
+            for paragraph in &mut paragraphs {
+                paragraph.generation = self.glyph_cache_generation;
+            }
+            text_box.render_data_info.paragraph_quads = paragraphs;
             text_box.render_data_info.cache_generation = self.glyph_cache_generation;
         }
 
@@ -601,21 +984,20 @@ impl RenderData {
         if show_selection {
             let selection_color = 0x33_33_ff_aa;
             text_box.selection().geometry_with(&text_box.layout, |rect, _line_i| {
-                let rect = self.make_selection_rect(rect, selection_color, box_index as u32);
-                if let Some(rect) = rect {
-                    self.glyph_quads.push(rect);
-                }
+                self.push_selection_rect(rect, selection_color, path, box_index as u32);
             });
         }
 
         let show_cursor = show_cursor && text_box.selection().is_collapsed();
         if show_cursor {
-            let size = CURSOR_WIDTH;
-            let cursor_rect = text_box.selection().focus().geometry(&text_box.layout, size);
-            let cursor_rect = self.make_selection_rect(cursor_rect, CURSOR_COLOR, box_index as u32);
-            if let Some(cursor_rect) = cursor_rect {
-                self.cursor_quad_index = Some(self.glyph_quads.len());
-                self.glyph_quads.push(cursor_rect);
+            let (cursor_rects, is_split) = text_box.caret_rects(CURSOR_WIDTH);
+            let cursor_rects = if is_split { &cursor_rects[..] } else { &cursor_rects[..1] };
+            let cursor_start = self.glyph_quads.len();
+            for &cursor_rect in cursor_rects {
+                self.push_selection_rect(cursor_rect, CURSOR_COLOR, path, box_index as u32);
+            }
+            if self.glyph_quads.len() > cursor_start {
+                self.cursor_quad_range = Some((cursor_start, self.glyph_quads.len()));
             }
         }
 
@@ -630,11 +1012,66 @@ impl RenderData {
         text_box.render_data_info.glyph_quad_range = Some((start_index, end_index));
     }
 
+    /// Prepare the glyphs, decorations, effects and inline images of a line into the quads of its paragraph.
+    ///
+    /// The line is positioned in the layout of its paragraph, so the quads are prepared there and then moved down by the top of the paragraph.
+    fn prepare_line_into(&mut self, text_box: &TextBox, text_line: &TextLine<'_>, box_index: u32, paragraph: &mut ParagraphQuads) {
+        let first_quads = [paragraph.glyphs.len(), paragraph.shadows.len(), paragraph.outlines.len()];
+        let line = &text_line.line;
+        let metrics = line.metrics();
+        let line_y = metrics.baseline;
+        for item in line.items() {
+            match item {
+                PositionedLayoutItem::GlyphRun(glyph_run) => {
+                    let upright = text_box.is_run_upright(glyph_run.run().text_range());
+                    if upright {
+                        let line_center = line_y + (metrics.descent - metrics.ascent) / 2.0;
+                        self.prepare_upright_glyph_run_into(&glyph_run, line_center, box_index, &mut paragraph.glyphs);
+                    } else {
+                        self.prepare_glyph_run_into(&glyph_run, text_box.flattened_path.as_ref(), box_index, &mut paragraph.glyphs);
+                    }
+
+                    let style_key = text_box.style_key_at(text_box.layout_to_text_index(glyph_run.run().text_range().start + text_line.offset));
+                    let decoration_style = text_box.shared().styles[style_key].decoration_style;
+                    // Decoration lines are straight, so they aren't drawn on a path.
+                    if text_box.flattened_path.is_none() {
+                        prepare_decorations_into(&glyph_run, &decoration_style, text_box.layout.scale(), box_index, &mut paragraph.glyphs);
+                    }
+
+                    let effect_style = text_box.shared().styles[style_key].effect_style;
+                    if effect_style.outline.is_some() || effect_style.shadow.is_some() {
+                        let upright_center = upright.then(|| line_y + (metrics.descent - metrics.ascent) / 2.0);
+                        self.prepare_glyph_run_effects_into(&glyph_run, &effect_style, text_box, upright_center, box_index, paragraph);
+                    }
+                }
+                // The content of regular inline boxes is drawn by the user, at the positions from TextBox::inline_box_rect().
+                PositionedLayoutItem::InlineBox(inline_box) => {
+                    if let Some(image_id) = text_box.inline_image_for_box_id(inline_box.id) {
+                        if let Some(Some(source)) = text_box.shared().images.get(image_id.0 as usize) {
+                            self.prepare_inline_image_into(image_id, source, &inline_box, box_index, &mut paragraph.glyphs);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Paragraph tops are whole pixels, so the quads end up the same as if they were prepared in place.
+        let dy = text_line.y as i32;
+        if dy != 0 {
+            for (quads, first) in [&mut paragraph.glyphs, &mut paragraph.shadows, &mut paragraph.outlines].into_iter().zip(first_quads) {
+                offset_glyph_quads(&mut quads[first..], 0, dy);
+            }
+        }
+    }
+
     /// Prepare a glyph run and push quads to a target Vec.
     /// Used for caching quads per text box.
+    ///
+    /// With a `path`, each glyph is moved onto it by the middle of its advance.
     fn prepare_glyph_run_into(
         &mut self,
         glyph_run: &GlyphRun<'_, ColorBrush>,
+        path: Option<&FlattenedPath>,
         box_index: u32,
         buffer: &mut Vec<GlyphQuad>
     ) {
@@ -654,6 +1091,7 @@ impl RenderData {
 
         for glyph in glyph_run.glyphs() {
             let glyph_ctx = GlyphWithContext::new(glyph, run_x, run_y, font_key, font_size, style.brush);
+            let first_quad = buffer.len();
 
             if let Some(stored_glyph) = self.glyph_cache.get(&glyph_ctx.key()) {
                 if let Some(stored_glyph) = stored_glyph {
@@ -681,6 +1119,13 @@ impl RenderData {
                 }
             }
 
+            if let Some(path) = path {
+                let anchor_x = run_x + glyph.x + glyph.advance / 2.0;
+                for quad in &mut buffer[first_quad..] {
+                    path.place_quad(quad, anchor_x, run_y);
+                }
+            }
+
             run_x += glyph.advance;
         }
 
@@ -688,6 +1133,328 @@ impl RenderData {
         self.scale_cx = Some(scale_cx);
     }
 
+    /// Prepare a glyph run of a vertical box that stays upright, and push the quads to a target Vec.
+    ///
+    /// The whole box is rotated by 90 degrees clockwise when it's drawn, so the glyphs are rasterized rotated the other way. Each glyph starts at its position along the column and is centered on `line_center` across it.
+    fn prepare_upright_glyph_run_into(
+        &mut self,
+        glyph_run: &GlyphRun<'_, ColorBrush>,
+        line_center: f32,
+        box_index: u32,
+        buffer: &mut Vec<GlyphQuad>
+    ) {
+        let mut run_x = glyph_run.offset();
+        let style = glyph_run.style();
+
+        let run = glyph_run.run();
+
+        let font = run.font();
+        let font_size = run.font_size();
+        let font_key = font.data.id();
+        let ascent = run.metrics().ascent;
+        let font_ref = FontRef::from_index(font.data.as_ref(), font.index as usize).unwrap();
+        // The advances in the layout include the spacing from the vertical metrics, so the glyphs are centered with their own advance.
+        let glyph_metrics = font_ref.glyph_metrics(run.normalized_coords()).scale(font_size);
+
+        let mut scale_cx = self.scale_cx.take().unwrap();
+        let mut scaler: Option<Scaler> = None;
+
+        for glyph in glyph_run.glyphs() {
+            let glyph_ctx = GlyphWithContext::new(glyph, run_x, line_center, font_key, font_size, style.brush);
+            let key = GlyphKey::Upright {
+                font_id: font_key,
+                glyph_id: glyph.id as u16,
+                font_size_bits: font_size.to_bits(),
+            };
+
+            let stored_glyph = match self.glyph_cache.get(&key) {
+                Some(stored_glyph) => *stored_glyph,
+                None => {
+                    if scaler.is_none() {
+                        scaler = Some(
+                            scale_cx
+                                .builder(font_ref)
+                                .size(font_size)
+                                .hint(true)
+                                .normalized_coords(run.normalized_coords())
+                                .build()
+                        );
+                    }
+                    let advance = glyph_metrics.advance_width(glyph.id as u16);
+                    self.prepare_upright_glyph(key, glyph.id as u16, advance, ascent, scaler.as_mut().unwrap())
+                }
+            };
+
+            if let Some(stored_glyph) = stored_glyph {
+                buffer.push(make_glyph_quad(&glyph_ctx, &stored_glyph, box_index));
+                #[cfg(debug_assertions)] {
+                    self.stats.glyph_quads_built += 1;
+                }
+            }
+
+            run_x += glyph.advance;
+        }
+
+        self.scale_cx = Some(scale_cx);
+    }
+
+    /// Rasterizes an upright glyph rotated by 90 degrees counterclockwise into the atlas and stores it in the cache under `key`. The placement is the one from [`upright_placement()`].
+    fn prepare_upright_glyph(
+        &mut self,
+        key: GlyphKey,
+        glyph_id: GlyphId,
+        advance: f32,
+        ascent: f32,
+        scaler: &mut Scaler,
+    ) -> Option<StoredGlyph> {
+        #[cfg(debug_assertions)] {
+            self.stats.glyphs_rasterized += 1;
+        }
+
+        self.tmp_image.clear();
+        Render::new(SOURCES)
+            .format(Format::Alpha)
+            .render_into(scaler, glyph_id, &mut self.tmp_image);
+        let content = self.tmp_image.content;
+        let placement = self.tmp_image.placement;
+
+        let (width, height) = (placement.width, placement.height);
+        let rotated = match content {
+            _ if width == 0 || height == 0 => None,
+            Content::Mask => GrayImage::from_raw(width, height, mem::take(&mut self.tmp_image.data))
+                .map(|image| image::imageops::rotate270(&image).into_raw()),
+            Content::Color => RgbaImage::from_raw(width, height, mem::take(&mut self.tmp_image.data))
+                .map(|image| image::imageops::rotate270(&image).into_raw()),
+            Content::SubpixelMask => unreachable!(),
+        };
+        let Some(rotated) = rotated else {
+            self.glyph_cache.push(key, None);
+            return None;
+        };
+        self.tmp_image.data = rotated;
+
+        let size = size2(height as i32, width as i32);
+        let (placement_left, placement_top) = upright_placement(placement.left, placement.top, width, ascent, advance);
+        let stored_glyph = self.allocate_in_atlas(size, content).map(|(alloc, page)| {
+            self.copy_glyph_to_atlas(size, &alloc, page, content);
+            StoredGlyph {
+                content_type: content,
+                page: page as u16,
+                frame: self.frame,
+                alloc,
+                placement_left,
+                placement_top,
+                size,
+            }
+        });
+
+        self.glyph_cache.push(key, stored_glyph);
+        return stored_glyph;
+    }
+
+    /// Prepare the shadow and outline quads for a glyph run, pushing them into the shadows and the outlines of the paragraph.
+    ///
+    /// For a run of upright glyphs in a vertical box, `upright_center` is the center of the column, and the effects are rotated and placed like the glyphs in [`RenderData::prepare_upright_glyph_run_into()`].
+    fn prepare_glyph_run_effects_into(
+        &mut self,
+        glyph_run: &GlyphRun<'_, ColorBrush>,
+        effect_style: &TextEffectStyle,
+        text_box: &TextBox,
+        upright_center: Option<f32>,
+        box_index: u32,
+        paragraph: &mut ParagraphQuads,
+    ) {
+        let scale = text_box.layout.scale();
+        let path = text_box.flattened_path.as_ref();
+        let mut run_x = glyph_run.offset();
+        let run_y = upright_center.unwrap_or(glyph_run.baseline());
+        let run = glyph_run.run();
+
+        let font = run.font();
+        let font_size = run.font_size();
+        let font_key = font.data.id();
+        let ascent = run.metrics().ascent;
+        let font_ref = FontRef::from_index(font.data.as_ref(), font.index as usize).unwrap();
+        let glyph_metrics = font_ref.glyph_metrics(run.normalized_coords()).scale(font_size);
+        let upright = upright_center.is_some();
+
+        let outline_width = effect_style.outline.map(|o| (o.width * scale).max(0.0)).unwrap_or(0.0);
+
+        let mut scale_cx = self.scale_cx.take().unwrap();
+        let mut scaler: Option<Scaler> = None;
+
+        for glyph in glyph_run.glyphs() {
+            let mut effects = [None, None];
+            if let Some(shadow) = &effect_style.shadow {
+                let (dx, dy) = (shadow.offset.0 * scale, shadow.offset.1 * scale);
+                let blur_radius = (shadow.blur_radius * scale).max(0.0);
+                let glyph_ctx = GlyphWithContext::new(glyph, run_x + dx, run_y + dy, font_key, font_size, shadow.color);
+                let key = glyph_ctx.effect_key(EffectKind::Shadow, outline_width, blur_radius, upright);
+                effects[0] = Some((glyph_ctx, key, EffectMask { outline_width, fill: true, blur_radius }));
+            }
+            if let Some(outline) = &effect_style.outline {
+                let glyph_ctx = GlyphWithContext::new(glyph, run_x, run_y, font_key, font_size, outline.color);
+                let key = glyph_ctx.effect_key(EffectKind::Outline, outline_width, 0.0, upright);
+                effects[1] = Some((glyph_ctx, key, EffectMask { outline_width, fill: false, blur_radius: 0.0 }));
+            }
+
+            for (i, effect) in effects.into_iter().enumerate() {
+                let Some((glyph_ctx, key, effect)) = effect else {
+                    continue;
+                };
+                let buffer = if i == 0 { &mut paragraph.shadows } else { &mut paragraph.outlines };
+
+                let stored_glyph = match self.glyph_cache.get(&key) {
+                    Some(stored_glyph) => *stored_glyph,
+                    None => {
+                        if scaler.is_none() {
+                            scaler = Some(
+                                scale_cx
+                                    .builder(font_ref)
+                                    .size(font_size)
+                                    .hint(true)
+                                    .normalized_coords(run.normalized_coords())
+                                    .build()
+                            );
+                        }
+                        let upright_metrics = upright.then(|| (ascent, glyph_metrics.advance_width(glyph.id as u16)));
+                        self.prepare_effect_glyph(&glyph_ctx, key, scaler.as_mut().unwrap(), effect, upright_metrics)
+                    }
+                };
+
+                if let Some(stored_glyph) = stored_glyph {
+                    let mut quad = make_glyph_quad(&glyph_ctx, &stored_glyph, box_index);
+                    // The anchor is the one of the glyph itself, so that shadows keep their offset from it.
+                    if let Some(path) = path {
+                        path.place_quad(&mut quad, run_x + glyph.x + glyph.advance / 2.0, run_y);
+                    }
+                    buffer.push(quad);
+                }
+            }
+
+            run_x += glyph.advance;
+        }
+
+        self.scale_cx = Some(scale_cx);
+    }
+
+    /// Rasterizes an effect mask into the mask atlas and stores it in the cache under `key`.
+    ///
+    /// For an upright glyph, `upright_metrics` has the ascent of the run and the advance of the glyph, and the mask is rotated like in [`RenderData::prepare_upright_glyph()`].
+    fn prepare_effect_glyph(
+        &mut self,
+        glyph: &GlyphWithContext,
+        key: GlyphKey,
+        scaler: &mut Scaler,
+        effect: EffectMask,
+        upright_metrics: Option<(f32, f32)>,
+    ) -> Option<StoredGlyph> {
+        #[cfg(debug_assertions)] {
+            self.stats.glyphs_rasterized += 1;
+        }
+
+        let offset = if upright_metrics.is_some() { Vector::new(0.0, 0.0) } else { glyph.frac_offset() };
+        let mask = self.render_effect_mask(glyph, offset, scaler, effect);
+        let mask = match (mask, upright_metrics) {
+            (Some((mask, left, top)), Some((ascent, advance))) => {
+                let (left, top) = upright_placement(left, top, mask.width(), ascent, advance);
+                Some((image::imageops::rotate270(&mask), left, top))
+            }
+            (mask, _) => mask,
+        };
+
+        let stored_glyph = match mask {
+            Some((mask, left, top)) => {
+                let size = size2(mask.width() as i32, mask.height() as i32);
+                self.allocate_in_atlas(size, Content::Mask).map(|(alloc, page)| {
+                    let atlas_page = &mut self.mask_atlas_pages[page];
+                    image::imageops::replace(&mut atlas_page.image, &mask, alloc.rectangle.min.x as i64, alloc.rectangle.min.y as i64);
+                    atlas_page.needs_upload = true;
+
+                    StoredGlyph {
+                        content_type: Content::Mask,
+                        page: page as u16,
+                        frame: self.frame,
+                        alloc,
+                        placement_left: left,
+                        placement_top: top,
+                        size,
+                    }
+                })
+            }
+            None => None,
+        };
+
+        self.glyph_cache.push(key, stored_glyph);
+        return stored_glyph;
+    }
+
+    /// Renders the mask for an outline or a shadow: the glyph stroked `outline_width` pixels out from its contour, also filled if `fill` is set, and then blurred. See [`EffectMask`].
+    /// 
+    /// Returns the mask with its left and top placement, or `None` if it's empty.
+    fn render_effect_mask(
+        &mut self,
+        glyph: &GlyphWithContext,
+        offset: Vector,
+        scaler: &mut Scaler,
+        EffectMask { outline_width, fill, blur_radius }: EffectMask,
+    ) -> Option<(GrayImage, i32, i32)> {
+        let mut render = |style: Style<'static>, image: &mut Image| {
+            image.clear();
+            Render::new(EFFECT_SOURCES)
+                .format(Format::Alpha)
+                .offset(offset)
+                .style(style)
+                .render_into(scaler, glyph.glyph.id as u16, image);
+            image.placement
+        };
+
+        let stroked = outline_width > 0.0;
+        let style = if stroked {
+            // The stroke is centered on the contour, so half of it is inside the glyph.
+            Style::Stroke(Stroke::new(outline_width * 2.0))
+        } else {
+            Style::Fill(Fill::NonZero)
+        };
+        let placement = render(style, &mut self.tmp_image);
+        if placement.width == 0 || placement.height == 0 {
+            return None;
+        }
+        let mut mask = GrayImage::from_raw(placement.width, placement.height, self.tmp_image.data.clone())?;
+
+        // The stroke is hollow, so fill it in when the whole shape is needed.
+        if fill && stroked {
+            let mut fill_image = Image::new();
+            let fill_placement = render(Style::Fill(Fill::NonZero), &mut fill_image);
+            let dx = fill_placement.left - placement.left;
+            let dy = placement.top - fill_placement.top;
+            for y in 0..fill_placement.height {
+                for x in 0..fill_placement.width {
+                    let (tx, ty) = (x as i32 + dx, y as i32 + dy);
+                    if tx < 0 || ty < 0 || tx >= mask.width() as i32 || ty >= mask.height() as i32 {
+                        continue;
+                    }
+                    let value = fill_image.data[(y * fill_placement.width + x) as usize];
+                    let pixel = mask.get_pixel_mut(tx as u32, ty as u32);
+                    pixel.0[0] = pixel.0[0].max(value);
+                }
+            }
+        }
+
+        let (mut left, mut top) = (placement.left, placement.top);
+        if blur_radius > 0.0 {
+            // Leave room for the blur to spread out.
+            let pad = (blur_radius * 1.5).ceil() as u32;
+            let mut padded = GrayImage::new(mask.width() + pad * 2, mask.height() + pad * 2);
+            image::imageops::replace(&mut padded, &mask, pad as i64, pad as i64);
+            mask = image::imageops::blur(&padded, blur_radius / 2.0);
+            left -= pad as i32;
+            top += pad as i32;
+        }
+
+        return Some((mask, left, top));
+    }
+
     fn copy_glyph_to_atlas(&mut self, size: Size2D<i32, UnknownUnit>, alloc: &Allocation, page: usize, content_type: Content) {
         for y in 0..size.height as i32 {
             let src_start = (y as usize) * (size.width as usize);
@@ -761,6 +1528,37 @@ impl RenderData {
             return None;
         }
 
+        if let Some((alloc, page)) = self.allocate_in_atlas(size, content) {
+            return self.store_glyph(glyph, size, &alloc, page, &placement, content, box_index);
+        }
+
+        // Glyph is too large to fit even in a new empty page. It's time to give up.
+        // todo: should probably try to catch these earlier by checking for unreasonable font sizes
+        // todo2: technically, we could split the huge glyph across multiple pages, or render it on the surface directly.
+        self.glyph_cache.push(glyph.key(), None);
+        return None;
+    }
+
+    // Helper method to store glyph once allocation is successful
+    // todo: don't carry around `size`, alloc probably has the same data
+    fn store_glyph(&mut self,
+            glyph: &GlyphWithContext,
+            size: Size2D<i32, UnknownUnit>,
+            alloc: &Allocation,
+            page: usize,
+            placement: &Placement,
+            content_type: Content,
+            box_index: u32,
+        ) -> Option<(GlyphQuad, StoredGlyph)> {
+        self.copy_glyph_to_atlas(size, alloc, page, content_type);
+        let stored_glyph = StoredGlyph::create(alloc, placement, page, self.frame, content_type);
+        self.glyph_cache.push(glyph.key(), Some(stored_glyph));
+        let quad = make_glyph_quad(glyph, &stored_glyph, box_index);
+        Some((quad, stored_glyph))
+    }
+
+    /// Finds space for a rectangle in the atlas pages for the given content type, evicting old glyphs or adding a new page if needed.
+    fn allocate_in_atlas(&mut self, size: Size2D<i32, UnknownUnit>, content: Content) -> Option<(Allocation, usize)> {
         let n_pages = match content {
             Content::Mask => self.mask_atlas_pages.len(),
             Content::Color => self.color_atlas_pages.len(),
@@ -769,7 +1567,7 @@ impl RenderData {
         // Try to allocate on existing pages
         for page in 0..n_pages {
             if let Some(alloc) = self.pack_rectangle(size, content, page) {
-                return self.store_glyph(glyph, size, &alloc, page, &placement, content, box_index);
+                return Some((alloc, page));
             }
 
             // Try evicting glyphs from previous frames and retry
@@ -777,7 +1575,7 @@ impl RenderData {
                 self.evict_old_glyphs();
 
                 if let Some(alloc) = self.pack_rectangle(size, content, page) {
-                    return self.store_glyph(glyph, size, &alloc, page, &placement, content, box_index);
+                    return Some((alloc, page));
                 }
             }
         }
@@ -785,32 +1583,64 @@ impl RenderData {
         // Create a new page and try to allocate there
         let new_page: usize = self.make_new_page(content);
         if let Some(alloc) = self.pack_rectangle(size, content, new_page) {
-            return self.store_glyph(glyph, size, &alloc, new_page, &placement, content, box_index);
+            return Some((alloc, new_page));
         }
 
-        // Glyph is too large to fit even in a new empty page. It's time to give up.
-        // todo: should probably try to catch these earlier by checking for unreasonable font sizes
-        // todo2: technically, we could split the huge glyph across multiple pages, or render it on the surface directly.
-        self.glyph_cache.push(glyph.key(), None);
         return None;
     }
 
-    // Helper method to store glyph once allocation is successful
-    // todo: don't carry around `size`, alloc probably has the same data
-    fn store_glyph(&mut self,
-            glyph: &GlyphWithContext,
-            size: Size2D<i32, UnknownUnit>,
-            alloc: &Allocation,
-            page: usize,
-            placement: &Placement,
-            content_type: Content,
-            box_index: u32,
-        ) -> Option<(GlyphQuad, StoredGlyph)> {
-        self.copy_glyph_to_atlas(size, alloc, page, content_type);
-        let stored_glyph = StoredGlyph::create(alloc, placement, page, self.frame, content_type);
-        self.glyph_cache.push(glyph.key(), Some(stored_glyph));
-        let quad = make_glyph_quad(glyph, &stored_glyph, box_index);
-        Some((quad, stored_glyph))
+    /// Pushes the quad for an inline image, resampling the image into the color atlas if it's not cached at this size.
+    fn prepare_inline_image_into(
+        &mut self,
+        image_id: ImageId,
+        source: &RgbaImage,
+        inline_box: &PositionedInlineBox,
+        box_index: u32,
+        buffer: &mut Vec<GlyphQuad>,
+    ) {
+        let width = inline_box.width.round().max(1.0) as u32;
+        let height = inline_box.height.round().max(1.0) as u32;
+        let key = GlyphKey::Image { image_id: image_id.0, width, height };
+
+        let stored = match self.glyph_cache.get(&key) {
+            Some(stored) => *stored,
+            None => {
+                let resized = image::imageops::resize(source, width, height, image::imageops::FilterType::Triangle);
+                let size = size2(width as i32, height as i32);
+
+                let stored = self.allocate_in_atlas(size, Content::Color).map(|(alloc, page)| {
+                    let atlas_page = &mut self.color_atlas_pages[page];
+                    image::imageops::replace(&mut atlas_page.image, &resized, alloc.rectangle.min.x as i64, alloc.rectangle.min.y as i64);
+                    atlas_page.needs_upload = true;
+
+                    StoredGlyph {
+                        content_type: Content::Color,
+                        page: page as u16,
+                        frame: self.frame,
+                        alloc,
+                        placement_left: 0,
+                        placement_top: 0,
+                        size,
+                    }
+                });
+                self.glyph_cache.push(key, stored);
+                stored
+            }
+        };
+
+        if let Some(stored) = stored {
+            let (uv_x, uv_y) = (stored.alloc.rectangle.min.x, stored.alloc.rectangle.min.y);
+            buffer.push(GlyphQuad {
+                pos_packed: pack_i32_pair_as_u16(inline_box.x.round() as i32, inline_box.y.round() as i32),
+                dim_packed: pack_u16_pair(width, height),
+                uv_origin_packed: pack_u16_pair(uv_x as u32, uv_y as u32),
+                color: 0xff_ff_ff_ff,
+                flags_and_page: pack_flags_and_page(CONTENT_TYPE_COLOR, stored.page as u32),
+                box_index,
+                rotation_bits: 0,
+                pivot_packed: 0,
+            });
+        }
     }
 
     fn pack_rectangle(&mut self, size: Size2D<i32, UnknownUnit>, content_type: Content, page: usize) -> Option<Allocation> {
@@ -176,8 +176,8 @@ impl TextRenderer {
                 3 => Uint32,      // color
                 4 => Uint32,      // flags_and_page
                 5 => Uint32,      // box_index
-                6 => Uint32,    // _padding1
-                7 => Uint32,    // _padding2
+                6 => Uint32,      // rotation_bits
+                7 => Uint32,      // pivot_packed
             ],
         };
 
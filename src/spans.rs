@@ -0,0 +1,208 @@
+use std::ops::Range;
+
+use parley::{InlineBox, StyleProperty, TreeBuilder};
+use slotmap::{DefaultKey, SlotMap};
+
+use crate::*;
+
+/// The style applied to the text covered by a [`TextSpan`].
+#[derive(Debug, Clone)]
+pub enum SpanStyle {
+    /// A full style, registered with [`Text::add_style()`]. It replaces the base style of the box inside the span.
+    Style(StyleHandle),
+    /// A list of properties that override the base style of the box inside the span.
+    Properties(Vec<StyleProperty<'static, ColorBrush>>),
+}
+
+/// A range of text with its own style, inside a [`TextBox`] or a [`TextEdit`].
+///
+/// Spans can overlap. When they do, the span that was added last wins for the properties that it sets.
+#[derive(Debug, Clone)]
+pub struct TextSpan {
+    /// Byte range in the text of the box.
+    pub range: Range<usize>,
+    /// The style for the range.
+    pub style: SpanStyle,
+}
+
+/// Maps a byte range through an edit that replaced `replaced` with `inserted_len` new bytes.
+///
+/// Text inserted at the edges of the range stays outside of it, text inserted inside grows it. The result can be empty if the whole range was deleted.
+pub(crate) fn map_range_for_replace(range: &Range<usize>, replaced: &Range<usize>, inserted_len: usize) -> Range<usize> {
+    let shift = |i: usize| i - replaced.len() + inserted_len;
+
+    let start = if range.start < replaced.start {
+        range.start
+    } else if range.start >= replaced.end {
+        shift(range.start)
+    } else {
+        replaced.start + inserted_len
+    };
+
+    let end = if range.end <= replaced.start {
+        range.end
+    } else if range.end >= replaced.end {
+        shift(range.end)
+    } else {
+        replaced.start
+    };
+
+    return start..end.max(start);
+}
+
+/// Maps a single byte index through an edit that replaced `replaced` with `inserted_len` new bytes.
+///
+/// Indices inside the deleted part collapse to its start.
+pub(crate) fn map_index_for_replace(index: usize, replaced: &Range<usize>, inserted_len: usize) -> usize {
+    if index <= replaced.start {
+        index
+    } else if index >= replaced.end {
+        index - replaced.len() + inserted_len
+    } else {
+        replaced.start
+    }
+}
+
+/// Pushes `text` into the builder, splitting it at span boundaries and opening the style spans that cover each piece.
+///
+/// `inline_boxes` must be sorted by index, and their indices must be valid char boundaries in `text`.
+///
+/// `locale` is the locale of the box. It's pushed again over style spans without their own locale, because they replace the whole style.
+///
+/// The color override is pushed last, so that it wins over the spans.
+pub(crate) fn push_text_with_spans(
+    builder: &mut TreeBuilder<'_, ColorBrush>,
+    text: &str,
+    spans: &[TextSpan],
+    inline_boxes: &[InlineBox],
+    styles: &SlotMap<DefaultKey, StyleInner>,
+    locale: Option<&StyleProperty<'static, ColorBrush>>,
+    color_override: Option<ColorBrush>,
+) {
+    let is_valid = |span: &TextSpan| {
+        span.range.start < span.range.end
+            && span.range.end <= text.len()
+            && text.is_char_boundary(span.range.start)
+            && text.is_char_boundary(span.range.end)
+    };
+
+    let mut boundaries: Vec<usize> = Vec::with_capacity(spans.len() * 2 + inline_boxes.len() + 2);
+    boundaries.push(0);
+    boundaries.push(text.len());
+    for span in spans.iter().filter(|s| is_valid(s)) {
+        boundaries.push(span.range.start);
+        boundaries.push(span.range.end);
+    }
+    for inline_box in inline_boxes {
+        boundaries.push(inline_box.index);
+    }
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    let mut next_box = 0;
+    for (i, &start) in boundaries.iter().enumerate() {
+        while next_box < inline_boxes.len() && inline_boxes[next_box].index <= start {
+            builder.push_inline_box(inline_boxes[next_box].clone());
+            next_box += 1;
+        }
+
+        let Some(&end) = boundaries.get(i + 1) else {
+            break;
+        };
+
+        let mut pushed = 0;
+        for span in spans.iter().filter(|s| is_valid(s) && s.range.start <= start && end <= s.range.end) {
+            match &span.style {
+                SpanStyle::Style(handle) => {
+                    if let Some(style) = styles.get(handle.key) {
+                        builder.push_style_span(style.text_style.clone());
+                        pushed += 1;
+                        if let Some(locale) = locale.filter(|_| style.text_style.locale.is_none()) {
+                            builder.push_style_modification_span(std::slice::from_ref(locale));
+                            pushed += 1;
+                        }
+                    }
+                }
+                SpanStyle::Properties(properties) => {
+                    builder.push_style_modification_span(properties);
+                    pushed += 1;
+                }
+            }
+        }
+
+        if let Some(color_override) = color_override {
+            builder.push_style_modification_span(&[StyleProperty::Brush(color_override)]);
+            pushed += 1;
+        }
+
+        builder.push_text(&text[start..end]);
+
+        for _ in 0..pushed {
+            builder.pop_style_span();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_before_range_shifts_it() {
+        assert_eq!(map_range_for_replace(&(5..10), &(2..2), 3), 8..13);
+    }
+
+    #[test]
+    fn insert_at_range_edges_stays_outside() {
+        assert_eq!(map_range_for_replace(&(5..10), &(5..5), 3), 8..13);
+        assert_eq!(map_range_for_replace(&(5..10), &(10..10), 3), 5..10);
+    }
+
+    #[test]
+    fn insert_inside_range_grows_it() {
+        assert_eq!(map_range_for_replace(&(5..10), &(7..7), 3), 5..13);
+    }
+
+    #[test]
+    fn insert_after_range_keeps_it() {
+        assert_eq!(map_range_for_replace(&(5..10), &(12..12), 3), 5..10);
+    }
+
+    #[test]
+    fn delete_inside_range_shrinks_it() {
+        assert_eq!(map_range_for_replace(&(5..10), &(6..8), 0), 5..8);
+    }
+
+    #[test]
+    fn delete_overlapping_start_cuts_it() {
+        assert_eq!(map_range_for_replace(&(5..10), &(3..7), 0), 3..6);
+    }
+
+    #[test]
+    fn delete_overlapping_end_cuts_it() {
+        assert_eq!(map_range_for_replace(&(5..10), &(8..12), 0), 5..8);
+    }
+
+    #[test]
+    fn delete_whole_range_empties_it() {
+        let range = map_range_for_replace(&(5..10), &(4..11), 0);
+        assert!(range.is_empty());
+        assert_eq!(range.start, 4);
+    }
+
+    #[test]
+    fn replace_covering_range_puts_it_after_the_new_text() {
+        let range = map_range_for_replace(&(5..10), &(5..10), 2);
+        assert!(range.is_empty());
+        assert_eq!(range.start, 7);
+    }
+
+    #[test]
+    fn index_inside_deleted_text_collapses_to_its_start() {
+        assert_eq!(map_index_for_replace(3, &(5..8), 1), 3);
+        assert_eq!(map_index_for_replace(5, &(5..8), 1), 5);
+        assert_eq!(map_index_for_replace(6, &(5..8), 1), 5);
+        assert_eq!(map_index_for_replace(8, &(5..8), 1), 6);
+        assert_eq!(map_index_for_replace(12, &(5..8), 1), 10);
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use crate::*;
+
+/// Size of a cell of the grid, in pixels.
+const GRID_CELL_SIZE: f32 = 256.0;
+
+/// Boxes that cover more cells than this are kept in a separate list that is checked on every query, instead of being added to all of their cells.
+const MAX_CELLS_PER_BOX: i64 = 64;
+
+/// Bounds of a box on the screen: (min_x, min_y, max_x, max_y).
+pub(crate) type ScreenBounds = (f32, f32, f32, f32);
+
+/// Where a box is stored in the grid.
+#[derive(Clone, Copy, Debug)]
+enum GridSpan {
+    Cells { x0: i32, y0: i32, x1: i32, y1: i32 },
+    Large,
+}
+
+/// A uniform grid of the screen bounds of all the boxes, used to find the boxes under the mouse without checking all of them.
+///
+/// The bounds are a conservative axis-aligned box around the transformed hitbox, so the boxes returned by [`SpatialIndex::candidates_at()`] still have to be checked with the exact hit tests.
+#[derive(Default)]
+pub(crate) struct SpatialIndex {
+    cells: HashMap<(i32, i32), Vec<AnyBox>, BuildHasherDefault<FxHasher>>,
+    large: Vec<AnyBox>,
+    spans: HashMap<AnyBox, GridSpan, BuildHasherDefault<FxHasher>>,
+    candidates: Vec<AnyBox>,
+}
+
+fn cell_coord(value: f32) -> i32 {
+    (value / GRID_CELL_SIZE).floor() as i32
+}
+
+impl SpatialIndex {
+    /// Moves a box to new bounds, or removes it from the grid if `bounds` is `None`.
+    pub(crate) fn update(&mut self, id: AnyBox, bounds: Option<ScreenBounds>) {
+        self.remove(id);
+        let Some((min_x, min_y, max_x, max_y)) = bounds else {
+            return;
+        };
+
+        let finite = [min_x, min_y, max_x, max_y].iter().all(|v| v.is_finite());
+        let span = if finite {
+            let (x0, y0, x1, y1) = (cell_coord(min_x), cell_coord(min_y), cell_coord(max_x), cell_coord(max_y));
+            let cell_count = (x1 as i64 - x0 as i64 + 1) * (y1 as i64 - y0 as i64 + 1);
+            if cell_count <= MAX_CELLS_PER_BOX { GridSpan::Cells { x0, y0, x1, y1 } } else { GridSpan::Large }
+        } else {
+            GridSpan::Large
+        };
+
+        match span {
+            GridSpan::Cells { x0, y0, x1, y1 } => {
+                for y in y0..=y1 {
+                    for x in x0..=x1 {
+                        self.cells.entry((x, y)).or_default().push(id);
+                    }
+                }
+            }
+            GridSpan::Large => self.large.push(id),
+        }
+        self.spans.insert(id, span);
+    }
+
+    /// Puts a box in the list that is checked on every query, until its bounds are known again.
+    pub(crate) fn mark_stale(&mut self, id: AnyBox) {
+        self.remove(id);
+        self.large.push(id);
+        self.spans.insert(id, GridSpan::Large);
+    }
+
+    /// Removes a box from the grid.
+    pub(crate) fn remove(&mut self, id: AnyBox) {
+        let Some(span) = self.spans.remove(&id) else {
+            return;
+        };
+        match span {
+            GridSpan::Cells { x0, y0, x1, y1 } => {
+                for y in y0..=y1 {
+                    for x in x0..=x1 {
+                        let Some(cell) = self.cells.get_mut(&(x, y)) else {
+                            continue;
+                        };
+                        if let Some(i) = cell.iter().position(|&other| other == id) {
+                            cell.swap_remove(i);
+                        }
+                        if cell.is_empty() {
+                            self.cells.remove(&(x, y));
+                        }
+                    }
+                }
+            }
+            GridSpan::Large => {
+                if let Some(i) = self.large.iter().position(|&other| other == id) {
+                    self.large.swap_remove(i);
+                }
+            }
+        }
+    }
+
+    /// Returns the boxes whose bounds might contain `pos`.
+    ///
+    /// They're sorted in the order that the boxes are iterated in, text edits first, so that boxes at the same depth are picked in the same order as when all of them are checked.
+    pub(crate) fn candidates_at(&mut self, pos: (f64, f64)) -> &[AnyBox] {
+        self.candidates.clear();
+        let cell = (cell_coord(pos.0 as f32), cell_coord(pos.1 as f32));
+        if let Some(boxes) = self.cells.get(&cell) {
+            self.candidates.extend_from_slice(boxes);
+        }
+        self.candidates.extend_from_slice(&self.large);
+        self.candidates.sort_unstable();
+        &self.candidates
+    }
+}
+
+impl TextBox {
+    /// Marks the screen bounds of the box as changed, so that it's moved in the [`SpatialIndex`] before the next pointer query.
+    pub(crate) fn mark_hit_bounds_dirty(&mut self) {
+        self.hit_bounds_dirty = true;
+        self.shared_mut().hit_bounds_changed = true;
+    }
+
+    /// Returns the screen bounds of the area checked by [`TextBox::hit_full_rect()`].
+    pub(crate) fn full_rect_screen_bounds(&self) -> ScreenBounds {
+        let local = match self.explicit_hitbox {
+            Some(hitbox) => hitbox,
+            None => (-X_TOLERANCE as f32, 0.0, self.width + X_TOLERANCE as f32, self.height),
+        };
+        self.local_to_screen_bounds(local)
+    }
+
+    /// Returns the screen bounds of the area checked by [`Ext1::hit_bounding_box()`]. Uses the current layout.
+    pub(crate) fn bounding_box_screen_bounds(&self) -> ScreenBounds {
+        if let Some(hitbox) = self.explicit_hitbox {
+            return self.local_to_screen_bounds(hitbox);
+        }
+        let (content_x, content_y) = self.content_offset();
+        let local = match &self.flattened_path {
+            Some(path) => {
+                let bounds = path.bounds(self.layout.height());
+                (bounds.x0 as f32, bounds.y0 as f32, bounds.x1 as f32, bounds.y1 as f32)
+            }
+            None => (-X_TOLERANCE as f32, 0.0, self.layout.full_width() + X_TOLERANCE as f32, self.layout.height()),
+        };
+        self.local_to_screen_bounds((local.0 + content_x, local.1 + content_y, local.2 + content_x, local.3 + content_y))
+    }
+
+    /// Transforms a rect in the layout frame to the screen, and returns the axis-aligned box around it, with a pixel of margin for rounding errors.
+    fn local_to_screen_bounds(&self, (min_x, min_y, max_x, max_y): ScreenBounds) -> ScreenBounds {
+        let transform = self.frame_transform();
+        let mut bounds = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for (x, y) in [(min_x, min_y), (max_x, min_y), (min_x, max_y), (max_x, max_y)] {
+            let point = transform.transform_point(euclid::Point2D::new(x, y));
+            bounds.0 = bounds.0.min(point.x);
+            bounds.1 = bounds.1.min(point.y);
+            bounds.2 = bounds.2.max(point.x);
+            bounds.3 = bounds.3.max(point.y);
+        }
+        (bounds.0 - 1.0, bounds.1 - 1.0, bounds.2 + 1.0, bounds.3 + 1.0)
+    }
+}
+
+impl Text {
+    /// Moves the boxes whose position, transform, hitbox, visibility or layout changed since the last time to their new place in the [`SpatialIndex`].
+    ///
+    /// This runs at the end of [`Text::prepare_all()`], after the boxes are laid out, and before pointer queries. Queries never lay out text: the bounds of a text box come from its last layout, and a box whose layout is out of date is marked as stale, so it's checked on every query until it's laid out again.
+    pub(crate) fn update_spatial_index(&mut self) {
+        if !self.shared.hit_bounds_changed {
+            return;
+        }
+        for (key, text_edit) in self.text_edits.iter_mut() {
+            let text_box = &mut text_edit.text_box;
+            if !text_box.hit_bounds_dirty {
+                continue;
+            }
+            text_box.hit_bounds_dirty = false;
+            let bounds = (!text_box.hidden).then(|| text_box.full_rect_screen_bounds());
+            self.spatial_index.update(AnyBox::TextEdit(key), bounds);
+        }
+        for (key, text_box) in self.text_boxes.iter_mut() {
+            if !text_box.hit_bounds_dirty {
+                continue;
+            }
+            // The box stays dirty, and laying it out marks the bounds as changed again.
+            if !text_box.hidden && (text_box.needs_relayout || text_box.style_version_changed()) {
+                self.spatial_index.mark_stale(AnyBox::TextBox(key));
+                continue;
+            }
+            text_box.hit_bounds_dirty = false;
+            let bounds = (!text_box.hidden).then(|| text_box.bounding_box_screen_bounds());
+            self.spatial_index.update(AnyBox::TextBox(key), bounds);
+        }
+        self.shared.hit_bounds_changed = false;
+    }
+}
@@ -0,0 +1,390 @@
+use parley::{Affinity, Cursor, Layout, PositionedInlineBox, PositionedLayoutItem};
+
+use crate::*;
+
+/// Inline box ids with this bit set are used internally for tab stops. User ids for [`TextBox::add_inline_box()`] should leave it unset.
+pub const TAB_BOX_ID_FLAG: u64 = 1 << 62;
+
+/// How a tab stop aligns the text that follows the tab.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TabAlignment {
+    /// The text starts at the stop.
+    #[default]
+    Left,
+    /// The text ends at the stop. The text ends at the next tab or at the end of the line.
+    Right,
+    /// The first `.` in the text is placed at the stop. Text without a `.` is aligned like [`TabAlignment::Right`].
+    Decimal,
+}
+
+/// A position that tabs can move the text to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TabStop {
+    /// Distance from the left edge of the layout, in logical pixels.
+    pub position: f32,
+    /// How the text after the tab is aligned to the stop.
+    pub alignment: TabAlignment,
+}
+
+/// Where tab characters move the text to. See [`TextBox::set_tab_stops()`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TabStops {
+    /// Stops every this many space widths, measured with the style of the box.
+    Spaces(u32),
+    /// Stops every this many logical pixels.
+    Pixels(f32),
+    /// Explicit stops, sorted by position. Tabs after the last stop are as wide as a space.
+    Explicit(Vec<TabStop>),
+}
+
+/// Tab widths closer than this are considered the same, so the layout isn't rebuilt for them.
+const TAB_WIDTH_TOLERANCE: f32 = 0.5;
+
+/// Tab widths change the positions of the following tabs and can change the line breaks, so they can need a few passes to settle.
+const MAX_TAB_PASSES: usize = 3;
+
+impl TextBox {
+    /// Sets where tab characters move the text to. With `None`, tabs are laid out by the shaper like other whitespace.
+    ///
+    /// The space after each tab is an inline box, so the tab character itself is still in the text, and the selection and the cursor move over it normally.
+    pub fn set_tab_stops(&mut self, tab_stops: Option<TabStops>) {
+        if self.tab_stops == tab_stops {
+            return;
+        }
+        self.tab_stops = tab_stops;
+        self.tab_widths.clear();
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the tab stops of the box.
+    pub fn tab_stops(&self) -> Option<&TabStops> {
+        self.tab_stops.as_ref()
+    }
+
+    /// Returns the inline boxes that fill the space after each tab, with the widths from the last pass.
+    pub(crate) fn tab_inline_boxes(&self) -> impl Iterator<Item = parley::InlineBox> + '_ {
+        let enabled = self.tab_stops.is_some();
+        self.text.match_indices('\t')
+            .filter(move |_| enabled)
+            .enumerate()
+            .map(|(i, (index, _))| parley::InlineBox {
+                id: TAB_BOX_ID_FLAG | i as u64,
+                kind: parley::InlineBoxKind::InFlow,
+                index: index + 1,
+                width: self.tab_widths.get(i).copied().unwrap_or(0.0),
+                height: 0.0,
+            })
+    }
+
+    /// Adjusts the tab widths to the tab stops, rebuilding the layout until they settle. Returns the final layout.
+    ///
+    /// `truncation` is the one `layout` was built with. It has to be the same as `self.truncation`, which is used to map the positions of the tabs.
+    pub(crate) fn apply_tab_stops(&mut self, mut layout: Layout<ColorBrush>, truncation: Option<&Truncation>, color_override: Option<ColorBrush>, single_line: bool) -> Layout<ColorBrush> {
+        if self.tab_stops.is_none() || !self.text.contains('\t') {
+            return layout;
+        }
+
+        for _ in 0..MAX_TAB_PASSES {
+            let widths = self.tab_widths_for(&layout);
+            let settled = widths.len() == self.tab_widths.len()
+                && widths.iter().zip(&self.tab_widths).all(|(a, b)| (a - b).abs() < TAB_WIDTH_TOLERANCE);
+            if settled {
+                break;
+            }
+            self.tab_widths = widths;
+            layout = self.build_layout(truncation, color_override, single_line);
+        }
+        return layout;
+    }
+
+    /// Returns the selection with its ends moved after the space of a tab when they're between a tab and its space.
+    ///
+    /// The index right after a tab has two caret positions: the end of the tab glyph, before the space that reaches the stop, and the start of the text after the space. Keeping only the second one means that the caret stops once at that index and is drawn next to the text that will follow it.
+    pub(crate) fn snap_selection_to_tab_boxes(&self, selection: TextSelection) -> TextSelection {
+        if self.tab_stops.is_none() {
+            return selection;
+        }
+        let snap = |cursor: TextCursor| {
+            if cursor.affinity() == Affinity::Upstream && self.is_after_tab(self.layout_to_text_index(cursor.index())) {
+                TextCursor::from_byte_index(&self.layout, cursor.index(), Affinity::Downstream)
+            } else {
+                cursor
+            }
+        };
+        let (anchor, focus) = (snap(selection.anchor()), snap(selection.focus()));
+        if anchor == selection.anchor() && focus == selection.focus() {
+            return selection;
+        }
+        if selection.is_collapsed() {
+            return focus.into();
+        }
+        return TextSelection::new(anchor, focus);
+    }
+
+    /// Returns `true` if the byte `index` of the text is right after a tab that gets a space for the tab stops.
+    pub(crate) fn is_after_tab(&self, index: usize) -> bool {
+        self.tab_stops.is_some() && self.text[..index].ends_with('\t')
+    }
+
+    /// Computes the width of the space after each tab so that the text after it reaches the next stop, given the positions in `layout`.
+    fn tab_widths_for(&self, layout: &Layout<ColorBrush>) -> Vec<f32> {
+        let Some(tab_stops) = &self.tab_stops else {
+            return Vec::new();
+        };
+        let scale = layout.scale();
+        let space_width = self.build_plain_layout(" ", self.style.key).width();
+
+        let tab_indices: Vec<usize> = self.text.match_indices('\t').map(|(i, _)| i).collect();
+        let mut widths = vec![0.0; tab_indices.len()];
+
+        // Takes an index in the text, not in the layout.
+        let x_of = |index: usize| {
+            let index = self.text_to_layout_index(index);
+            Cursor::from_byte_index(layout, index, Affinity::Downstream).geometry(layout, 0.0).x0 as f32
+        };
+
+        // Returns the next stop after `x`.
+        let next_stop = |x: f32| -> (f32, TabAlignment) {
+            let interval = match tab_stops {
+                TabStops::Spaces(spaces) => *spaces as f32 * space_width,
+                TabStops::Pixels(pixels) => *pixels * scale,
+                TabStops::Explicit(stops) => {
+                    return stops.iter()
+                        .map(|stop| (stop.position * scale, stop.alignment))
+                        .find(|(position, _)| *position > x + TAB_WIDTH_TOLERANCE)
+                        .unwrap_or((x + space_width, TabAlignment::Left));
+                }
+            };
+            if interval <= 0.0 {
+                return (x, TabAlignment::Left);
+            }
+            (((x + TAB_WIDTH_TOLERANCE) / interval).floor() * interval + interval, TabAlignment::Left)
+        };
+
+        for line in layout.lines() {
+            let mut line_end = 0.0_f32;
+            let mut tab_boxes: Vec<PositionedInlineBox> = Vec::new();
+            for item in line.items() {
+                match item {
+                    PositionedLayoutItem::GlyphRun(glyph_run) => line_end = line_end.max(glyph_run.offset() + glyph_run.advance()),
+                    PositionedLayoutItem::InlineBox(inline_box) => {
+                        line_end = line_end.max(inline_box.x + inline_box.width);
+                        if inline_box.id & TAB_BOX_ID_FLAG != 0 {
+                            tab_boxes.push(inline_box);
+                        }
+                    }
+                }
+            }
+            tab_boxes.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+            // How much the tabs before the current one on this line grew in this pass.
+            let mut shift = 0.0;
+            for (k, tab_box) in tab_boxes.iter().enumerate() {
+                let tab_i = (tab_box.id & !TAB_BOX_ID_FLAG) as usize;
+                let Some(&tab_index) = tab_indices.get(tab_i) else {
+                    continue;
+                };
+
+                // The text aligned by this tab goes until the next tab on the line, or the end of the line.
+                let segment_start = tab_box.x + tab_box.width;
+                let segment_end_index = tab_boxes.get(k + 1)
+                    .and_then(|next| tab_indices.get((next.id & !TAB_BOX_ID_FLAG) as usize))
+                    .copied();
+                let segment_end = segment_end_index.map_or(line_end, |index| x_of(index));
+                let segment_end_index = segment_end_index.unwrap_or_else(|| self.layout_to_text_index(line.text_range().end));
+
+                let start = tab_box.x + shift;
+                let (stop, alignment) = next_stop(start);
+                let offset_in_segment = match alignment {
+                    TabAlignment::Left => 0.0,
+                    TabAlignment::Right => segment_end - segment_start,
+                    TabAlignment::Decimal => {
+                        let segment_text = self.text.get(tab_index + 1..segment_end_index).unwrap_or("");
+                        match segment_text.find('.') {
+                            Some(dot) => x_of(tab_index + 1 + dot) - segment_start,
+                            None => segment_end - segment_start,
+                        }
+                    }
+                };
+
+                let width = (stop - offset_in_segment - start).max(0.0);
+                shift += width - tab_box.width;
+                widths[tab_i] = width;
+            }
+        }
+
+        return widths;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::NonNull;
+
+    use super::*;
+
+    const TEXT: &str = "a\tbb\tcc\t1.5";
+
+    fn all_modes() -> Vec<TabStops> {
+        vec![
+            TabStops::Spaces(4),
+            TabStops::Pixels(40.0),
+            TabStops::Explicit(vec![
+                TabStop { position: 60.0, alignment: TabAlignment::Left },
+                TabStop { position: 150.0, alignment: TabAlignment::Right },
+                TabStop { position: 250.0, alignment: TabAlignment::Decimal },
+            ]),
+        ]
+    }
+
+    fn text_box(shared: &mut Shared, text: &str, tab_stops: TabStops) -> TextBox {
+        let default_style_key = shared.default_style_key;
+        let mut text_box = TextBox::new(text.to_string(), (0.0, 0.0), (1000.0, 1000.0), 0.0, default_style_key, NonNull::from(shared));
+        text_box.set_tab_stops(Some(tab_stops));
+        text_box.refresh_layout();
+        return text_box;
+    }
+
+    /// The text index and the x of the selection focus.
+    fn caret(text_box: &TextBox) -> (usize, f64) {
+        let focus = text_box.selection.focus();
+        (text_box.layout_to_text_index(focus.index()), focus.geometry(&text_box.layout, 0.0).x0)
+    }
+
+    /// The right edges of the tab spaces, in the order of the tabs.
+    fn tab_space_ends(text_box: &TextBox) -> Vec<f64> {
+        let mut boxes: Vec<PositionedInlineBox> = text_box.layout.lines()
+            .flat_map(|line| line.items().collect::<Vec<_>>())
+            .filter_map(|item| match item {
+                PositionedLayoutItem::InlineBox(inline_box) if inline_box.id & TAB_BOX_ID_FLAG != 0 => Some(inline_box),
+                _ => None,
+            })
+            .collect();
+        boxes.sort_by_key(|b| b.id);
+        boxes.iter().map(|b| (b.x + b.width) as f64).collect()
+    }
+
+    /// Moves the caret with `step` until it stops moving, and returns all the positions it went through.
+    fn walk(text_box: &mut TextBox, step: impl Fn(&mut TextBox)) -> Vec<(usize, f64)> {
+        let mut stops = vec![caret(text_box)];
+        loop {
+            step(text_box);
+            let stop = caret(text_box);
+            if stop == *stops.last().unwrap() {
+                return stops;
+            }
+            stops.push(stop);
+        }
+    }
+
+    #[test]
+    fn right_arrow_stops_once_at_each_index() {
+        for tab_stops in all_modes() {
+            let mut shared = Shared::new();
+            let mut text_box = text_box(&mut shared, TEXT, tab_stops.clone());
+            text_box.move_to_text_start();
+            let stops = walk(&mut text_box, |text_box| text_box.move_right());
+
+            let indices: Vec<usize> = stops.iter().map(|stop| stop.0).collect();
+            assert_eq!(indices, (0..=TEXT.len()).collect::<Vec<_>>(), "{tab_stops:?}");
+            assert!(stops.windows(2).all(|w| w[1].1 > w[0].1), "{tab_stops:?}: {stops:?}");
+
+            // After a tab, the caret is after its space.
+            let ends = tab_space_ends(&text_box);
+            for ((index, _), end) in TEXT.match_indices('\t').zip(ends) {
+                assert!((stops[index + 1].1 - end).abs() < 0.5, "{tab_stops:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn left_arrow_stops_once_at_each_index() {
+        for tab_stops in all_modes() {
+            let mut shared = Shared::new();
+            let mut text_box = text_box(&mut shared, TEXT, tab_stops.clone());
+            text_box.move_to_text_end();
+            let stops = walk(&mut text_box, |text_box| text_box.move_left());
+
+            let indices: Vec<usize> = stops.iter().map(|stop| stop.0).collect();
+            assert_eq!(indices, (0..=TEXT.len()).rev().collect::<Vec<_>>(), "{tab_stops:?}");
+            assert!(stops.windows(2).all(|w| w[1].1 < w[0].1), "{tab_stops:?}: {stops:?}");
+        }
+    }
+
+    #[test]
+    fn word_movement_crosses_tabs_without_stopping_twice() {
+        for tab_stops in all_modes() {
+            let mut shared = Shared::new();
+            let mut text_box = text_box(&mut shared, TEXT, tab_stops.clone());
+            text_box.move_to_text_start();
+            let right = walk(&mut text_box, |text_box| text_box.move_word_right());
+            assert!(right.windows(2).all(|w| w[1].0 > w[0].0 && w[1].1 > w[0].1), "{tab_stops:?}: {right:?}");
+            assert_eq!(right.last().unwrap().0, TEXT.len());
+
+            let left = walk(&mut text_box, |text_box| text_box.move_word_left());
+            assert!(left.windows(2).all(|w| w[1].0 < w[0].0 && w[1].1 < w[0].1), "{tab_stops:?}: {left:?}");
+            assert_eq!(left.last().unwrap().0, 0);
+        }
+    }
+
+    #[test]
+    fn shift_selection_grows_one_char_at_a_time_over_tabs() {
+        for tab_stops in all_modes() {
+            let mut shared = Shared::new();
+            let mut text_box = text_box(&mut shared, TEXT, tab_stops.clone());
+            text_box.move_to_text_start();
+            for end in 1..=TEXT.len() {
+                text_box.set_selection(text_box.step_horizontally(text_box.selection, true, true));
+                assert_eq!(text_box.selection_text_range(), 0..end, "{tab_stops:?}");
+            }
+            for end in (0..TEXT.len()).rev() {
+                text_box.set_selection(text_box.step_horizontally(text_box.selection, false, true));
+                assert_eq!(text_box.selection_text_range(), 0..end, "{tab_stops:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn clicking_on_a_tab_space_puts_the_caret_after_it() {
+        for tab_stops in all_modes() {
+            let mut shared = Shared::new();
+            let mut text_box = text_box(&mut shared, TEXT, tab_stops.clone());
+            let y = text_box.layout.get(0).unwrap().metrics().baseline - 1.0;
+
+            let ends = tab_space_ends(&text_box);
+            for ((index, _), end) in TEXT.match_indices('\t').zip(ends) {
+                // Even when asked for the upstream side, the cursor after a tab goes after its space.
+                let after = text_box.cursor_at(index + 1, Affinity::Upstream).geometry(&text_box.layout, 0.0).x0;
+                assert!((after - end).abs() < 0.5, "{tab_stops:?}");
+                let start_of_space = TextCursor::from_byte_index(&text_box.layout, text_box.text_to_layout_index(index + 1), Affinity::Upstream).geometry(&text_box.layout, 0.0).x0;
+
+                for x in [start_of_space + 1.0, (start_of_space + end) / 2.0, end - 1.0] {
+                    text_box.move_to_point(x as f32, y);
+                    let (caret_index, caret_x) = caret(&text_box);
+                    assert_eq!(caret_index, index + 1, "{tab_stops:?}");
+                    assert!((caret_x - end).abs() < 0.5, "{tab_stops:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_text_gets_its_own_tab_widths() {
+        let mut shared = Shared::new();
+        let text = "aaaa bbbb cccc dddd eeee ffff gggg hhhh iiii jjjj\tend";
+        let default_style_key = shared.default_style_key;
+        let mut text_box = TextBox::new(text.to_string(), (0.0, 0.0), (200.0, 1000.0), 0.0, default_style_key, NonNull::from(&mut shared));
+        text_box.set_tab_stops(Some(TabStops::Pixels(50.0)));
+        text_box.set_overflow(Overflow::EllipsisStart);
+        text_box.set_max_lines(Some(1));
+        text_box.refresh_layout();
+        assert!(text_box.truncation.is_some());
+
+        let end = text.find("end").unwrap();
+        let x = text_box.cursor_at(end, Affinity::Downstream).geometry(&text_box.layout, 0.0).x0;
+        let distance_to_stop = x % 50.0;
+        assert!(distance_to_stop.min(50.0 - distance_to_stop) < 0.5, "{x}");
+    }
+}
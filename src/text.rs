@@ -38,6 +38,8 @@ pub(crate) struct WindowInfo {
 pub(crate) struct StyleInner {
     pub(crate) text_style: TextStyle2,
     pub(crate) text_edit_style: TextEditStyle,
+    pub(crate) decoration_style: DecorationStyle,
+    pub(crate) effect_style: TextEffectStyle,
     pub(crate) version: u64,
 }
 
@@ -46,6 +48,7 @@ pub(crate) struct StyleInner {
 pub struct Text {
     pub(crate) text_boxes: SlotMap<DefaultKey, TextBox>,
     pub(crate) text_edits: SlotMap<DefaultKey, TextEdit>,
+    pub(crate) text_threads: SlotMap<DefaultKey, TextThread>,
 
     // Box to have a stable address for the backref pointers
     pub(crate) shared: Box<Shared>,
@@ -55,6 +58,7 @@ pub struct Text {
     pub(crate) input_state: TextInputState,
 
     pub(crate) mouse_hit_stack: Vec<(AnyBox, f32)>,
+    pub(crate) spatial_index: SpatialIndex,
 
     pub(crate) using_frame_based_visibility: bool,
 
@@ -80,6 +84,8 @@ pub(crate) struct Shared {
     pub default_style_key: DefaultKey,
     pub rebuild_glyph_quad_buffer: bool,
     pub scrolled: bool,
+    /// Set when the screen bounds of any box changed, so that the [`SpatialIndex`] has to be updated.
+    pub hit_bounds_changed: bool,
     pub focused: Option<AnyBox>,
 
     /// Text boxes that are part of the current multi-box selection.
@@ -109,9 +115,57 @@ pub(crate) struct Shared {
     pub cursor_blink_waker: Option<CursorBlinkWaker>,
 
     pub window: Option<Weak<Window>>,
+
+    /// Images registered with [`Text::add_image()`], indexed by [`ImageId`]. Removed images leave a `None`.
+    pub images: Vec<Option<RgbaImage>>,
+
+    pub markdown_theme: MarkdownTheme,
 }
 
 impl Shared {
+    pub(crate) fn new() -> Self {
+        let mut styles = SlotMap::with_capacity_and_key(10);
+        let default_style_key = styles.insert(StyleInner {
+            text_style: original_default_style(),
+            text_edit_style: TextEditStyle::default(),
+            decoration_style: DecorationStyle::default(),
+            effect_style: TextEffectStyle::default(),
+            version: 0,
+        });
+
+        Self {
+            windows: Vec::with_capacity(1),
+            styles,
+            default_style_key,
+            rebuild_glyph_quad_buffer: true,
+            scrolled: true,
+            hit_bounds_changed: true,
+            focused: None,
+            multi_box_selection: Vec::new(),
+            layout_cx: LayoutContext::new(),
+            font_cx: FontContext::new(),
+            rerender_cursor: false,
+            pasted_this_frame: false,
+            #[cfg(feature = "accessibility")]
+            accesskit_focus_tracker: FocusChange::new(),
+            current_event_number: 1,
+            #[cfg(feature = "accessibility")]
+            node_id_generator: crate::accessibility::next_node_id,
+            #[cfg(feature = "accessibility")]
+            accesskit_tree_update: TreeUpdate {
+                nodes: Vec::new(),
+                tree: None,
+                focus: NodeId(0),
+            },
+            cursor_blink_start: None,
+            cursor_blink_animation_currently_visible: false,
+            cursor_blink_waker: None,
+            window: None,
+            images: Vec::new(),
+            markdown_theme: MarkdownTheme::default(),
+        }
+    }
+
     pub(crate) fn update_blink_timer(&mut self) {
         if let Some(start_time) = self.cursor_blink_start {
             let elapsed = Instant::now().duration_since(start_time);
@@ -288,7 +342,7 @@ impl MouseState {
 /// A non-owning reference to either a `TextBox` or a `TextEditBox`.
 /// 
 ///[`TextBoxHandle`] and [`TextEditHandle`] can be converted into `AnyBox`.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum AnyBox {
     /// Text edit box
     TextEdit(DefaultKey),
@@ -357,13 +411,6 @@ impl Text {
         depth_stencil: Option<DepthStencilState>,
         params: TextRendererParams,
     ) -> Self {
-        let mut styles = SlotMap::with_capacity_and_key(10);
-        let default_style_key = styles.insert(StyleInner {
-            text_style: original_default_style(),
-            text_edit_style: TextEditStyle::default(),
-            version: 0,
-        });
-
         let renderer = TextRenderer::new_with_params(device.clone(), queue.clone(), format, depth_stencil, params);
         let mut render_data = RenderData::new();
         render_data.set_srgb(format.is_srgb());
@@ -371,9 +418,11 @@ impl Text {
         Self {
             text_boxes: SlotMap::with_capacity(10),
             text_edits: SlotMap::with_capacity(10),
+            text_threads: SlotMap::new(),
             style_version_id_counter: 0,
             input_state: TextInputState::new(),
             mouse_hit_stack: Vec::with_capacity(6),
+            spatial_index: SpatialIndex::default(),
             scrolled_moved_indices: Vec::new(),
             scroll_animations: Vec::new(),
             current_visibility_frame: 1,
@@ -386,34 +435,7 @@ impl Text {
 
             selected_text_buffer: String::with_capacity(25),
 
-            shared: Box::new(Shared {
-                windows: Vec::with_capacity(1),
-                styles,
-                default_style_key,
-                rebuild_glyph_quad_buffer: true,
-                scrolled: true,
-                focused: None,
-                multi_box_selection: Vec::new(),
-                layout_cx: LayoutContext::new(),
-                font_cx: FontContext::new(),
-                rerender_cursor: false,
-                pasted_this_frame: false,
-                #[cfg(feature = "accessibility")]
-                accesskit_focus_tracker: FocusChange::new(),
-                current_event_number: 1,
-                #[cfg(feature = "accessibility")]
-                node_id_generator: crate::accessibility::next_node_id,
-                #[cfg(feature = "accessibility")]
-                accesskit_tree_update: TreeUpdate {
-                    nodes: Vec::new(),
-                    tree: None,
-                    focus: NodeId(0),
-                },
-                cursor_blink_start: None,
-                cursor_blink_animation_currently_visible: false,
-                cursor_blink_waker: None,
-                window: None,
-            }),
+            shared: Box::new(Shared::new()),
         }
     }
 
@@ -477,16 +499,18 @@ impl Text {
             self.shared.rerender_cursor = false;
         } else if self.shared.rerender_cursor {
             // Cursor-blink-only sync: just update the cursor quad's color
-            if let Some(cursor_index) = self.render_data.cursor_quad_index {
+            if let Some((cursor_start, cursor_end)) = self.render_data.cursor_quad_range {
                 let color = if self.shared.cursor_blink_animation_currently_visible {
                     CURSOR_COLOR
                 } else {
                     0x00_00_00_00
                 };
-                self.render_data.glyph_quads[cursor_index].color = color;
+                for quad in &mut self.render_data.glyph_quads[cursor_start..cursor_end] {
+                    quad.color = color;
+                }
 
-                let bytes: &[u8] = bytemuck::cast_slice(&self.render_data.glyph_quads[cursor_index..cursor_index + 1]);
-                let offset = (cursor_index * std::mem::size_of::<GlyphQuad>()) as u64;
+                let bytes: &[u8] = bytemuck::cast_slice(&self.render_data.glyph_quads[cursor_start..cursor_end]);
+                let offset = (cursor_start * std::mem::size_of::<GlyphQuad>()) as u64;
                 self.renderer.queue.write_buffer(&self.renderer.vertex_buffer, offset, bytes);
             }
 
@@ -562,6 +586,7 @@ impl Text {
         text_box.style_version = self.shared.styles[text_box.style.key].version;
         let key = self.text_boxes.insert(text_box);
         self.shared.rebuild_glyph_quad_buffer = true;
+        self.shared.hit_bounds_changed = true;
         let handle = TextBoxHandle { key };
         // Fill in the local copy of the key.
         self.get_text_box_mut(&handle).key = key;
@@ -585,6 +610,7 @@ impl Text {
         text_edit.text_box.style_version = self.shared.styles[text_edit.text_box.style.key].version;
         let key = self.text_edits.insert(text_edit);
         self.shared.rebuild_glyph_quad_buffer = true;
+        self.shared.hit_bounds_changed = true;
         let handle = TextEditHandle { key };
         // Fill in the local copy of the key.
         self.get_text_edit_mut(&handle).text_box.key = key;
@@ -604,6 +630,7 @@ impl Text {
         text_box.window_id = Some(window_id);
         let key = self.text_boxes.insert(text_box);
         self.shared.rebuild_glyph_quad_buffer = true;
+        self.shared.hit_bounds_changed = true;
         let handle = TextBoxHandle { key };
         // Fill in the local copy of the key.
         self.get_text_box_mut(&handle).key = key;
@@ -624,6 +651,7 @@ impl Text {
         text_edit.text_box.window_id = Some(window_id);
         let key = self.text_edits.insert(text_edit);
         self.shared.rebuild_glyph_quad_buffer = true;
+        self.shared.hit_bounds_changed = true;
         let handle = TextEditHandle { key };
         // Fill in the local copy of the key.
         self.get_text_edit_mut(&handle).text_box.key = key;
@@ -669,6 +697,8 @@ impl Text {
         let key = self.shared.styles.insert(StyleInner {
             text_style,
             text_edit_style,
+            decoration_style: DecorationStyle::default(),
+            effect_style: TextEffectStyle::default(),
             version: new_version,
         });
         StyleHandle { key }
@@ -698,6 +728,42 @@ impl Text {
         &mut self.shared.styles[handle.key].text_edit_style
     }
 
+    /// Returns a reference to the decoration style.
+    pub fn get_decoration_style(&self, handle: &StyleHandle) -> &DecorationStyle {
+        &self.shared.styles[handle.key].decoration_style
+    }
+
+    /// Returns a mutable reference to the decoration style.
+    pub fn get_decoration_style_mut(&mut self, handle: &StyleHandle) -> &mut DecorationStyle {
+        self.shared.styles[handle.key].version = self.new_style_version();
+        self.shared.rebuild_glyph_quad_buffer = true;
+        &mut self.shared.styles[handle.key].decoration_style
+    }
+
+    /// Returns a reference to the effect style.
+    pub fn get_effect_style(&self, handle: &StyleHandle) -> &TextEffectStyle {
+        &self.shared.styles[handle.key].effect_style
+    }
+
+    /// Returns a mutable reference to the effect style.
+    pub fn get_effect_style_mut(&mut self, handle: &StyleHandle) -> &mut TextEffectStyle {
+        self.shared.styles[handle.key].version = self.new_style_version();
+        self.shared.rebuild_glyph_quad_buffer = true;
+        &mut self.shared.styles[handle.key].effect_style
+    }
+
+    /// Sets the styles used by [`TextBox::set_markdown()`].
+    ///
+    /// Boxes that already have Markdown text keep the styles they were set with, until their text is set again.
+    pub fn set_markdown_theme(&mut self, theme: MarkdownTheme) {
+        self.shared.markdown_theme = theme;
+    }
+
+    /// Returns the styles used by [`TextBox::set_markdown()`].
+    pub fn markdown_theme(&self) -> &MarkdownTheme {
+        &self.shared.markdown_theme
+    }
+
     /// Returns a reference to the default text style.
     pub fn get_default_text_style(&self) -> &TextStyle2 {
         &self.shared.styles[self.shared.default_style_key].text_style
@@ -777,9 +843,16 @@ impl Text {
         }
         
         let text_box = self.text_boxes.remove(handle.key).unwrap();
+        self.spatial_index.remove(AnyBox::TextBox(handle.key));
+        if let Some(thread_key) = text_box.thread {
+            self.remove_box_from_thread(handle.key, thread_key);
+        }
         
         let box_data_i = text_box.render_data_info.box_index;
         self.render_data.box_data.remove(box_data_i);
+        if let Some(background_box_index) = text_box.render_data_info.background_box_index {
+            self.render_data.box_data.remove(background_box_index);
+        }
 
         std::mem::forget(handle);
     }
@@ -805,9 +878,13 @@ impl Text {
         }
         
         let text_edit = self.text_edits.remove(handle.key).unwrap();
+        self.spatial_index.remove(AnyBox::TextEdit(handle.key));
 
         let box_data_i = text_edit.text_box.render_data_info.box_index;
         self.render_data.box_data.remove(box_data_i);
+        if let Some(background_box_index) = text_edit.text_box.render_data_info.background_box_index {
+            self.render_data.box_data.remove(background_box_index);
+        }
 
         std::mem::forget(handle);
     }
@@ -820,6 +897,27 @@ impl Text {
     }
 
 
+    /// Registers an image that can be placed inside text with [`TextBox::add_inline_image()`].
+    /// 
+    /// `rgba` holds the pixels row by row, 4 bytes per pixel. Panics if its length isn't `width * height * 4`.
+    pub fn add_image(&mut self, width: u32, height: u32, rgba: Vec<u8>) -> ImageId {
+        let image = RgbaImage::from_raw(width, height, rgba).expect("rgba should hold width * height * 4 bytes");
+        self.shared.images.push(Some(image));
+        return ImageId((self.shared.images.len() - 1) as u32);
+    }
+
+    /// Removes a registered image.
+    /// 
+    /// Text boxes that still use it will keep an empty space where the image was.
+    pub fn remove_image(&mut self, id: ImageId) {
+        if let Some(image) = self.shared.images.get_mut(id.0 as usize) {
+            *image = None;
+            // Cached quads might still point to the image in the atlas.
+            self.render_data.glyph_cache_generation += 1;
+            self.shared.rebuild_glyph_quad_buffer = true;
+        }
+    }
+
     /// Layout and rasterize all text belonging to a window, prepare the render data.
     pub fn prepare_all_for_window(&mut self, window: &Window) {
         let window_id = window.id();
@@ -859,6 +957,7 @@ impl Text {
 
         self.shared.pasted_this_frame = false;
         self.render_data.update_resolution(window_size.0, window_size.1);
+        self.flow_text_threads();
 
         // todo: not sure if this works correctly with multi-window.
         if !self.shared.rebuild_glyph_quad_buffer && self.using_frame_based_visibility {
@@ -910,6 +1009,9 @@ impl Text {
             }
         }
 
+        // The boxes were laid out above, so their bounds are ready for the pointer queries.
+        self.update_spatial_index();
+
         // Multi-window: mark prepared and check if all windows done.
         let should_clear_flags = {
             if let Some(window_info) = self.shared.windows.iter_mut().find(|info| info.window_id == window_id) {
@@ -1094,23 +1196,30 @@ impl Text {
 
     fn find_topmost_selectable_at_pos_for_window(&mut self, cursor_pos: (f64, f64), window_id: WindowId) -> Option<AnyBox> {
         self.mouse_hit_stack.clear();
+        self.update_spatial_index();
 
         // Find all text widgets at this position that belong to this window
-        for (i, ed) in self.text_edits.iter_mut() {
-            if ! ed.text_box.selectable { continue };
-            if !ed.text_box.hidden && ed.text_box.last_frame_touched == self.current_visibility_frame && ed.text_box.hit_full_rect(cursor_pos) {
-                // Only consider if this text edit belongs to this window (or has no window restriction)
-                if ed.text_box.window_id.is_none() || ed.text_box.window_id == Some(window_id) {
-                    self.mouse_hit_stack.push((AnyBox::TextEdit(i), ed.text_box.depth));
+        for &id in self.spatial_index.candidates_at(cursor_pos) {
+            match id {
+                AnyBox::TextEdit(i) => {
+                    let Some(ed) = self.text_edits.get_mut(i) else { continue };
+                    if ! ed.text_box.selectable { continue };
+                    if !ed.text_box.hidden && ed.text_box.last_frame_touched == self.current_visibility_frame && ed.text_box.hit_full_rect(cursor_pos) {
+                        // Only consider if this text edit belongs to this window (or has no window restriction)
+                        if ed.text_box.window_id.is_none() || ed.text_box.window_id == Some(window_id) {
+                            self.mouse_hit_stack.push((id, ed.text_box.depth));
+                        }
+                    }
                 }
-            }
-        }
-        for (i, text_box) in self.text_boxes.iter_mut() {
-            if ! text_box.selectable { continue };
-            if !text_box.hidden && text_box.last_frame_touched == self.current_visibility_frame && text_box.hit_bounding_box(cursor_pos) {
-                // Only consider if this text box belongs to this window (or has no window restriction)
-                if text_box.window_id.is_none() || text_box.window_id == Some(window_id) {
-                    self.mouse_hit_stack.push((AnyBox::TextBox(i), text_box.depth));
+                AnyBox::TextBox(i) => {
+                    let Some(text_box) = self.text_boxes.get_mut(i) else { continue };
+                    if ! text_box.selectable { continue };
+                    if !text_box.hidden && text_box.last_frame_touched == self.current_visibility_frame && text_box.hit_bounding_box(cursor_pos) {
+                        // Only consider if this text box belongs to this window (or has no window restriction)
+                        if text_box.window_id.is_none() || text_box.window_id == Some(window_id) {
+                            self.mouse_hit_stack.push((id, text_box.depth));
+                        }
+                    }
                 }
             }
         }
@@ -1209,16 +1318,23 @@ impl Text {
 
     fn find_topmost_at_pos(&mut self, cursor_pos: (f64, f64)) -> Option<AnyBox> {
         self.mouse_hit_stack.clear();
+        self.update_spatial_index();
 
         // Find all text widgets at this position
-        for (i, te) in self.text_edits.iter_mut() {
-            if !te.text_box.hidden && te.text_box.last_frame_touched == self.current_visibility_frame && te.text_box.hit_full_rect(cursor_pos) {
-                self.mouse_hit_stack.push((AnyBox::TextEdit(i), te.text_box.depth));
-            }
-        }
-        for (i, text_box) in self.text_boxes.iter_mut() {
-            if !text_box.hidden && text_box.last_frame_touched == self.current_visibility_frame && text_box.hit_bounding_box(cursor_pos) {
-                self.mouse_hit_stack.push((AnyBox::TextBox(i), text_box.depth));
+        for &id in self.spatial_index.candidates_at(cursor_pos) {
+            match id {
+                AnyBox::TextEdit(i) => {
+                    let Some(te) = self.text_edits.get_mut(i) else { continue };
+                    if !te.text_box.hidden && te.text_box.last_frame_touched == self.current_visibility_frame && te.text_box.hit_full_rect(cursor_pos) {
+                        self.mouse_hit_stack.push((id, te.text_box.depth));
+                    }
+                }
+                AnyBox::TextBox(i) => {
+                    let Some(text_box) = self.text_boxes.get_mut(i) else { continue };
+                    if !text_box.hidden && text_box.last_frame_touched == self.current_visibility_frame && text_box.hit_bounding_box(cursor_pos) {
+                        self.mouse_hit_stack.push((id, text_box.depth));
+                    }
+                }
             }
         }
 
@@ -1365,7 +1481,7 @@ impl Text {
                                 }
                                 AnyBox::TextEdit(i) => {
                                     if let Some(te) = self.text_edits.get(i) {
-                                        if let Some(text) = te.text_box.selected_text() {
+                                        if let Some(text) = te.text_box.selected_text_for_copy() {
                                             with_clipboard(|cb| { cb.set_text(text).ok(); });
                                         }
                                     }
@@ -1419,7 +1535,7 @@ impl Text {
         // Reset all extended selections first, they'll be recreated as needed
         for &key in &self.shared.multi_box_selection {
             if key != focused_key {
-                self.text_boxes[key].selection = parley::Selection::default();
+                self.text_boxes[key].selection = TextSelection::default();
             }
         }
         self.shared.multi_box_selection.retain(|&key| key == focused_key);
@@ -1499,18 +1615,13 @@ impl Text {
                 }
 
                 let linked_box = &mut self.text_boxes[linked_key];
-                let inv_transform = linked_box.transform().inverse().unwrap_or(Transform2D::identity());
-                let local_pos = inv_transform.transform_point(euclid::Point2D::new(cursor_pos.0 as f32, cursor_pos.1 as f32));
-                let local_cursor = (
-                    local_pos.x + linked_box.scroll_offset.0,
-                    local_pos.y + linked_box.scroll_offset.1,
-                );
+                let local_cursor = linked_box.screen_to_layout((cursor_pos.0 as f32, cursor_pos.1 as f32));
 
                 match copied_selection_anchor_base {
-                    parley::AnchorBase::Word(_, _) => {
+                    TextAnchorBase::Word(_, _) => {
                         linked_box.selection.select_word_at_point(&linked_box.layout, anchor_point.0, anchor_point.1);
                     }
-                    parley::AnchorBase::Line(_, _) => {
+                    TextAnchorBase::Line(_, _) => {
                         linked_box.selection.select_line_at_point(&linked_box.layout, anchor_point.0, anchor_point.1);
                     }
                     _ => {
@@ -1610,6 +1721,8 @@ impl Text {
     /// When selecting past the end of `first`, the selection will continue into `second`.
     /// When selecting before the start of `second`, the selection will continue into `first`.
     /// This only affects non-editable text boxes (TextBox, not TextEdit).
+    ///
+    /// To make the text itself flow from one box to the next, use [`Text::add_text_thread()`].
     pub fn link_text_boxes(&mut self, first: &TextBoxHandle, second: &TextBoxHandle) {
         self.text_boxes[first.key].next_box = Some(second.key);
         self.text_boxes[second.key].prev_box = Some(first.key);
@@ -1624,27 +1737,26 @@ impl Text {
 
     /// Convenience function that returns the selected text from all text boxes in the current cross-box selection as a single contiguous string, inserting a space between each segment, or `None` if nothing is selected.
     ///
-    /// If only one box is selected, a reference to the selected text is returned directly without any copying.
-    /// Otherwise, the text is copied into an internal buffer.
+    /// Segments from consecutive boxes of the same [text thread](`Text::add_text_thread()`) are joined without a space, since they're parts of the same text.
+    ///
+    /// If only one box is selected and it has no selected inline images, a reference to the selected text is returned directly without any copying.
+    /// Otherwise, the text is copied into an internal buffer, with the alt text of the inline images in place of the images.
     /// 
     /// Use [`Text::selected_text_iter()`] to get a zero-cost iterator over the different segments.
     pub fn selected_text(&mut self) -> Option<&str> {
         if self.shared.multi_box_selection.len() == 1 {
             let key = self.shared.multi_box_selection[0];
-            return self.text_boxes.get(key).and_then(|tb| tb.selected_text());
+            return match self.text_boxes.get(key)?.selected_text_for_copy()? {
+                Cow::Borrowed(text) => Some(text),
+                Cow::Owned(text) => {
+                    self.selected_text_buffer = text;
+                    Some(&self.selected_text_buffer)
+                }
+            };
         }
 
         self.selected_text_buffer.clear();
-        for &key in &self.shared.multi_box_selection {
-            if let Some(tb) = self.text_boxes.get(key) {
-                if let Some(text) = tb.selected_text() {
-                    if !self.selected_text_buffer.is_empty() && !self.selected_text_buffer.ends_with(' ') {
-                        self.selected_text_buffer.push(' ');
-                    }
-                    self.selected_text_buffer.push_str(text);
-                }
-            }
-        }
+        push_selected_text(&mut self.selected_text_buffer, &self.text_boxes, &self.shared.multi_box_selection);
 
         if self.selected_text_buffer.is_empty() {
             None
@@ -1790,7 +1902,7 @@ impl Text {
                         let target_scroll = current_scroll - scroll_amount;
                         
                         let total_text_height = te.text_box.layout.height();
-                        let text_height = te.text_box.height;
+                        let text_height = te.text_box.content_height();
                         let max_scroll = (total_text_height - text_height).max(0.0).round();
                         let clamped_target = target_scroll.clamp(0.0, max_scroll).round();
                         
@@ -2040,6 +2152,23 @@ impl Text {
     }
 }
 
+/// Appends the selected text of the boxes with the given keys to `buffer`, with a space between the segments of different boxes. Segments from consecutive boxes of the same text thread are joined without a space.
+pub(crate) fn push_selected_text(buffer: &mut String, text_boxes: &SlotMap<DefaultKey, TextBox>, keys: &[DefaultKey]) {
+    let mut previous_thread = None;
+    for &key in keys {
+        if let Some(tb) = text_boxes.get(key) {
+            if let Some(text) = tb.selected_text_for_copy() {
+                let same_thread = tb.thread.is_some() && tb.thread == previous_thread;
+                if !same_thread && !buffer.is_empty() && !buffer.ends_with(' ') {
+                    buffer.push(' ');
+                }
+                buffer.push_str(&text);
+                previous_thread = tb.thread;
+            }
+        }
+    }
+}
+
 /// Update scroll by adjusting BoxGpu translation instead of modifying quad positions.
 /// Returns false if scroll has exceeded the tolerance from the base position (line culling boundary),
 /// in which case a full re-prepare is needed to get the correct lines.
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, ptr::NonNull};
+use std::{cell::RefCell, ops::Range, ptr::NonNull};
 
 #[cfg(feature = "accessibility")]
 use accesskit::{Node, NodeId, Rect as AccessRect, Role, TreeUpdate};
@@ -9,14 +9,23 @@ use winit::{
 };
 use arboard::Clipboard;
 
-use parley::{Affinity, Alignment, Selection};
+use parley::{Affinity, Alignment};
 
 use crate::*;
 use slotmap::{DefaultKey, Key as SlotMapKeyTrait};
 use std::hash::{Hash, Hasher};
 use ahash::AHasher;
 
-const X_TOLERANCE: f64 = 35.0;
+pub(crate) const X_TOLERANCE: f64 = 35.0;
+
+/// The text shown in a layout, with the spans and the inline boxes moved to it. See [`TextBox::layout_input()`].
+pub(crate) struct LayoutInput<'a> {
+    pub text: Cow<'a, str>,
+    pub spans: Cow<'a, [TextSpan]>,
+    pub inline_boxes: Vec<InlineBox>,
+    /// Positions of the direction marks in the text before they were inserted.
+    pub marks: Vec<usize>,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum TextIdentity {
@@ -36,11 +45,11 @@ pub(crate) fn hash_text(text: &str) -> u64 {
 /// 
 /// Then, pass the handle to [`Text::get_text_box_mut()`] to get a reference to it.
 pub struct TextBox {
-    pub(crate) text: Cow<'static, str>,
+    pub(crate) text: TextContent,
     pub(crate) text_identity: Option<TextIdentity>,
     pub(crate) style: StyleHandle,
     pub(crate) style_version: u64,
-    pub(crate) layout: Layout<ColorBrush>,
+    pub(crate) layout: TextLayout,
 
     #[cfg(feature = "accessibility")]
     pub(crate) layout_access: LayoutAccessibility,
@@ -51,10 +60,11 @@ pub struct TextBox {
     pub(crate) transform: Transform2D,
     pub(crate) max_advance: f32,
     pub(crate) depth: f32,
-    pub(crate) selection: Selection,
+    pub(crate) selection: TextSelection,
     pub(crate) width: f32,
     pub(crate) height: f32,
     pub(crate) alignment: Alignment,
+    pub(crate) vertical_alignment: VerticalAlignment,
     pub(crate) clip_rect: Option<parley::BoundingBox>,
     pub(crate) screen_space_clip_rect: Option<(f32, f32, f32, f32)>, // (min_x, min_y, max_x, max_y) in screen space
     pub(crate) auto_clip: bool,
@@ -87,6 +97,83 @@ pub struct TextBox {
     /// For cross-box selection: the previous text box in the sequence.
     /// When selecting before the start of this box, selection continues into the previous box.
     pub(crate) prev_box: Option<DefaultKey>,
+    /// The text thread that the box is part of, if any.
+    pub(crate) thread: Option<DefaultKey>,
+    /// Set when the box starts in the middle of a paragraph of its thread.
+    pub(crate) continued_paragraph: Option<ContinuedParagraph>,
+    /// The lines of a virtual text box. The text of the box only holds the ones around the view.
+    pub(crate) virtual_lines: Option<Box<VirtualLines>>,
+    /// Whether the screen bounds of the box changed since it was last put in the [`SpatialIndex`].
+    pub(crate) hit_bounds_dirty: bool,
+
+    /// Styled ranges of the text. Kept in sync with the text when edited through a [`TextEdit`].
+    pub(crate) spans: Vec<TextSpan>,
+
+    pub(crate) inline_boxes: Vec<InlineBoxSlot>,
+    /// Positions of the inline boxes in the last layout, in layout coordinates.
+    pub(crate) inline_box_rects: Vec<(u64, BoundingBox)>,
+    pub(crate) inline_images: Vec<InlineImage>,
+    pub(crate) markdown_links: Vec<MarkdownLink>,
+
+    pub(crate) background: BoxBackground,
+    pub(crate) padding: Padding,
+
+    pub(crate) highlights: Vec<Highlight>,
+    pub(crate) next_highlight_id: u64,
+
+    pub(crate) overflow: Overflow,
+    pub(crate) max_lines: Option<usize>,
+    /// The part of the text hidden by the overflow mode in the current layout. When set, the indices in the layout are shifted from the ones in the text.
+    pub(crate) truncation: Option<Truncation>,
+
+    /// When shrink-to-fit is enabled, the size set with [`TextBox::set_size()`]. `width` and `height` hold the shrunk size.
+    pub(crate) shrink_to_fit: Option<(f32, f32)>,
+
+    pub(crate) font_size_fit: Option<FontSizeFit>,
+    /// Multiplier for all font sizes, chosen by the font size fit. It's 1.0 when the fit is disabled.
+    pub(crate) font_fit_scale: f32,
+
+    pub(crate) paragraph_style: Option<ParagraphStyle>,
+    pub(crate) paragraph_style_overrides: Vec<(usize, ParagraphStyle)>,
+    pub(crate) paragraph_markers: Vec<ParagraphMarker>,
+
+    pub(crate) tab_stops: Option<TabStops>,
+    /// Width of the space after each tab in the text, from the last layout.
+    pub(crate) tab_widths: Vec<f32>,
+
+    pub(crate) base_direction: BaseDirection,
+    pub(crate) cursor_movement: CursorMovement,
+    /// Positions in the shown text where the layout has a direction mark. See [`direction_mark_positions()`].
+    pub(crate) direction_marks: Vec<usize>,
+
+    /// Language tag set with [`TextBox::set_locale()`].
+    pub(crate) locale: Option<String>,
+    /// The parsed `locale`, pushed over the style spans that don't have their own.
+    pub(crate) locale_property: Option<StyleProperty<'static, ColorBrush>>,
+
+    pub(crate) writing_mode: WritingMode,
+
+    pub(crate) text_path: Option<TextPath>,
+    /// The text path split into straight segments, kept in sync with `text_path`.
+    pub(crate) flattened_path: Option<FlattenedPath>,
+
+    pub(crate) exclusions: Vec<Exclusion>,
+}
+
+/// Vertical position of the text inside a [`TextBox`]. See [`TextBox::set_vertical_alignment()`].
+///
+/// Text that is taller than the box is always placed at the top, so that it can be scrolled normally.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum VerticalAlignment {
+    /// The text starts at the top of the box.
+    #[default]
+    Top,
+    /// The text is centered in the box.
+    Center,
+    /// The text ends at the bottom of the box.
+    Bottom,
+    /// The baseline of the first line is placed at this distance from the top of the box, inside the padding.
+    Baseline(f32),
 }
 
 /// Metadata and cache for the render data of a text box
@@ -96,12 +183,18 @@ pub(crate) struct RenderDataInfo {
     pub glyph_quad_range: Option<(usize, usize)>,
     /// Index into the text renderer's box_data array for this text box
     pub box_index: usize,
+    /// Index into the box_data array for the background of this text box, which isn't scrolled or clipped. Only allocated while the box has a visible background.
+    pub background_box_index: Option<usize>,
     /// The scroll offset when quads were prepared (for tolerance check)
     pub base_scroll: (f32, f32),
     /// The scroll offset currently reflected in BoxGpu translation (for incremental delta)
     pub last_scroll: (f32, f32),
     /// These quads are still quite slow to create, even if the glyph bitmaps are all in the cache. Probably because the parley datastructures are complicated and slow to traverse (I think I remember seeing it spend a lot of time in ".flat_map(|cluster| cluster.glyphs()))") or because of the cache lookups.
     pub cached_glyph_quads: Vec<GlyphQuad>,
+    /// The same quads, split by paragraph, so that the unchanged paragraphs can be reused when the text changes.
+    pub paragraph_quads: Vec<ParagraphQuads>,
+    /// The paragraphs of the current layout, with their keys once they're hashed. Emptied when the layout is rebuilt.
+    pub layout_paragraphs: Vec<LayoutParagraph>,
     /// Cache generation when quads were cached. Compared against RenderData.cache_generation
     /// to check validity. Set to 0 to invalidate (text change), global generation increments on glyph eviction.
     pub cache_generation: u64,
@@ -157,10 +250,10 @@ impl TextBox {
             scale: 1.0,
         };
         Self {
-            text: text.into(),
+            text: TextContent::Cow(text.into()),
             text_identity: None,
             style_version: 0,
-            layout: Layout::new(),
+            layout: TextLayout::default(),
             #[cfg(feature = "accessibility")]
             layout_access: LayoutAccessibility::default(),
             #[cfg(feature = "accessibility")]
@@ -171,10 +264,11 @@ impl TextBox {
             max_advance: size.0,
             height: size.1,
             depth,
-            selection: Selection::default(),
+            selection: TextSelection::default(),
             style: StyleHandle { key: default_style_key },
             width: size.0,
             alignment: Default::default(),
+            vertical_alignment: VerticalAlignment::default(),
             clip_rect: None,
             screen_space_clip_rect: None,
             auto_clip: false,
@@ -186,9 +280,12 @@ impl TextBox {
             render_data_info: RenderDataInfo {
                 glyph_quad_range: None,
                 box_index: 0,
+                background_box_index: None,
                 base_scroll: (0.0, 0.0),
                 last_scroll: (0.0, 0.0),
                 cached_glyph_quads: Vec::with_capacity(10),
+                paragraph_quads: Vec::new(),
+                layout_paragraphs: Vec::new(),
                 cache_generation: 0,
             },
             explicit_hitbox: None,
@@ -196,13 +293,46 @@ impl TextBox {
             key: DefaultKey::null(), // Remember to fill it in later, I guess.
             next_box: None,
             prev_box: None,
+            thread: None,
+            continued_paragraph: None,
+            virtual_lines: None,
+            hit_bounds_dirty: true,
+            spans: Vec::new(),
+            inline_boxes: Vec::new(),
+            inline_box_rects: Vec::new(),
+            inline_images: Vec::new(),
+            markdown_links: Vec::new(),
+            background: BoxBackground::default(),
+            padding: Padding::default(),
+            highlights: Vec::new(),
+            next_highlight_id: 0,
+            overflow: Overflow::default(),
+            max_lines: None,
+            truncation: None,
+            shrink_to_fit: None,
+            font_size_fit: None,
+            font_fit_scale: 1.0,
+            paragraph_style: None,
+            paragraph_style_overrides: Vec::new(),
+            paragraph_markers: Vec::new(),
+            tab_stops: None,
+            tab_widths: Vec::new(),
+            base_direction: BaseDirection::default(),
+            cursor_movement: CursorMovement::default(),
+            direction_marks: Vec::new(),
+            locale: None,
+            locale_property: None,
+            writing_mode: WritingMode::default(),
+            text_path: None,
+            flattened_path: None,
+            exclusions: Vec::new(),
         }
     }
 
     #[must_use]
     pub(crate) fn hit_full_rect(&self, cursor_pos: (f64, f64)) -> bool {
         // Transform cursor position to text box local space
-        let inv_transform = self.transform().inverse().unwrap_or(Transform2D::identity());
+        let inv_transform = self.frame_transform().inverse().unwrap_or(Transform2D::identity());
         let local_pos = inv_transform.transform_point(euclid::Point2D::new(cursor_pos.0 as f32, cursor_pos.1 as f32));
 
         let offset = (local_pos.x as f64, local_pos.y as f64);
@@ -218,7 +348,7 @@ impl TextBox {
 
         // Default behavior
         let hit = offset.0 > -X_TOLERANCE
-            && offset.0 < self.max_advance as f64 + X_TOLERANCE
+            && offset.0 < self.width as f64 + X_TOLERANCE
             && offset.1 > 0.0
             && offset.1 < self.height as f64;
 
@@ -229,7 +359,7 @@ impl TextBox {
     /// Used for determining when to extend selection to the next linked box.
     /// Returns true if the cursor is below the box, or on the last line and past the right edge.
     pub(crate) fn is_cursor_past_end(&self, cursor_pos: (f64, f64)) -> bool {
-        let inv_transform = self.transform().inverse().unwrap_or(Transform2D::identity());
+        let inv_transform = self.frame_transform().inverse().unwrap_or(Transform2D::identity());
         let local_pos = inv_transform.transform_point(euclid::Point2D::new(cursor_pos.0 as f32, cursor_pos.1 as f32));
 
         // Past the bottom of the box
@@ -238,9 +368,10 @@ impl TextBox {
         }
 
         // On or past the last line and past the right edge
+        let (offset_x, offset_y) = self.content_offset();
         let text_height = self.layout.height();
         let last_line_y = text_height - self.layout.lines().last().map(|l| l.metrics().line_height).unwrap_or(0.0);
-        if local_pos.y >= last_line_y && local_pos.x > self.layout.full_width() {
+        if local_pos.y - offset_y >= last_line_y && local_pos.x - offset_x > self.layout.full_width() {
             return true;
         }
 
@@ -251,7 +382,7 @@ impl TextBox {
     /// Used for determining when to extend selection to the previous linked box.
     /// Returns true if the cursor is above the box, or on the first line and before the left edge.
     pub(crate) fn is_cursor_before_start(&self, cursor_pos: (f64, f64)) -> bool {
-        let inv_transform = self.transform().inverse().unwrap_or(Transform2D::identity());
+        let inv_transform = self.frame_transform().inverse().unwrap_or(Transform2D::identity());
         let local_pos = inv_transform.transform_point(euclid::Point2D::new(cursor_pos.0 as f32, cursor_pos.1 as f32));
 
         // Before the top of the box
@@ -260,8 +391,9 @@ impl TextBox {
         }
 
         // On the first line and before the left edge
+        let (offset_x, offset_y) = self.content_offset();
         let first_line_height = self.layout.lines().next().map(|l| l.metrics().line_height).unwrap_or(self.height);
-        if local_pos.y < first_line_height && local_pos.x < 0.0 {
+        if local_pos.y - offset_y < first_line_height && local_pos.x - offset_x < 0.0 {
             return true;
         }
 
@@ -282,10 +414,11 @@ impl TextBox {
         let width = self.layout.width();
         let height = self.layout.height();
 
-        let top_left = self.transform.transform_point(euclid::Point2D::new(0.0, 0.0));
-        let top_right = self.transform.transform_point(euclid::Point2D::new(width, 0.0));
-        let bottom_left = self.transform.transform_point(euclid::Point2D::new(0.0, height));
-        let bottom_right = self.transform.transform_point(euclid::Point2D::new(width, height));
+        let transform = self.frame_transform();
+        let top_left = transform.transform_point(euclid::Point2D::new(0.0, 0.0));
+        let top_right = transform.transform_point(euclid::Point2D::new(width, 0.0));
+        let bottom_left = transform.transform_point(euclid::Point2D::new(0.0, height));
+        let bottom_right = transform.transform_point(euclid::Point2D::new(width, height));
 
         // Compute the axis-aligned bounding box of the transformed corners
         let min_x = top_left.x.min(top_right.x).min(bottom_left.x).min(bottom_right.x);
@@ -355,14 +488,14 @@ impl TextBox {
     /// Returns the currently selected text, or `None` if no text is currently selected.
     pub fn selected_text(&self) -> Option<&str> {
         if !self.selection.is_collapsed() {
-            self.text.get(self.selection.text_range())
+            self.text.get(self.layout_to_text_range(self.selection.text_range()))
         } else {
             None
         }
     }
 
     /// Returns the current selection of the text box.
-    pub fn selection(&self) -> Selection {
+    pub fn selection(&self) -> TextSelection {
         self.selection
     }
 
@@ -393,22 +526,26 @@ impl TextBox {
 impl TextBox {
     pub(crate) fn effective_clip_rect(&self) -> Option<parley::BoundingBox> {
         let auto_clip_rect = if self.auto_clip {
+            // The content area in layout coordinates starts above the layout if the text is aligned down.
+            let top = self.scroll_offset.1 - self.vertical_align_offset();
             Some(parley::BoundingBox {
                 x0: self.scroll_offset.0 as f64,
-                y0: self.scroll_offset.1 as f64,
+                y0: top as f64,
                 x1: (self.scroll_offset.0 + self.max_advance) as f64,
-                y1: (self.scroll_offset.1 + self.height) as f64,
+                y1: (top + self.content_height()) as f64,
             })
         } else {
             None
         };
 
+        // The explicit clip rect is in local space, so it has to be moved by the padding as well.
+        let (offset_x, offset_y) = self.content_offset();
         let clip_rect = self.clip_rect.map(|explicit| {
             parley::BoundingBox {
-                x0: explicit.x0 + self.scroll_offset.0 as f64,
-                y0: explicit.y0 + self.scroll_offset.1 as f64,
-                x1: explicit.x1 + self.scroll_offset.0 as f64,
-                y1: explicit.y1 + self.scroll_offset.1 as f64,
+                x0: explicit.x0 + (self.scroll_offset.0 - offset_x) as f64,
+                y0: explicit.y0 + (self.scroll_offset.1 - offset_y) as f64,
+                x1: explicit.x1 + (self.scroll_offset.0 - offset_x) as f64,
+                y1: explicit.y1 + (self.scroll_offset.1 - offset_y) as f64,
             }
         });
 
@@ -474,6 +611,7 @@ impl TextBox {
         let initial_selection = self.selection;
 
         let mut consumed = self.handle_event_no_edit(event, input_state, false);
+        self.sync_virtual_selection();
 
         // Handle mouse wheel scrolling for multi-line text boxes with auto_clip
         if let WindowEvent::MouseWheel { delta, .. } = event {
@@ -485,13 +623,15 @@ impl TextBox {
                         winit::event::MouseScrollDelta::PixelDelta(pos) => pos.y as f32,
                     };
 
-                    if scroll_amount.abs() > 0.1 {
+                    if scroll_amount.abs() > 0.1 && self.virtual_lines.is_some() {
+                        consumed |= self.scroll_virtual_lines_by(-scroll_amount);
+                    } else if scroll_amount.abs() > 0.1 {
                         let old_scroll = self.scroll_offset.1;
                         let new_scroll = old_scroll - scroll_amount;
 
                         self.refresh_layout();
                         let total_text_height = self.layout.height();
-                        let text_height = self.height;
+                        let text_height = self.content_height();
                         let max_scroll = (total_text_height - text_height).max(0.0).round();
                         let new_scroll = new_scroll.clamp(0.0, max_scroll).round();
 
@@ -530,10 +670,10 @@ impl TextBox {
                 // macOS seems to generate a spurious move after selecting word?
                 if input_state.mouse.pointer_down {
                     // Transform cursor position to text box local space
-                    let inv_transform = self.transform().inverse().unwrap_or(Transform2D::identity());
+                    let inv_transform = self.frame_transform().inverse().unwrap_or(Transform2D::identity());
                     let local_pos = inv_transform.transform_point(euclid::Point2D::new(cursor_pos.0, cursor_pos.1));
-                    let left = 0.0f32;
-                    let top = 0.0f32;
+                    // Edges of the visible content area, not of the layout, which can be moved by the vertical alignment.
+                    let (left, top) = (self.padding.left, self.padding.top);
                     let scroll_offset_x = self.scroll_offset.0;
                     let scroll_offset_y = self.scroll_offset.1;
                     let cursor_pos = (local_pos.x, local_pos.y);
@@ -571,10 +711,10 @@ impl TextBox {
                             if new_scroll_y != scroll_offset_y {
                                 did_scroll = true;
                             }
-                        } else if cursor_pos.1 > (top + self.height) - scroll_margin {
+                        } else if cursor_pos.1 > (top + self.content_height()) - scroll_margin {
                             // Near bottom border - scroll down
                             let total_text_height = self.layout.height();
-                            let max_scroll_y = (total_text_height - self.height).max(0.0);
+                            let max_scroll_y = (total_text_height - self.content_height()).max(0.0);
                             new_scroll_y = (scroll_offset_y + scroll_speed).min(max_scroll_y);
                             if new_scroll_y != scroll_offset_y {
                                 did_scroll = true;
@@ -588,10 +728,14 @@ impl TextBox {
                         }
                     }
 
-                    let cursor_pos = (
-                        cursor_pos.0 - left + new_scroll_x,
-                        cursor_pos.1 - top + new_scroll_y,
-                    );
+                    let cursor_pos = if self.flattened_path.is_some() {
+                        self.local_to_layout(cursor_pos)
+                    } else {
+                        (
+                            cursor_pos.0 - left + new_scroll_x,
+                            cursor_pos.1 - top + new_scroll_y,
+                        )
+                    };
                     self.selection.extend_selection_to_point(
                         &self.layout,
                         cursor_pos.0,
@@ -603,16 +747,11 @@ impl TextBox {
             WindowEvent::MouseInput { state, button, .. } => {
                 let shift = input_state.modifiers.state().shift_key();
                 if *button == winit::event::MouseButton::Left {
-                    // Transform cursor position to text box local space
-                    let inv_transform = self.transform().inverse().unwrap_or(Transform2D::identity());
-                    let local_pos = inv_transform.transform_point(euclid::Point2D::new(
+                    // Transform cursor position to layout space
+                    let cursor_pos = self.screen_to_layout((
                         input_state.mouse.cursor_pos.0 as f32,
                         input_state.mouse.cursor_pos.1 as f32
                     ));
-                    let cursor_pos = (
-                        local_pos.x + self.scroll_offset.0,
-                        local_pos.y + self.scroll_offset.1,
-                    );
 
                     if state.is_pressed() {
                         let click_count = input_state.mouse.click_count;
@@ -622,7 +761,7 @@ impl TextBox {
                             _ => {
                                 if shift {
                                     self.set_selection(
-                                        self.selection.shift_click_extension(&self.layout, cursor_pos.0, cursor_pos.1)
+                                        self.selection.extend_to_point(&self.layout, cursor_pos.0, cursor_pos.1)
                                     )
                                 } else {
                                     self.selection.move_to_point(&self.layout, cursor_pos.0, cursor_pos.1);
@@ -647,12 +786,12 @@ impl TextBox {
                 };
 
                 if shift {
-                    match &event.logical_key {
+                    match &self.frame_arrow_key(&event.logical_key) {
                         Key::Named(NamedKey::ArrowLeft) => {
                             if action_mod {
                                 self.selection.select_word_left(&self.layout);
                             } else {
-                                self.selection.select_left(&self.layout);
+                                self.selection = self.step_horizontally(self.selection, false, true);
                             }
                             consumed = true;
                         }
@@ -660,7 +799,7 @@ impl TextBox {
                             if action_mod {
                                 self.selection.select_word_right(&self.layout);
                             } else {
-                                self.selection.select_right(&self.layout);
+                                self.selection = self.step_horizontally(self.selection, true, true);
                             }
                             consumed = true;
                         }
@@ -711,6 +850,8 @@ impl TextBox {
             _ => {}
         }
 
+        self.selection = self.snap_selection_to_tab_boxes(self.selection);
+
         return consumed;
     }
 
@@ -724,20 +865,33 @@ impl TextBox {
     /// 
     /// To manipulate the text as a `String`, call `Cow::to_mut()` on the result, or use [`Self::text_mut_string()`]
     /// 
+    /// If the text is held in a custom [`TextStorage`], it's moved back into a `String` and the custom storage is dropped. Use [`Self::text_mut_string()`] to keep it.
+    /// 
     /// After this method is called, the [`TextBox`] will assume that its text has changed. If you have to call this method many times with the same text every time, as when building a declarative or immediate mode interface, consider using a method like [`Self::set_text_hashed()`].
     pub fn text_mut(&mut self) -> &mut Cow<'static, str> {
-        self.needs_relayout = true;
-        self.render_data_info.cache_generation = 0;
-        self.shared_mut().rebuild_glyph_quad_buffer = true;
-        self.text_identity = None;
-        &mut self.text
+        if let TextContent::Storage(storage) = self.text_storage_mut() {
+            self.text = TextContent::Cow(Cow::Owned(mem::take(storage.as_mut_string())));
+        }
+        match &mut self.text {
+            TextContent::Cow(text) => text,
+            TextContent::Storage(_) => unreachable!(),
+        }
     }
 
     /// Returns a mutable reference to the text content as a `String`. If the text was a borrowed `&str`, it will be cloned.
     ///
-    /// This is a convenience method over [`Self::text_mut()`]
+    /// If the text is held in a custom [`TextStorage`], this returns the `String` from [`TextStorage::as_mut_string()`].
     pub fn text_mut_string(&mut self) -> &mut String {
-        self.text_mut().to_mut()
+        self.text_storage_mut().as_mut_string()
+    }
+
+    /// Returns the text storage, assuming that the text will change.
+    pub(crate) fn text_storage_mut(&mut self) -> &mut TextContent {
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+        self.text_identity = None;
+        &mut self.text
     }
 
     /// Set the text in the text box.
@@ -751,16 +905,7 @@ impl TextBox {
         self.shared_mut().rebuild_glyph_quad_buffer = true;
         self.text_identity = None;
 
-        match &mut self.text {
-            Cow::Owned(s) => {
-                s.clear();
-                s.push_str(new_text);
-            }
-            Cow::Borrowed(_) => {
-                // We can't store &str references with arbitrary lifetimes, so if a text box that's currently holding a 'static str receives a non-'static string, we have to allocate a new string buffer.
-                self.text = Cow::Owned(new_text.to_string());
-            }
-        }
+        self.text.set(new_text);
     }
 
     /// Set the text in the text box.
@@ -777,15 +922,7 @@ impl TextBox {
         self.render_data_info.cache_generation = 0;
         self.shared_mut().rebuild_glyph_quad_buffer = true;
 
-        match &mut self.text {
-            Cow::Owned(s) => {
-                s.clear();
-                s.push_str(new_text);
-            }
-            Cow::Borrowed(_) => {
-                self.text = Cow::Owned(new_text.to_string());
-            }
-        }
+        self.text.set(new_text);
     }
 
     /// Sets the text in the text box, storing the value of the `text` pointer for comparison.
@@ -805,15 +942,7 @@ impl TextBox {
         self.render_data_info.cache_generation = 0;
         self.shared_mut().rebuild_glyph_quad_buffer = true;
 
-        match &mut self.text {
-            Cow::Owned(s) => {
-                s.clear();
-                s.push_str(new_text);
-            }
-            Cow::Borrowed(_) => {
-                self.text = Cow::Owned(new_text.to_string());
-            }
-        }
+        self.text.set(new_text);
     }
 
     /// Sets the text to a static string reference.
@@ -822,7 +951,7 @@ impl TextBox {
         self.render_data_info.cache_generation = 0;
         self.shared_mut().rebuild_glyph_quad_buffer = true;
         self.text_identity = None;
-        self.text = Cow::Borrowed(text);
+        self.text.set_static(text);
     }
 
     /// Sets the text to a static string reference, storing the value of the `text` pointer for comparison.
@@ -839,7 +968,7 @@ impl TextBox {
         self.needs_relayout = true;
         self.render_data_info.cache_generation = 0;
         self.shared_mut().rebuild_glyph_quad_buffer = true;
-        self.text = Cow::Borrowed(text);
+        self.text.set_static(text);
     }
 
     #[cfg(feature = "accessibility")]
@@ -858,6 +987,7 @@ impl TextBox {
     pub fn set_pos(&mut self, pos: (f64, f64)) {
         let new_translation = (pos.0 as f32, pos.1 as f32);
         self.transform.translation = new_translation;
+        self.mark_hit_bounds_dirty();
     }
 
     /// Sets the position of the text box without updating the retained transform.
@@ -867,6 +997,7 @@ impl TextBox {
             return;
         }
         self.transform.translation = new_translation;
+        self.mark_hit_bounds_dirty();
         self.shared_mut().rebuild_glyph_quad_buffer = true;
     }
 
@@ -881,6 +1012,7 @@ impl TextBox {
         self.transform.translation = transform.translation;
         self.transform.rotation = transform.rotation;
         self.transform.scale = transform.scale;
+        self.mark_hit_bounds_dirty();
         self.shared_mut().rebuild_glyph_quad_buffer = true;
     }
 
@@ -895,6 +1027,7 @@ impl TextBox {
         self.transform.translation = transform.translation;
         self.transform.rotation = transform.rotation;
         self.transform.scale = transform.scale;
+        self.mark_hit_bounds_dirty();
         self.shared_mut().rebuild_glyph_quad_buffer = true;
     }
 
@@ -913,6 +1046,7 @@ impl TextBox {
             return;
         }
         self.transform.translation = (x, y);
+        self.mark_hit_bounds_dirty();
         self.shared_mut().rebuild_glyph_quad_buffer = true;
     }
 
@@ -922,6 +1056,7 @@ impl TextBox {
             return;
         }
         self.transform.rotation = radians;
+        self.mark_hit_bounds_dirty();
         self.shared_mut().rebuild_glyph_quad_buffer = true;
     }
 
@@ -934,6 +1069,7 @@ impl TextBox {
         if hidden {
             self.reset_selection();
         }
+        self.mark_hit_bounds_dirty();
         self.shared_mut().rebuild_glyph_quad_buffer = true;
     }
 
@@ -974,6 +1110,7 @@ impl TextBox {
     /// computing one from the text box dimensions or layout.
     pub fn set_hitbox(&mut self, hitbox: Option<(f32, f32, f32, f32)>) {
         self.explicit_hitbox = hitbox;
+        self.mark_hit_bounds_dirty();
     }
 
     /// Returns the explicit hitbox if set.
@@ -1003,8 +1140,90 @@ impl TextBox {
         self.shared_mut().rebuild_glyph_quad_buffer = true;
     }
 
+    /// Adds a styled range of text.
+    /// 
+    /// `range` is a byte range into the text. Spans that don't fall on char boundaries are ignored when building the layout.
+    pub fn add_span(&mut self, range: Range<usize>, style: SpanStyle) {
+        self.spans.push(TextSpan { range, style });
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Replaces all the styled ranges of the text.
+    pub fn set_spans(&mut self, spans: Vec<TextSpan>) {
+        self.spans = spans;
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Removes all the styled ranges of the text.
+    pub fn clear_spans(&mut self) {
+        if self.spans.is_empty() {
+            return;
+        }
+        self.spans.clear();
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the styled ranges of the text.
+    pub fn spans(&self) -> &[TextSpan] {
+        &self.spans
+    }
+
+    /// Returns the key of the registered style that applies at a byte index: the last added span with a [`SpanStyle::Style`] covering it, or the box's own style.
+    pub(crate) fn style_key_at(&self, index: usize) -> DefaultKey {
+        for span in self.spans.iter().rev() {
+            if let SpanStyle::Style(handle) = &span.style {
+                if span.range.contains(&index) && self.shared().styles.contains_key(handle.key) {
+                    return handle.key;
+                }
+            }
+        }
+        return self.style.key;
+    }
+
+    /// Replaces a range of the text, moving the spans so that they keep covering the same text.
+    pub(crate) fn replace_text_range(&mut self, range: Range<usize>, new_text: &str) {
+        self.map_paragraph_styles_for_replace(&range, new_text);
+        for span in &mut self.spans {
+            span.range = map_range_for_replace(&span.range, &range, new_text.len());
+        }
+        self.spans.retain(|span| !span.range.is_empty());
+        for inline_box in &mut self.inline_boxes {
+            inline_box.index = map_index_for_replace(inline_box.index, &range, new_text.len());
+        }
+        for image in &mut self.inline_images {
+            image.index = map_index_for_replace(image.index, &range, new_text.len());
+        }
+        for link in &mut self.markdown_links {
+            link.range = map_range_for_replace(&link.range, &range, new_text.len());
+        }
+        self.markdown_links.retain(|link| !link.range.is_empty());
+        for highlight in &mut self.highlights {
+            highlight.range = map_range_for_replace(&highlight.range, &range, new_text.len());
+        }
+
+        self.text_storage_mut().replace_range(range, new_text);
+    }
+
+    /// Returns the version of the box's style, or of the most recently changed style used by one of its spans.
+    /// 
+    /// Versions come from a single increasing counter, so any style change bumps the result.
     pub(crate) fn style_version(&self) -> u64 {
-        self.shared().styles[self.style.key].version
+        let styles = &self.shared().styles;
+        let mut version = styles[self.style.key].version;
+        for span in &self.spans {
+            if let SpanStyle::Style(handle) = &span.style {
+                if let Some(style) = styles.get(handle.key) {
+                    version = version.max(style.version);
+                }
+            }
+        }
+        return version;
     }
 
     pub(crate) fn style_version_changed(&self) -> bool {
@@ -1026,69 +1245,219 @@ impl TextBox {
         scale_factor
     }
 
-    pub(crate) fn rebuild_layout(
-        &mut self,
+    /// Returns the text shown in the layout, with the spans and the inline boxes moved to it: the text with a part of it replaced by an ellipsis if it's truncated, and with the direction marks inserted.
+    pub(crate) fn layout_input(&self, truncation: Option<&Truncation>) -> LayoutInput<'_> {
+        let has_spans = !(self.spans.is_empty() && self.inline_boxes.is_empty() && self.inline_images.is_empty() && self.tab_stops.is_none());
+        let inline_boxes = if has_spans { self.parley_inline_boxes() } else { Vec::new() };
+
+        let text = match truncation {
+            Some(truncation) => Cow::Owned(truncation.apply(&self.text)),
+            None => Cow::Borrowed(&*self.text),
+        };
+        let (mut spans, mut inline_boxes) = match truncation {
+            Some(truncation) if has_spans => {
+                let (spans, inline_boxes) = truncation.map_spans_and_boxes(&self.spans, inline_boxes);
+                (Cow::Owned(spans), inline_boxes)
+            }
+            _ => (Cow::Borrowed(&self.spans[..]), inline_boxes),
+        };
+
+        let (marks, mark_direction) = self.direction_marks_for(&text);
+        let text = if marks.is_empty() {
+            text
+        } else {
+            let map = |index: usize| index_with_direction_marks(&marks, index);
+            spans = spans.iter()
+                .map(|span| TextSpan { range: map(span.range.start)..map(span.range.end), style: span.style.clone() })
+                .collect();
+            for inline_box in &mut inline_boxes {
+                inline_box.index = map(inline_box.index);
+            }
+            Cow::Owned(insert_direction_marks(&text, &marks, mark_direction))
+        };
+
+        return LayoutInput { text, spans, inline_boxes, marks };
+    }
+
+    /// Shapes a text in the style of the box. The lines aren't broken yet.
+    pub(crate) fn shape_text(
+        &self,
+        text: &str,
+        spans: &[TextSpan],
+        inline_boxes: &[InlineBox],
         color_override: Option<ColorBrush>,
-        single_line: bool,
-    ) {
-        let scale_factor = self.get_scale_factor();
-        
-        let k = self.style.key;
+    ) -> Layout<ColorBrush> {
+        let scale_factor = self.get_scale_factor() * self.font_fit_scale as f64;
         // even sketchier partial borrow moment. self.shared_mut() borrows the whole self
-        let shared = unsafe { self.shared_backref.as_mut() };
-        let style = &mut shared.styles[k].text_style;
-        
-        let layout_cx = &mut shared.layout_cx;
-        let font_cx = &mut shared.font_cx;
-        
-        let mut builder = layout_cx.tree_builder(font_cx, scale_factor as f32, true, style);
+        let mut shared_backref = self.shared_backref;
+        let shared = unsafe { shared_backref.as_mut() };
+        let style = self.localized_style(&shared.styles[self.style.key].text_style);
+        let mut builder = shared.layout_cx.tree_builder(&mut shared.font_cx, scale_factor as f32, true, &style);
 
-        if let Some(color_override) = color_override {
+        if self.is_vertical() {
             builder.push_style_modification_span(&[
-                StyleProperty::Brush(color_override)
+                StyleProperty::FontFeatures(VERTICAL_FONT_FEATURES.into())
             ]);
         }
 
-        builder.push_text(&self.text);
+        if spans.is_empty() && inline_boxes.is_empty() {
+            if let Some(color_override) = color_override {
+                builder.push_style_modification_span(&[
+                    StyleProperty::Brush(color_override)
+                ]);
+            }
 
-        let (mut layout, _) = builder.build();
+            builder.push_text(text);
+        } else {
+            push_text_with_spans(&mut builder, text, spans, inline_boxes, &shared.styles, self.locale_property.as_ref(), color_override);
+        }
+
+        let (layout, _) = builder.build();
+        return layout;
+    }
 
-        if ! single_line {
-            layout.break_all_lines(Some(self.max_advance));
+    /// Builds a layout for the text, or for the text with a part of it replaced by an ellipsis.
+    pub(crate) fn build_layout(
+        &self,
+        truncation: Option<&Truncation>,
+        color_override: Option<ColorBrush>,
+        single_line: bool,
+    ) -> Layout<ColorBrush> {
+        let LayoutInput { text, spans, inline_boxes, marks } = self.layout_input(truncation);
+
+        let mut layout = self.shape_text(&text, &spans, &inline_boxes, color_override);
+        if self.is_vertical() {
+            // The runs are only reachable through the lines.
+            layout.break_all_lines(None);
+            let spacing_spans = upright_spacing_spans(&layout, &text);
+            if !spacing_spans.is_empty() {
+                layout = self.shape_text(&text, &[&spans[..], &spacing_spans[..]].concat(), &inline_boxes, color_override);
+            }
+        }
+
+        if let Some(path) = &self.flattened_path {
+            // Text on a path is a single line, aligned within the length of the path.
+            layout.break_all_lines(None);
+            layout.align(
+                Some(path.length()),
+                self.alignment,
+                AlignmentOptions::default(),
+            );
+        } else if single_line {
+            layout.break_all_lines(None);
+        } else {
+            if self.has_paragraph_styles() || !self.exclusions.is_empty() {
+                self.break_lines_with_geometry(&mut layout, truncation, &marks);
+            } else {
+                layout.break_all_lines(Some(self.max_advance));
+            }
             layout.align(
                 Some(self.max_advance),
                 self.alignment,
                 AlignmentOptions::default(),
             );
+        }
+
+        return layout;
+    }
+
+    /// Builds a single-line layout for a short string in one style, at the same scale as the main layout. Used for things drawn next to the text, like list markers.
+    pub(crate) fn build_plain_layout(&self, text: &str, style_key: DefaultKey) -> Layout<ColorBrush> {
+        let scale_factor = self.get_scale_factor() * self.font_fit_scale as f64;
+        // Same partial borrow trick as in shape_text().
+        let mut shared_backref = self.shared_backref;
+        let shared = unsafe { shared_backref.as_mut() };
+        let style = self.localized_style(&shared.styles[style_key].text_style);
+        let mut builder = shared.layout_cx.tree_builder(&mut shared.font_cx, scale_factor as f32, true, &style);
+        builder.push_text(text);
+        let (mut layout, _) = builder.build();
+        layout.break_all_lines(None);
+        return layout;
+    }
+
+    pub(crate) fn rebuild_layout(
+        &mut self,
+        color_override: Option<ColorBrush>,
+        single_line: bool,
+    ) {
+        if let Some((width, height)) = self.shrink_to_fit {
+            self.width = width;
+            self.height = height;
+        }
+
+        self.update_virtual_window();
+        // Lines of virtual boxes all have the same height, so they aren't wrapped.
+        let single_line = single_line || self.virtual_lines.is_some();
+
+        self.truncation = None;
+        self.direction_marks = self.direction_marks_for(&self.text).0;
+
+        let mut layout = if self.can_split_paragraphs(single_line) {
+            // Only the paragraphs that changed since the last layout are shaped again.
+            let old_layout = mem::take(&mut self.layout);
+            self.build_paragraph_layout(old_layout, color_override)
         } else {
-            layout.break_all_lines(None);
+            let layout = match self.fit_font_size(color_override, single_line) {
+                Some(layout) => layout,
+                None => self.build_layout(None, color_override, single_line),
+            };
+            TextLayout::from_layout(self.apply_tab_stops(layout, None, color_override, single_line))
+        };
+
+        self.truncation = self.find_truncation(&layout, color_override, single_line);
+        if let Some(truncation) = self.truncation.clone() {
+            self.direction_marks = self.direction_marks_for(&truncation.apply(&self.text)).0;
+            let truncated = self.build_layout(Some(&truncation), color_override, single_line);
+            // The ellipsis moves the text after it, so the tabs need other widths to reach their stops.
+            layout = TextLayout::from_layout(self.apply_tab_stops(truncated, Some(&truncation), color_override, single_line));
         }
 
         self.layout = layout;
+        self.render_data_info.layout_paragraphs.clear();
         self.needs_relayout = false;
+        self.apply_shrink_to_fit(single_line);
+        self.collect_paragraph_markers();
+        self.collect_inline_box_rects();
         
         // todo: does this do anything?
         self.selection = self.selection.refresh(&self.layout);
+
+        self.mark_hit_bounds_dirty();
+
+        if self.apply_virtual_layout() {
+            self.rebuild_layout(color_override, single_line);
+        }
     }
 
 
     /// Sets the size of the text box.
     pub fn set_size(&mut self, size: (f32, f32)) {
-        let relayout = (self.width != size.0) || (self.height != size.1) || (self.max_advance != size.0);
-        self.width = size.0;
-        self.height = size.1;
-        self.max_advance = size.0;
+        let size = self.frame_size(size);
+        let relayout = if let Some(max_size) = &mut self.shrink_to_fit {
+            let relayout = *max_size != size;
+            *max_size = size;
+            relayout
+        } else {
+            let relayout = (self.width != size.0) || (self.height != size.1);
+            self.width = size.0;
+            self.height = size.1;
+            relayout
+        };
         if relayout {
             self.needs_relayout = true;
             self.render_data_info.cache_generation = 0;
+            self.mark_hit_bounds_dirty();
         }
+        self.update_max_advance();
     }
 
     /// Returns the size of the text box.
     /// 
+    /// With [shrink-to-fit](`Self::set_shrink_to_fit()`), this is the shrunk size, which is only updated when the layout is refreshed.
+    /// 
     /// By default text boxes don't clip the text. Depending on the purpose, you might want to use the size of the [layout](`Self::layout()`) rather than the size of the text box itself.
     pub fn size(&self) -> (f32, f32) {
-        (self.width, self.height)
+        self.frame_size((self.width, self.height))
     }
 
     /// Sets the text alignment.
@@ -1102,6 +1471,22 @@ impl TextBox {
         self.shared_mut().rebuild_glyph_quad_buffer = true;
     }
 
+    /// Sets the vertical alignment of the text inside the box.
+    pub fn set_vertical_alignment(&mut self, vertical_alignment: VerticalAlignment) {
+        if self.vertical_alignment == vertical_alignment {
+            return;
+        }
+        self.vertical_alignment = vertical_alignment;
+        self.render_data_info.cache_generation = 0;
+        self.mark_hit_bounds_dirty();
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the vertical alignment of the text inside the box.
+    pub fn vertical_alignment(&self) -> VerticalAlignment {
+        self.vertical_alignment
+    }
+
     // todo: scale factor was meant to be a different thing?
     /// Sets the scale factor for the text.
     pub fn set_scale(&mut self, scale: f32) {
@@ -1110,6 +1495,7 @@ impl TextBox {
         }
         self.transform.scale = scale;
         self.needs_relayout = true;
+        self.mark_hit_bounds_dirty();
         self.render_data_info.cache_generation = 0;
         self.shared_mut().rebuild_glyph_quad_buffer = true;
     }
@@ -1139,7 +1525,7 @@ impl TextBox {
     // }
 
     /// Update the selection, and nudge the `Generation` if something other than `h_pos` changed.
-    pub(crate) fn set_selection(&mut self, new_sel: Selection) {
+    pub(crate) fn set_selection(&mut self, new_sel: TextSelection) {
 
         // This debug code is quite useful when diagnosing selection problems.
         #[allow(clippy::print_stderr)] // reason = "unreachable debug code"
@@ -1152,25 +1538,9 @@ impl TextBox {
                 focus.affinity(),
                 cluster[1].as_ref().map(|c| &self.text[c.text_range()]),
             );
-            eprint!("{dbg:?}");
-            let cluster = focus.visual_clusters(&self.layout);
-            let dbg = (
-                cluster[0].as_ref().map(|c| &self.text[c.text_range()]),
-                cluster[0]
-                    .as_ref()
-                    .map(|c| if c.is_word_boundary() { " W" } else { "" })
-                    .unwrap_or_default(),
-                focus.index(),
-                focus.affinity(),
-                cluster[1].as_ref().map(|c| &self.text[c.text_range()]),
-                cluster[1]
-                    .as_ref()
-                    .map(|c| if c.is_word_boundary() { " W" } else { "" })
-                    .unwrap_or_default(),
-            );
-            eprintln!(" | visual: {dbg:?}");
+            eprintln!("{dbg:?}");
         }
-        self.selection = new_sel;
+        self.selection = self.snap_selection_to_tab_boxes(new_sel);
     }
 
     // #[cfg(feature = "accesskit")]
@@ -1214,7 +1584,7 @@ impl TextBox {
 
     /// Move the cursor to the cluster boundary nearest this point in the layout.
     pub(crate) fn move_to_point(&mut self, x: f32, y: f32) {
-        self.set_selection(Selection::from_point(&self.layout, x, y));
+        self.set_selection(TextSelection::from_point(&self.layout, x, y));
     }
 
     /// Move the cursor to the start of the text.
@@ -1252,16 +1622,14 @@ impl TextBox {
         self.set_selection(self.selection.next_line(&self.layout, false));
     }
 
-    /// Move to the next cluster left in visual order.
+    /// Move to the next cluster left, in visual or logical order depending on the cursor movement mode.
     pub(crate) fn move_left(&mut self) {
-        self.set_selection(
-            self.selection.previous_visual(&self.layout, false),
-        );
+        self.set_selection(self.step_horizontally(self.selection, false, false));
     }
 
-    /// Move to the next cluster right in visual order.
+    /// Move to the next cluster right, in visual or logical order depending on the cursor movement mode.
     pub(crate) fn move_right(&mut self) {
-        self.set_selection(self.selection.next_visual(&self.layout, false));
+        self.set_selection(self.step_horizontally(self.selection, true, false));
     }
 
     /// Move to the next word boundary left.
@@ -1282,7 +1650,7 @@ impl TextBox {
     /// Select the whole text.
     pub(crate) fn select_all(&mut self) {
         self.set_selection(
-            Selection::from_byte_index(&self.layout, 0_usize, Affinity::default()).move_lines(
+            TextSelection::from_byte_index(&self.layout, 0_usize, Affinity::default()).move_lines(
                 &self.layout,
                 isize::MAX,
                 true,
@@ -1301,7 +1669,7 @@ impl TextBox {
     }
 
     /// Returns the layout, refreshing it if needed.
-    pub fn layout(&mut self) -> &Layout<ColorBrush> {
+    pub fn layout(&mut self) -> &TextLayout {
         self.refresh_layout();
         &self.layout
     }
@@ -1330,10 +1698,10 @@ impl TextBox {
         self.refresh_layout();
         if let Some(selection) = Selection::from_access_selection(
             selection,
-            &self.layout,
+            self.layout.single(),
             &self.layout_access,
         ) {
-            self.set_selection(selection);
+            self.set_selection(TextSelection::from_parley(selection));
         }
     }
 
@@ -1346,13 +1714,13 @@ impl TextBox {
 pub use parley::BoundingBox;
 
 pub(crate) trait Ext1 {
-    fn hit_bounding_box(&mut self, cursor_pos: (f64, f64)) -> bool;
+    fn hit_bounding_box(&self, cursor_pos: (f64, f64)) -> bool;
 }
 impl Ext1 for TextBox {
-    fn hit_bounding_box(&mut self, cursor_pos: (f64, f64)) -> bool {
-        self.refresh_layout();
+    /// Checks the cursor against the bounds of the last layout. The layout isn't refreshed here, so that pointer queries never lay out text.
+    fn hit_bounding_box(&self, cursor_pos: (f64, f64)) -> bool {
         // Transform cursor position to text box local space
-        let inv_transform = self.transform().inverse().unwrap_or(Transform2D::identity());
+        let inv_transform = self.frame_transform().inverse().unwrap_or(Transform2D::identity());
         let local_pos = inv_transform.transform_point(euclid::Point2D::new(cursor_pos.0 as f32, cursor_pos.1 as f32));
 
         let offset = (local_pos.x as f64, local_pos.y as f64);
@@ -1366,7 +1734,16 @@ impl Ext1 for TextBox {
             return hit;
         }
 
-        // Default behavior
+        // Default behavior: the layout bounds, moved by the padding
+        let (content_x, content_y) = self.content_offset();
+        let offset = (offset.0 - content_x as f64, offset.1 - content_y as f64);
+
+        // Text on a path is somewhere around the path, as far from it as the line is tall.
+        if let Some(path) = &self.flattened_path {
+            let bounds = path.bounds(self.layout.height());
+            return offset.0 > bounds.x0 && offset.0 < bounds.x1 && offset.1 > bounds.y0 && offset.1 < bounds.y1;
+        }
+
         let hit = offset.0 > -X_TOLERANCE
             && offset.0 < self.layout.full_width() as f64 + X_TOLERANCE
             && offset.1 > 0.0
@@ -1378,76 +1755,66 @@ impl Ext1 for TextBox {
 
 
 pub(crate) trait SelectionExt {
-    fn move_to_point(&mut self, layout: &Layout<ColorBrush>, x: f32, y: f32);
-    fn select_word_at_point(&mut self, layout: &Layout<ColorBrush>, x: f32, y: f32);
-    fn select_line_at_point(&mut self, layout: &Layout<ColorBrush>, x: f32, y: f32);
-    fn extend_selection_to_point(&mut self, layout: &Layout<ColorBrush>, x: f32, y: f32);
-    fn select_to_text_start(&mut self, layout: &Layout<ColorBrush>);
-    fn select_to_line_start(&mut self, layout: &Layout<ColorBrush>);
-    fn select_to_text_end(&mut self, layout: &Layout<ColorBrush>);
-    fn select_to_line_end(&mut self, layout: &Layout<ColorBrush>);
-    fn select_up(&mut self, layout: &Layout<ColorBrush>);
-    fn select_down(&mut self, layout: &Layout<ColorBrush>);
-    fn select_left(&mut self, layout: &Layout<ColorBrush>);
-    fn select_right(&mut self, layout: &Layout<ColorBrush>);
-    fn select_word_left(&mut self, layout: &Layout<ColorBrush>);
-    fn select_word_right(&mut self, layout: &Layout<ColorBrush>);
+    fn move_to_point(&mut self, layout: &TextLayout, x: f32, y: f32);
+    fn select_word_at_point(&mut self, layout: &TextLayout, x: f32, y: f32);
+    fn select_line_at_point(&mut self, layout: &TextLayout, x: f32, y: f32);
+    fn extend_selection_to_point(&mut self, layout: &TextLayout, x: f32, y: f32);
+    fn select_to_text_start(&mut self, layout: &TextLayout);
+    fn select_to_line_start(&mut self, layout: &TextLayout);
+    fn select_to_text_end(&mut self, layout: &TextLayout);
+    fn select_to_line_end(&mut self, layout: &TextLayout);
+    fn select_up(&mut self, layout: &TextLayout);
+    fn select_down(&mut self, layout: &TextLayout);
+    fn select_word_left(&mut self, layout: &TextLayout);
+    fn select_word_right(&mut self, layout: &TextLayout);
 }
 
-impl SelectionExt for Selection {
-    fn move_to_point(&mut self, layout: &Layout<ColorBrush>, x: f32, y: f32) {
-        *self = Selection::from_point(layout, x, y);
+impl SelectionExt for TextSelection {
+    fn move_to_point(&mut self, layout: &TextLayout, x: f32, y: f32) {
+        *self = TextSelection::from_point(layout, x, y);
     }
 
-    fn select_word_at_point(&mut self, layout: &Layout<ColorBrush>, x: f32, y: f32) {
-        *self = Selection::word_from_point(layout, x, y);
+    fn select_word_at_point(&mut self, layout: &TextLayout, x: f32, y: f32) {
+        *self = TextSelection::word_from_point(layout, x, y);
     }
 
-    fn select_line_at_point(&mut self, layout: &Layout<ColorBrush>, x: f32, y: f32) {
-        *self = Selection::line_from_point(layout, x, y);
+    fn select_line_at_point(&mut self, layout: &TextLayout, x: f32, y: f32) {
+        *self = TextSelection::line_from_point(layout, x, y);
     }
 
-    fn extend_selection_to_point(&mut self, layout: &Layout<ColorBrush>, x: f32, y: f32) {
+    fn extend_selection_to_point(&mut self, layout: &TextLayout, x: f32, y: f32) {
         *self = self.extend_to_point(layout, x, y);
     }
 
-    fn select_to_text_start(&mut self, layout: &Layout<ColorBrush>) {
+    fn select_to_text_start(&mut self, layout: &TextLayout) {
         *self = self.move_lines(layout, isize::MIN, true);
     }
 
-    fn select_to_line_start(&mut self, layout: &Layout<ColorBrush>) {
+    fn select_to_line_start(&mut self, layout: &TextLayout) {
         *self = self.line_start(layout, true);
     }
 
-    fn select_to_text_end(&mut self, layout: &Layout<ColorBrush>) {
+    fn select_to_text_end(&mut self, layout: &TextLayout) {
         *self = self.move_lines(layout, isize::MAX, true);
     }
 
-    fn select_to_line_end(&mut self, layout: &Layout<ColorBrush>) {
+    fn select_to_line_end(&mut self, layout: &TextLayout) {
         *self = self.line_end(layout, true);
     }
 
-    fn select_up(&mut self, layout: &Layout<ColorBrush>) {
+    fn select_up(&mut self, layout: &TextLayout) {
         *self = self.previous_line(layout, true);
     }
 
-    fn select_down(&mut self, layout: &Layout<ColorBrush>) {
+    fn select_down(&mut self, layout: &TextLayout) {
         *self = self.next_line(layout, true);
     }
 
-    fn select_left(&mut self, layout: &Layout<ColorBrush>) {
-        *self = self.previous_visual(layout, true);
-    }
-
-    fn select_right(&mut self, layout: &Layout<ColorBrush>) {
-        *self = self.next_visual(layout, true);
-    }
-
-    fn select_word_left(&mut self, layout: &Layout<ColorBrush>) {
+    fn select_word_left(&mut self, layout: &TextLayout) {
         *self = self.previous_visual_word(layout, true);
     }
 
-    fn select_word_right(&mut self, layout: &Layout<ColorBrush>) {
+    fn select_word_right(&mut self, layout: &TextLayout) {
         *self = self.next_visual_word(layout, true);
     }
 }
@@ -1465,7 +1832,7 @@ fn push_accesskit_update_text_box_partial_borrows(
     if let Some(id) = accesskit_id {
         inner.layout_access.build_nodes(
             &inner.text,
-            &inner.layout,
+            inner.layout.single(),
             tree_update,
             &mut node,
             node_id_generator,
@@ -1473,7 +1840,7 @@ fn push_accesskit_update_text_box_partial_borrows(
             top,
         );
 
-        if let Some(ak_sel) = inner.selection.to_access_selection(&inner.layout, &inner.layout_access) {
+        if let Some(ak_sel) = inner.selection.to_parley(inner.layout.single()).to_access_selection(inner.layout.single(), &inner.layout_access) {
             node.set_text_selection(ak_sel);
         }
 
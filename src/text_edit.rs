@@ -20,7 +20,7 @@ use crate::*;
 macro_rules! clear_placeholder_partial_borrows {
     ($self:expr) => {
         if $self.showing_placeholder {
-            $self.text_box.text_mut_string().clear();
+            $self.text_box.text_storage_mut().set("");
             $self.showing_placeholder = false;
             $self.text_box.refresh_layout();
             $self.text_box.move_to_text_start();
@@ -90,7 +90,7 @@ impl<'source> IntoIterator for SplitString<'source> {
     }
 }
 
-pub(crate) fn selection_rects_changed(initial_selection: Selection, new_selection: Selection, is_editable: bool) -> bool {
+pub(crate) fn selection_rects_changed(initial_selection: TextSelection, new_selection: TextSelection, is_editable: bool) -> bool {
     // For non-editable boxes, if both selections are collapsed, no change
     if !is_editable && initial_selection.is_collapsed() && new_selection.is_collapsed() {
         return false;
@@ -256,8 +256,8 @@ impl TextEdit {
                             match c.as_str() {
                                 "x" if !shift => {
                                     with_clipboard(|cb| {
-                                        if let Some(text) = self.text_box.selected_text() {
-                                            cb.set_text(text.to_owned()).ok();
+                                        if let Some(text) = self.text_box.selected_text_for_copy() {
+                                            cb.set_text(text.into_owned()).ok();
                                             self.delete_selection();
                                             self.text_box.shared_mut().rebuild_glyph_quad_buffer = true;
                                         }
@@ -289,7 +289,7 @@ impl TextEdit {
                     };
                 }
 
-                match &event.logical_key {
+                match &self.text_box.frame_arrow_key(&event.logical_key) {
                     Key::Named(NamedKey::ArrowLeft) => {
                         if !shift && ! self.showing_placeholder {
                             if action_mod {
@@ -401,26 +401,17 @@ impl TextEdit {
                     consumed = true;
                     match phase {
                         Started => {
-                            // Transform touch position to text box local space
-                            let inv_transform = self.text_box.transform().inverse().unwrap_or(Transform2D::identity());
-                            let local_pos = inv_transform.transform_point(euclid::Point2D::new(location.x as f32, location.y as f32));
-                            let cursor_pos = (
-                                local_pos.x as f64 + self.text_box.scroll_offset.0 as f64,
-                                local_pos.y as f64 + self.text_box.scroll_offset.1 as f64,
-                            );
-                            self.text_box.move_to_point(cursor_pos.0 as f32, cursor_pos.1 as f32);
+                            // Transform touch position to layout space
+                            let cursor_pos = self.text_box.screen_to_layout((location.x as f32, location.y as f32));
+                            self.text_box.move_to_point(cursor_pos.0, cursor_pos.1);
                         }
                         Cancelled => {
                             self.text_box.collapse_selection();
                         }
                         Moved => {
-                            // Transform touch position to text box local space
-                            let inv_transform = self.text_box.transform().inverse().unwrap_or(Transform2D::identity());
-                            let local_pos = inv_transform.transform_point(euclid::Point2D::new(location.x as f32, location.y as f32));
-                            self.text_box.extend_selection_to_point(
-                                local_pos.x + self.text_box.scroll_offset.0,
-                                local_pos.y + self.text_box.scroll_offset.1,
-                            );
+                            // Transform touch position to layout space
+                            let cursor_pos = self.text_box.screen_to_layout((location.x as f32, location.y as f32));
+                            self.text_box.extend_selection_to_point(cursor_pos.0, cursor_pos.1);
                         }
                         Ended => (),
                     }
@@ -491,7 +482,7 @@ impl TextEdit {
     // }
 
     /// Insert at cursor, or replace selection.
-    fn replace_range_and_record(&mut self, range: Range<usize>, old_selection: Selection, s: &str) {
+    fn replace_range_and_record(&mut self, range: Range<usize>, old_selection: TextSelection, s: &str) {
         let old_text = &self.text_box.text_inner()[range.clone()];
 
         let new_range_start = range.start;
@@ -500,7 +491,7 @@ impl TextEdit {
         self.history
             .record(&old_text, s, old_selection, new_range_start..new_range_end);
 
-        self.text_box.text_mut_string().replace_range(range, s);
+        self.text_box.replace_text_range(range, s);
         
         if self.single_line {
             self.remove_newlines();
@@ -510,7 +501,7 @@ impl TextEdit {
     fn replace_selection_and_record(&mut self, s: &str) {
         let old_selection = self.text_box.selection();
 
-        let range = self.text_box.selection().text_range();
+        let range = self.text_box.selection_text_range();
         let old_text = &self.text_box.text_inner()[range.clone()];
 
         let new_range_start = range.start;
@@ -546,13 +537,13 @@ impl TextEdit {
     pub(crate) fn restore_placeholder_if_any(&mut self) {
         if self.text_box.text_inner().is_empty() && !self.showing_placeholder {
             if self.placeholder_text.is_some() {
-                self.text_box.text_mut_string().clear();
+                self.text_box.text_storage_mut().set("");
                 self.refresh_layout();
                 self.text_box.move_to_text_start();
             }
 
             if let Some(placeholder) = &self.placeholder_text {
-                self.text_box.text_mut_string().push_str(&placeholder);
+                self.text_box.text_storage_mut().set(placeholder);
                 self.showing_placeholder = true;
                 self.refresh_layout();
                 self.text_box.shared_mut().rebuild_glyph_quad_buffer = true;
@@ -579,8 +570,15 @@ impl TextEdit {
                 .logical_clusters(&self.text_box.layout())[1]
                 .as_ref()
                 .map(|cluster| cluster.text_range())
-                .and_then(|range| (!range.is_empty()).then_some(range))
             {
+                let mut range = self.text_box.layout_to_text_range(range);
+                // Direction marks are only in the layout, so their cluster is empty in the text. Delete the next character instead.
+                if range.is_empty() {
+                    let Some(next_char) = self.text_box.text_inner()[range.end..].chars().next() else {
+                        return;
+                    };
+                    range.end += next_char.len_utf8();
+                }
                 self.replace_range_and_record(range, self.text_box.selection(), "");
                 self.refresh_layout();
             }
@@ -595,13 +593,14 @@ impl TextEdit {
 
         if self.text_box.selection().is_collapsed() {
             let focus = self.text_box.selection().focus();
-            let start = focus.index();
+            let start = self.text_box.layout_to_text_index(focus.index());
             let end = focus.next_logical_word(&self.text_box.layout()).index();
+            let end = self.text_box.layout_to_text_index(end);
             if self.text_box.text_inner().get(start..end).is_some() {
                 self.replace_range_and_record(start..end, self.text_box.selection(), "");
                 self.refresh_layout();
                 self.text_box.set_selection(
-                    Cursor::from_byte_index(&self.text_box.layout, start, Affinity::Downstream).into(),
+                    self.text_box.cursor_at(start, Affinity::Downstream).into(),
                 );
             }
         } else {
@@ -621,9 +620,12 @@ impl TextEdit {
                 .logical_clusters(&self.text_box.layout())[0]
                 .clone()
             {
+                let is_whole_cluster = cluster.is_hard_line_break() || cluster.is_emoji();
                 let range = cluster.text_range();
+                let range = self.text_box.layout_to_text_range(range);
                 let end = range.end;
-                let start = if cluster.is_hard_line_break() || cluster.is_emoji() {
+                // Direction marks are only in the layout, so their cluster is empty in the text and the previous character is deleted instead.
+                let start = if is_whole_cluster && !range.is_empty() {
                     // For newline sequences and emoji, delete the previous cluster
                     range.start
                 } else {
@@ -640,7 +642,7 @@ impl TextEdit {
                 self.replace_range_and_record(start..end, self.text_box.selection(), "");
                 self.refresh_layout();
                 self.text_box.set_selection(
-                    Cursor::from_byte_index(&self.text_box.layout, start, Affinity::Downstream).into(),
+                    self.text_box.cursor_at(start, Affinity::Downstream).into(),
                 );
             }
         } else {
@@ -654,13 +656,14 @@ impl TextEdit {
 
         if self.text_box.selection().is_collapsed() {
             let focus = self.text_box.selection().focus();
-            let end = focus.index();
+            let end = self.text_box.layout_to_text_index(focus.index());
             let start = focus.previous_logical_word(&self.text_box.layout()).index();
+            let start = self.text_box.layout_to_text_index(start);
             if self.text_box.text_inner().get(start..end).is_some() {
                 self.replace_range_and_record(start..end, self.text_box.selection(), "");
                 self.refresh_layout();
                 self.text_box.set_selection(
-                    Cursor::from_byte_index(&self.text_box.layout, start, Affinity::Downstream).into(),
+                    self.text_box.cursor_at(start, Affinity::Downstream).into(),
                 );
             }
         } else {
@@ -683,21 +686,19 @@ impl TextEdit {
         debug_assert!(cursor.map(|cursor| cursor.1 <= text.len()).unwrap_or(true));
 
         let start = if let Some(preedit_range) = &self.compose {
-            self.text_box.text_mut_string().replace_range(preedit_range.clone(), text);
+            self.text_box.replace_text_range(preedit_range.clone(), text);
             preedit_range.start
         } else {
-            let selection_start = self.text_box.selection().text_range().start;
+            let selection_start = self.text_box.selection_text_range().start;
             if self.text_box.selection().is_collapsed() {
-                self.text_box.text_mut_string()
-                    .insert_str(selection_start, text);
+                self.text_box.replace_text_range(selection_start..selection_start, text);
                 
                 if self.single_line {
                     self.remove_newlines();
                 }
             } else {
-                let range = self.text_box.selection().text_range();
-                self.text_box.text_mut_string()
-                    .replace_range(range, text);
+                let range = self.text_box.selection_text_range();
+                self.text_box.replace_text_range(range, text);
             }
             selection_start
         };
@@ -711,10 +712,10 @@ impl TextEdit {
         self.text_box.shared_mut().rebuild_glyph_quad_buffer = true;
 
         let cursor = cursor.unwrap_or((0, 0));
-        self.text_box.set_selection(Selection::new(
+        self.text_box.set_selection(TextSelection::new(
             // In parley, the layout is updated first, then the checked version is used. This should be fine too.
-            Cursor::from_byte_index(&self.text_box.layout, start + cursor.0, Affinity::Downstream),
-            Cursor::from_byte_index(&self.text_box.layout, start + cursor.1, Affinity::Downstream),
+            self.text_box.cursor_at(start + cursor.0, Affinity::Downstream),
+            self.text_box.cursor_at(start + cursor.1, Affinity::Downstream),
         ));
 
         self.text_box.needs_relayout = true;
@@ -725,7 +726,7 @@ impl TextEdit {
     /// This removes the IME preedit text.
     pub(crate) fn clear_compose(&mut self) {
         if let Some(preedit_range) = self.compose.take() {
-            self.text_box.text_mut_string().replace_range(preedit_range.clone(), "");
+            self.text_box.replace_text_range(preedit_range.clone(), "");
             self.text_box.shared_mut().cursor_blink_animation_currently_visible = true;
 
             let (index, affinity) = if preedit_range.start >= self.text_box.text_inner().len() {
@@ -735,7 +736,7 @@ impl TextEdit {
             };
 
             self.refresh_layout();
-            self.text_box.selection = Cursor::from_byte_index(&self.text_box.layout, index, affinity).into();
+            self.text_box.selection = self.text_box.cursor_at(index, affinity).into();
             self.text_box.shared_mut().rebuild_glyph_quad_buffer = true;
         }
     }
@@ -773,18 +774,15 @@ impl TextEdit {
             return;
         }
 
-        if let Some(op) = self.history.undo(self.text_box.text_mut_string()) {
+        if let Some(op) = self.history.undo(self.text_box.text_inner()) {
 
             if ! op.text_to_restore.is_empty() {
                 clear_placeholder_partial_borrows!(self);
             }
 
-            self
-                .text_box.text_mut_string()
-                .replace_range(op.range_to_clear.clone(), "");
-            self
-                .text_box.text_mut_string()
-                .insert_str(op.range_to_clear.start, op.text_to_restore);
+            self.text_box.replace_text_range(op.range_to_clear.clone(), "");
+            let start = op.range_to_clear.start;
+            self.text_box.replace_text_range(start..start, op.text_to_restore);
 
             let prev_selection = op.prev_selection;
             self.text_box.set_selection(prev_selection);
@@ -801,22 +799,19 @@ impl TextEdit {
         }
 
         if let Some(op) = self.history.redo() {
-            self
-                .text_box.text_mut_string()
-                .replace_range(op.range_to_clear.clone(), "");
+            self.text_box.replace_text_range(op.range_to_clear.clone(), "");
 
             if ! op.text_to_restore.is_empty() {
                 clear_placeholder_partial_borrows!(self);
             }
 
-            self
-                .text_box.text_mut_string()
-                .insert_str(op.range_to_clear.start, op.text_to_restore);
+            let start = op.range_to_clear.start;
+            self.text_box.replace_text_range(start..start, op.text_to_restore);
 
             let end = op.range_to_clear.start + op.text_to_restore.len();
 
             self.refresh_layout();
-            self.text_box.selection = Cursor::from_byte_index(&self.text_box.layout, end, Affinity::Upstream).into();
+            self.text_box.selection = self.text_box.cursor_at(end, Affinity::Upstream).into();
             
             if self.single_line {
                 self.remove_newlines();
@@ -825,16 +820,16 @@ impl TextEdit {
     }
 
     pub(crate) fn replace_selection_inner(&mut self, s: &str) {
-        let range = self.text_box.selection().text_range();
+        let range = self.text_box.selection_text_range();
         let start = range.start;
         if self.text_box.selection().is_collapsed() {
-            self.text_box.text_mut_string().insert_str(start, s);
+            self.text_box.replace_text_range(start..start, s);
             
             if self.single_line {
                 self.remove_newlines();
             }
         } else {
-            self.text_box.text_mut_string().replace_range(range, s);
+            self.text_box.replace_text_range(range, s);
         
             if self.single_line {
                 self.remove_newlines();
@@ -850,11 +845,11 @@ impl TextEdit {
 
         // With the new setup, we can do refresh_layout here and use the checked from_byte_index functions. However, the check is still completely useless, all it does is turn a potential explicit panic into a silent failure.
         self.refresh_layout();
-        self.text_box.selection = Cursor::from_byte_index(&self.text_box.layout, index, affinity).into();
+        self.text_box.selection = self.text_box.cursor_at(index, affinity).into();
     }
 
     /// Returns the layout, refreshing it if needed.
-    pub fn layout(&mut self) -> &Layout<ColorBrush> {
+    pub fn layout(&mut self) -> &TextLayout {
         self.text_box.layout()
     }
 
@@ -931,7 +926,7 @@ struct RecordedOp {
     /// To save memory, the redo data only gets populated when the element is undone.
     redo: Option<Ranges>,
     /// State of the selection right before this operation.
-    prev_selection: Selection,
+    prev_selection: TextSelection,
 }
 
 /// Internal Data for an undo or redo operation.
@@ -962,7 +957,7 @@ struct TextRestore<'a> {
     /// The state of selection right before the operation was made.
     /// Typically, undo operations restore the selection to this stored value,
     /// while redo operations ignore it and place a collapsed selection at the end of the newly restored text.
-    prev_selection: Selection,
+    prev_selection: TextSelection,
 }
 
 impl TextEditHistory {
@@ -1004,7 +999,7 @@ impl TextEditHistory {
         &mut self,
         old_str: &str,
         new_str: &str,
-        selection: Selection,
+        selection: TextSelection,
         inserted_range: Range<usize>,
     ) {
         if self.current_position < self.history.len() {
@@ -1043,7 +1038,7 @@ impl TextEditHistory {
         self.set_grow_hint(new_str, old_str);
     }
 
-    pub fn push_new(&mut self, old_str: &str, selection: Selection, inserted_range: Range<usize>) {
+    pub fn push_new(&mut self, old_str: &str, selection: TextSelection, inserted_range: Range<usize>) {
         let undo_range = self.undo_text.store_str(old_str);
 
         self.history.push(RecordedOp {
@@ -1090,7 +1085,7 @@ impl TextEditHistory {
         };
     }
 
-    fn undo(&mut self, buffer: &String) -> Option<TextRestore<'_>> {
+    fn undo(&mut self, buffer: &str) -> Option<TextRestore<'_>> {
         if self.current_position > 0 {
             self.current_position -= 1;
             let last = &mut self.history[self.current_position];
@@ -1138,18 +1133,14 @@ impl TextEditHistory {
     }
 }
 
-/// Replace newlines with spaces in-place. This probably doesn't allocate.
-fn remove_newlines_inplace(text: &mut String) -> bool {
-    let mut changed = false;
-    for i in 0..text.len() {
-        let b = text.as_bytes()[i];
-        if b == b'\n' || b == b'\r' {
-            text.replace_range(i..=i, " ");
-            changed = true;
-        }
+/// Replace newlines with spaces in-place. This doesn't allocate if there are no newlines.
+fn remove_newlines_inplace(text: &mut impl TextStorage) -> bool {
+    let newlines: Vec<usize> = text.as_str().match_indices(['\n', '\r']).map(|(i, _)| i).collect();
+    for &i in &newlines {
+        text.replace_range(i..i + 1, " ");
     }
 
-    return changed;
+    return !newlines.is_empty();
 }
 
 #[cfg(feature = "accessibility")]
@@ -1169,11 +1160,12 @@ impl_for_textedit_and_texteditmut! {
         }
         
         let (left, top) = self.text_box.pos();
+        let (width, height) = self.text_box.size();
         let bounds = AccessRect::new(
             left,
             top,
-            left + self.text_box.width as f64,
-            top + self.text_box.height as f64,
+            left + width as f64,
+            top + height as f64,
         );
         node.set_bounds(bounds);
 
@@ -1277,7 +1269,7 @@ impl TextEdit {
     }
 
     /// Returns the current text selection.
-    pub fn selection(&self) -> Selection {
+    pub fn selection(&self) -> TextSelection {
         self.text_box.selection()
     }
 
@@ -1289,7 +1281,7 @@ impl TextEdit {
 
 impl TextEdit {
     pub(crate) fn style_version(&self) -> u64 {
-        self.text_box.shared().styles[self.text_box.style.key].version
+        self.text_box.style_version()
     }
 
     pub(crate) fn style_version_changed(&self) -> bool {
@@ -1297,6 +1289,8 @@ impl TextEdit {
     }
 
     /// Returns a mutable reference to the raw text content.
+    ///
+    /// With a [`PieceTable`] storage, this joins the pending edits into a single `String`.
     pub fn raw_text_mut(&mut self) -> &mut String {
         self.text_box.text_mut_string()
     }
@@ -1344,6 +1338,96 @@ impl TextEdit {
         self.text_box.hitbox()
     }
 
+    /// Sets the background and the border of the text edit box. See [`TextBox::set_background()`].
+    pub fn set_background(&mut self, background: BoxBackground) {
+        self.text_box.set_background(background);
+    }
+
+    /// Returns the background and the border of the text edit box.
+    pub fn background(&self) -> BoxBackground {
+        self.text_box.background()
+    }
+
+    /// Sets the vertical alignment of the text inside the text edit box. See [`TextBox::set_vertical_alignment()`].
+    pub fn set_vertical_alignment(&mut self, vertical_alignment: VerticalAlignment) {
+        self.text_box.set_vertical_alignment(vertical_alignment);
+    }
+
+    /// Returns the vertical alignment of the text inside the text edit box.
+    pub fn vertical_alignment(&self) -> VerticalAlignment {
+        self.text_box.vertical_alignment()
+    }
+
+    /// Sets the paragraph direction of the text edit box. See [`TextBox::set_base_direction()`].
+    pub fn set_base_direction(&mut self, base_direction: BaseDirection) {
+        self.text_box.set_base_direction(base_direction);
+    }
+
+    /// Returns the paragraph direction of the text edit box.
+    pub fn base_direction(&self) -> BaseDirection {
+        self.text_box.base_direction()
+    }
+
+    /// Sets the shapes that the text flows around. See [`TextBox::set_exclusions()`].
+    pub fn set_exclusions(&mut self, exclusions: Vec<Exclusion>) {
+        self.text_box.set_exclusions(exclusions);
+    }
+
+    /// Returns the shapes that the text flows around.
+    pub fn exclusions(&self) -> &[Exclusion] {
+        self.text_box.exclusions()
+    }
+
+    /// Lays out the text along a path. See [`TextBox::set_text_path()`].
+    pub fn set_text_path(&mut self, text_path: Option<TextPath>) {
+        self.text_box.set_text_path(text_path);
+    }
+
+    /// Returns the path set with [`TextEdit::set_text_path()`].
+    pub fn text_path(&self) -> Option<&TextPath> {
+        self.text_box.text_path()
+    }
+
+    /// Sets the writing mode of the text edit box. See [`TextBox::set_writing_mode()`].
+    pub fn set_writing_mode(&mut self, writing_mode: WritingMode) {
+        self.text_box.set_writing_mode(writing_mode);
+    }
+
+    /// Returns the writing mode of the text edit box.
+    pub fn writing_mode(&self) -> WritingMode {
+        self.text_box.writing_mode()
+    }
+
+    /// Sets the language of the text. See [`TextBox::set_locale()`].
+    pub fn set_locale(&mut self, locale: Option<&str>) -> Result<(), InvalidLocale> {
+        self.text_box.set_locale(locale)
+    }
+
+    /// Returns the language tag set with [`TextEdit::set_locale()`].
+    pub fn locale(&self) -> Option<&str> {
+        self.text_box.locale()
+    }
+
+    /// Sets what the left and right arrow keys do in mixed-direction text. See [`TextBox::set_cursor_movement()`].
+    pub fn set_cursor_movement(&mut self, cursor_movement: CursorMovement) {
+        self.text_box.set_cursor_movement(cursor_movement);
+    }
+
+    /// Returns what the left and right arrow keys do in mixed-direction text.
+    pub fn cursor_movement(&self) -> CursorMovement {
+        self.text_box.cursor_movement()
+    }
+
+    /// Sets the padding of the text edit box. See [`TextBox::set_padding()`].
+    pub fn set_padding(&mut self, padding: Padding) {
+        self.text_box.set_padding(padding);
+    }
+
+    /// Returns the padding of the text edit box.
+    pub fn padding(&self) -> Padding {
+        self.text_box.padding()
+    }
+
     /// Sets the scroll offset for the text edit box.
     pub fn set_scroll_offset(&mut self, offset: (f32, f32)) {
         self.text_box.set_scroll_offset(offset);
@@ -1387,13 +1471,15 @@ impl TextEdit {
                 }
             } else {
                 // Vertical scrolling for multi-line edits
-                let text_height = self.text_box.height;
-                let cursor_top = cursor_rect.y0 as f32;
-                let cursor_bottom = cursor_rect.y1 as f32;
+                // Cursor and text positions relative to the top of the content area, including the vertical alignment.
+                let align_offset = self.text_box.vertical_align_offset();
+                let text_height = self.text_box.content_height();
+                let cursor_top = cursor_rect.y0 as f32 + align_offset;
+                let cursor_bottom = cursor_rect.y1 as f32 + align_offset;
                 let current_scroll = self.text_box.scroll_offset().1;
                 
                 // Get the total text height to check if we're overflowing
-                let total_text_height = self.text_box.layout.height();
+                let total_text_height = self.text_box.layout.height() + align_offset;
                 
                 // Calculate visible range
                 let visible_start = current_scroll;
@@ -1430,6 +1516,91 @@ impl TextEdit {
     pub fn set_style(&mut self, style: &StyleHandle) {
         self.text_box.set_style(style);
     }
+
+    /// Adds a styled range of text. The range follows the text as it gets edited.
+    pub fn add_span(&mut self, range: Range<usize>, style: SpanStyle) {
+        self.text_box.add_span(range, style);
+    }
+
+    /// Replaces all the styled ranges of the text.
+    pub fn set_spans(&mut self, spans: Vec<TextSpan>) {
+        self.text_box.set_spans(spans);
+    }
+
+    /// Removes all the styled ranges of the text.
+    pub fn clear_spans(&mut self) {
+        self.text_box.clear_spans();
+    }
+
+    /// Returns the styled ranges of the text.
+    pub fn spans(&self) -> &[TextSpan] {
+        self.text_box.spans()
+    }
+
+    /// Highlights a byte range of the text with a background color. The range follows the text as it gets edited. See [`TextBox::add_highlight()`].
+    pub fn add_highlight(&mut self, range: Range<usize>, color: ColorBrush) -> HighlightId {
+        self.text_box.add_highlight(range, color)
+    }
+
+    /// Removes a highlight.
+    pub fn remove_highlight(&mut self, id: HighlightId) {
+        self.text_box.remove_highlight(id);
+    }
+
+    /// Removes all highlights.
+    pub fn clear_highlights(&mut self) {
+        self.text_box.clear_highlights();
+    }
+
+    /// Returns the highlights of the text edit box.
+    pub fn highlights(&self) -> &[Highlight] {
+        self.text_box.highlights()
+    }
+
+    /// Inserts an inline box at the byte offset `index`, or moves and resizes the existing one with the same `id`. The box follows the text as it gets edited.
+    pub fn add_inline_box(&mut self, id: u64, index: usize, size: (f32, f32)) {
+        self.text_box.add_inline_box(id, index, size);
+    }
+
+    /// Removes the inline box with the given id.
+    pub fn remove_inline_box(&mut self, id: u64) {
+        self.text_box.remove_inline_box(id);
+    }
+
+    /// Removes all inline boxes.
+    pub fn clear_inline_boxes(&mut self) {
+        self.text_box.clear_inline_boxes();
+    }
+
+    /// Returns the inline boxes of the text edit box.
+    pub fn inline_boxes(&self) -> &[InlineBoxSlot] {
+        self.text_box.inline_boxes()
+    }
+
+    /// Returns the rect of an inline box in the local space of the text edit box. See [`TextBox::inline_box_rect()`].
+    pub fn inline_box_rect(&self, id: u64) -> Option<BoundingBox> {
+        self.text_box.inline_box_rect(id)
+    }
+
+    /// Returns the screen-space bounding box of an inline box. See [`TextBox::inline_box_screen_rect()`].
+    pub fn inline_box_screen_rect(&self, id: u64) -> Option<BoundingBox> {
+        self.text_box.inline_box_screen_rect(id)
+    }
+
+    /// Places an image at the byte offset `index`. See [`TextBox::add_inline_image()`].
+    pub fn add_inline_image(&mut self, index: usize, image: ImageId, alt_text: impl Into<Cow<'static, str>>) {
+        self.text_box.add_inline_image(index, image, alt_text);
+    }
+
+    /// Removes all inline images.
+    pub fn clear_inline_images(&mut self) {
+        self.text_box.clear_inline_images();
+    }
+
+    /// Returns the inline images of the text edit box.
+    pub fn inline_images(&self) -> &[InlineImage] {
+        self.text_box.inline_images()
+    }
     
     /// Returns the cursor geometry if visible.
     pub fn cursor_geometry(&mut self, size: f32) -> Option<parley::BoundingBox> {
@@ -1454,11 +1625,31 @@ impl TextEdit {
         if self.text_box.needs_relayout || self.style_version_changed() {
             if self.style_version_changed() {
                 self.text_box.style_version = self.style_version();
+                self.text_box.render_data_info.cache_generation = 0;
             }
             self.text_box.rebuild_layout(color_override, self.single_line);
         }
     }
 
+    /// Sets the storage that holds the text of the text edit box, and replaces the text with the storage's content.
+    ///
+    /// The default `String` storage is best for short fields. For long documents, a [`PieceTable`] avoids moving the rest of the text on every edit:
+    ///
+    /// ```no_run
+    /// # use keru_text::*;
+    /// # let mut text = Text::new();
+    /// # let handle = text.add_text_edit(String::new(), (0.0, 0.0), (400.0, 400.0), 0.0);
+    /// # let document = String::new();
+    /// text.get_text_edit_mut(&handle).set_text_storage(PieceTable::new(document));
+    /// ```
+    pub fn set_text_storage(&mut self, storage: impl TextStorage + 'static) {
+        *self.text_box.text_storage_mut() = TextContent::Storage(Box::new(storage));
+        self.text_box.move_to_text_end();
+        // Clear any composition state
+        self.compose = None;
+        self.showing_placeholder = false;
+    }
+
     /// Set the text of the text edit box.
     pub fn set_text(&mut self, new_text: &str) {
         self.text_box.set_text(new_text);
@@ -1474,8 +1665,7 @@ impl TextEdit {
         let placeholder_cow = placeholder.into();
         self.placeholder_text = Some(placeholder_cow.clone());
         if self.text_box.text_inner().is_empty() || self.showing_placeholder {
-            self.text_box.text_mut_string().clear();
-            self.text_box.text_mut_string().push_str(&placeholder_cow);
+            self.text_box.text_storage_mut().set(&placeholder_cow);
             self.text_box.needs_relayout = true;
             self.showing_placeholder = true;
             self.text_box.reset_selection();
@@ -1485,7 +1675,7 @@ impl TextEdit {
 
     // todo: we could also pass a range to check only the newly inserted part.
     fn remove_newlines(&mut self) {
-        let removed = remove_newlines_inplace(self.text_box.text_mut_string());
+        let removed = remove_newlines_inplace(self.text_box.text_storage_mut());
         if removed {
             self.text_box.needs_relayout = true;
             self.text_box.shared_mut().rebuild_glyph_quad_buffer = true;
@@ -1514,7 +1704,8 @@ impl TextEdit {
             // until https://github.com/rust-windowing/winit/pull/3966 is in the Winit release
             // used by this example.
             // Transform the IME cursor area to screen space
-            let screen_pos = self.text_box.transform().transform_point(euclid::Point2D::new(area.x0 as f32, area.y0 as f32));
+            let (offset_x, offset_y) = self.text_box.content_offset();
+            let screen_pos = self.text_box.frame_transform().transform_point(euclid::Point2D::new(area.x0 as f32 + offset_x, area.y0 as f32 + offset_y));
             window.set_ime_cursor_area(
                 winit::dpi::PhysicalPosition::new(
                     screen_pos.x as f64,
@@ -1559,7 +1750,7 @@ fn push_accesskit_update_textedit_partial_borrows(
     if let Some(id) = accesskit_id {
         inner.layout_access.build_nodes(
             &inner.text,
-            &inner.layout,
+            inner.layout.single(),
             tree_update,
             &mut node,
             node_id_generator,
@@ -1567,7 +1758,7 @@ fn push_accesskit_update_textedit_partial_borrows(
             top,
         );
 
-        if let Some(ak_sel) = inner.selection.to_access_selection(&inner.layout, &inner.layout_access) {
+        if let Some(ak_sel) = inner.selection.to_parley(inner.layout.single()).to_access_selection(inner.layout.single(), &inner.layout_access) {
             node.set_text_selection(ak_sel);
         }
         
@@ -0,0 +1,994 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ahash::AHasher;
+use parley::{Affinity, Alignment, AlignmentOptions, BoundingBox, Cluster, Cursor, InlineBox, Layout, Line, LineMetrics, PositionedLayoutItem, Selection};
+use rustc_hash::FxHasher;
+
+use crate::*;
+
+static NEXT_LAYOUT_VERSION: AtomicU64 = AtomicU64::new(1);
+
+/// Returns a new value for [`ParagraphLayout::version`]. Versions are never reused, even across boxes.
+fn next_layout_version() -> u64 {
+    NEXT_LAYOUT_VERSION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Width of the rect drawn for a selected line break between two paragraphs, relative to the height of the line.
+const LINE_BREAK_SELECTION_WIDTH: f64 = 0.25;
+
+/// One paragraph of a [`TextLayout`], with its own parley layout.
+pub(crate) struct ParagraphLayout {
+    pub layout: Layout<ColorBrush>,
+    /// Byte offset of the paragraph in the text of the layout.
+    pub start: usize,
+    /// Length of the paragraph in bytes, without the line break after it.
+    pub len: usize,
+    /// Top of the paragraph. It's always a whole number of pixels, so that the quads of a paragraph can be moved with it.
+    pub y: f32,
+    /// Index of the first line of the paragraph in the whole layout.
+    pub first_line: usize,
+    pub is_rtl: bool,
+    /// Hash of everything the shaping of the paragraph depends on. See [`TextBox::paragraph_shape_key()`].
+    pub shape_key: u64,
+    /// The max advance the lines were broken at.
+    pub break_width: Option<f32>,
+    /// The width and the alignment the lines were aligned with.
+    pub aligned: Option<(f32, Alignment)>,
+    /// Changes every time the paragraph is shaped, broken or aligned, so it can be used as the key of its quads.
+    pub version: u64,
+}
+
+impl ParagraphLayout {
+    fn end(&self) -> usize {
+        self.start + self.len
+    }
+
+    fn local_cursor(&self, cursor: TextCursor) -> Cursor {
+        let index = cursor.index.saturating_sub(self.start).min(self.len);
+        Cursor::from_byte_index(&self.layout, index, cursor.affinity)
+    }
+}
+
+/// The layout of the text of a [`TextBox`].
+///
+/// The paragraphs of most boxes are laid out on their own, so that an edit only shapes the paragraphs that it touches again. Boxes where paragraphs depend on each other, like the ones with paragraph styles, tab stops or a path, have a single layout for all the text. Either way, lines, cursors and selections cover the whole text.
+#[derive(Default)]
+pub struct TextLayout {
+    paragraphs: Vec<ParagraphLayout>,
+    split: bool,
+    width: f32,
+    full_width: f32,
+    height: f32,
+    line_count: usize,
+}
+
+impl TextLayout {
+    /// Wraps a layout of the whole text.
+    pub(crate) fn from_layout(layout: Layout<ColorBrush>) -> Self {
+        let len = layout.lines().last().map_or(0, |line| line.text_range().end);
+        let paragraph = ParagraphLayout {
+            layout,
+            start: 0,
+            len,
+            y: 0.0,
+            first_line: 0,
+            is_rtl: false,
+            shape_key: 0,
+            break_width: None,
+            aligned: None,
+            version: next_layout_version(),
+        };
+        return Self::from_paragraphs(vec![paragraph], false);
+    }
+
+    /// Stacks paragraphs that were laid out on their own.
+    fn from_paragraphs(mut paragraphs: Vec<ParagraphLayout>, split: bool) -> Self {
+        let mut y = 0.0;
+        let mut line_count = 0;
+        let (mut width, mut full_width) = (0.0_f32, 0.0_f32);
+        for paragraph in &mut paragraphs {
+            paragraph.y = y;
+            paragraph.first_line = line_count;
+            y = (y + paragraph.layout.height()).round();
+            line_count += paragraph.layout.len();
+            width = width.max(paragraph.layout.width());
+            full_width = full_width.max(paragraph.layout.full_width());
+        }
+        let height = paragraphs.last().map_or(0.0, |last| last.y + last.layout.height());
+        return Self { paragraphs, split, width, full_width, height, line_count };
+    }
+
+    /// Returns the width of the widest line, without trailing whitespace.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Returns the width of the widest line, with trailing whitespace.
+    pub fn full_width(&self) -> f32 {
+        self.full_width
+    }
+
+    /// Returns the height of the layout.
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// Returns the number of lines.
+    pub fn len(&self) -> usize {
+        self.line_count
+    }
+
+    /// Returns `true` if the layout has no lines.
+    pub fn is_empty(&self) -> bool {
+        self.line_count == 0
+    }
+
+    /// Returns the scale the layout was built at.
+    pub fn scale(&self) -> f32 {
+        self.paragraphs.first().map_or(1.0, |paragraph| paragraph.layout.scale())
+    }
+
+    /// Returns the lines of all the paragraphs, in order.
+    pub fn lines(&self) -> impl Iterator<Item = TextLine<'_>> + '_ {
+        self.paragraphs.iter().flat_map(|paragraph| {
+            paragraph.layout.lines().map(move |line| TextLine { line, y: paragraph.y, offset: paragraph.start })
+        })
+    }
+
+    /// Returns the line at `index`.
+    pub fn get(&self, index: usize) -> Option<TextLine<'_>> {
+        let paragraph = &self.paragraphs[self.paragraphs.partition_point(|p| p.first_line <= index).checked_sub(1)?];
+        let line = paragraph.layout.get(index - paragraph.first_line)?;
+        return Some(TextLine { line, y: paragraph.y, offset: paragraph.start });
+    }
+
+    /// Returns the parley layouts of the paragraphs, with the top of each one. A box that isn't split into paragraphs has a single layout.
+    pub fn paragraph_layouts(&self) -> impl Iterator<Item = (f32, &Layout<ColorBrush>)> + '_ {
+        self.paragraphs.iter().map(|paragraph| (paragraph.y, &paragraph.layout))
+    }
+
+    /// Returns `true` if each paragraph has its own layout.
+    pub(crate) fn is_split(&self) -> bool {
+        self.split
+    }
+
+    pub(crate) fn paragraphs(&self) -> &[ParagraphLayout] {
+        &self.paragraphs
+    }
+
+    /// Returns the layout of the whole text. Only accesskit needs it, and boxes aren't split into paragraphs when it's enabled.
+    #[cfg(feature = "accessibility")]
+    pub(crate) fn single(&self) -> &Layout<ColorBrush> {
+        debug_assert!(!self.split);
+        &self.paragraphs[0].layout
+    }
+
+    /// Returns the length of the text of the layout.
+    pub(crate) fn text_len(&self) -> usize {
+        self.paragraphs.last().map_or(0, |paragraph| paragraph.end())
+    }
+
+    fn paragraph_index_at(&self, index: usize) -> Option<usize> {
+        let i = self.paragraphs.partition_point(|p| p.start <= index).saturating_sub(1);
+        return (i < self.paragraphs.len()).then_some(i);
+    }
+
+    /// Returns the paragraph containing the byte index `index`. The line break after a paragraph belongs to it.
+    fn paragraph_at_index(&self, index: usize) -> Option<&ParagraphLayout> {
+        self.paragraphs.get(self.paragraph_index_at(index)?)
+    }
+
+    /// Returns the paragraph at the height `y`, or the first or the last one if `y` is outside of the layout.
+    fn paragraph_at_y(&self, y: f32) -> Option<&ParagraphLayout> {
+        self.paragraphs.get(self.paragraphs.partition_point(|p| p.y <= y).saturating_sub(1))
+    }
+
+    /// Returns the index of the line at the height `y`, clamped to the first and the last line.
+    fn line_at_y(&self, y: f32) -> usize {
+        let Some(paragraph) = self.paragraph_at_y(y) else {
+            return 0;
+        };
+        let local_y = y - paragraph.y;
+        let mut line_i = 0;
+        for (i, line) in paragraph.layout.lines().enumerate() {
+            let metrics = line.metrics();
+            if metrics.baseline - metrics.ascent > local_y {
+                break;
+            }
+            line_i = i;
+        }
+        return paragraph.first_line + line_i;
+    }
+
+    /// Aligns the lines of every paragraph in `width`. Paragraphs that are already aligned like this are left alone.
+    pub(crate) fn align(&mut self, width: f32, alignment: Alignment) {
+        for paragraph in &mut self.paragraphs {
+            if paragraph.aligned == Some((width, alignment)) {
+                continue;
+            }
+            paragraph.layout.align(Some(width), alignment, AlignmentOptions::default());
+            paragraph.aligned = Some((width, alignment));
+            paragraph.version = next_layout_version();
+        }
+    }
+
+    /// Returns the min and max content widths of the layout.
+    pub(crate) fn content_widths(&self) -> (f32, f32) {
+        self.paragraphs.iter()
+            .map(|paragraph| paragraph.layout.calculate_content_widths())
+            .fold((0.0, 0.0), |(min, max), widths| (f32::max(min, widths.min), f32::max(max, widths.max)))
+    }
+
+    /// Returns the byte range of the cluster at a point, if there is one.
+    pub(crate) fn cluster_range_at_point(&self, x: f32, y: f32) -> Option<Range<usize>> {
+        let paragraph = self.paragraph_at_y(y)?;
+        let (cluster, _) = Cluster::from_point(&paragraph.layout, x, y - paragraph.y)?;
+        let range = cluster.text_range();
+        return Some(range.start + paragraph.start..range.end + paragraph.start);
+    }
+}
+
+/// A line of a [`TextLayout`].
+///
+/// The parley line is positioned in the layout of its paragraph, whose top is at `y`.
+pub struct TextLine<'a> {
+    /// The line in the layout of its paragraph.
+    pub line: Line<'a, ColorBrush>,
+    /// Top of the paragraph of the line.
+    pub y: f32,
+    /// Byte offset of the paragraph of the line in the text of the layout.
+    pub offset: usize,
+}
+
+impl<'a> TextLine<'a> {
+    /// Returns the metrics of the line, with the baseline in the coordinates of the whole layout.
+    pub fn metrics(&self) -> LineMetrics {
+        let metrics = *self.line.metrics();
+        LineMetrics { baseline: metrics.baseline + self.y, ..metrics }
+    }
+
+    /// Returns the byte range of the line in the text of the layout.
+    pub fn text_range(&self) -> Range<usize> {
+        let range = self.line.text_range();
+        range.start + self.offset..range.end + self.offset
+    }
+
+    /// Returns the glyph runs and inline boxes of the line. Like the line, they're positioned in the layout of the paragraph, so `y` has to be added to their baselines.
+    pub fn items(&self) -> impl Iterator<Item = PositionedLayoutItem<'a, ColorBrush>> + '_ {
+        self.line.items()
+    }
+}
+
+fn offset_rect(rect: BoundingBox, dy: f32) -> BoundingBox {
+    BoundingBox { y0: rect.y0 + dy as f64, y1: rect.y1 + dy as f64, ..rect }
+}
+
+/// A caret position in a [`TextLayout`]: a byte index in the text of the layout, and the side of it that the caret sticks to.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct TextCursor {
+    index: usize,
+    affinity: Affinity,
+}
+
+impl TextCursor {
+    pub(crate) fn from_byte_index(layout: &TextLayout, index: usize, affinity: Affinity) -> Self {
+        let Some(paragraph) = layout.paragraph_at_index(index) else {
+            return Self::default();
+        };
+        let cursor = paragraph.local_cursor(Self { index, affinity });
+        return Self::from_local(paragraph, cursor);
+    }
+
+    fn from_local(paragraph: &ParagraphLayout, cursor: Cursor) -> Self {
+        Self { index: paragraph.start + cursor.index(), affinity: cursor.affinity() }
+    }
+
+    pub(crate) fn from_point(layout: &TextLayout, x: f32, y: f32) -> Self {
+        let Some(paragraph) = layout.paragraph_at_y(y) else {
+            return Self::default();
+        };
+        let cursor = Selection::from_point(&paragraph.layout, x, y - paragraph.y).focus();
+        return Self::from_local(paragraph, cursor);
+    }
+
+    /// Returns the byte index of the cursor in the text of the layout.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the side of the index that the cursor sticks to.
+    pub fn affinity(&self) -> Affinity {
+        self.affinity
+    }
+
+    /// Returns the rect of a caret of width `size` at the cursor.
+    pub fn geometry(&self, layout: &TextLayout, size: f32) -> BoundingBox {
+        let Some(paragraph) = layout.paragraph_at_index(self.index) else {
+            return BoundingBox { x0: 0.0, y0: 0.0, x1: size as f64, y1: 0.0 };
+        };
+        let rect = paragraph.local_cursor(*self).geometry(&paragraph.layout, size);
+        return offset_rect(rect, paragraph.y);
+    }
+
+    /// Returns the clusters before and after the cursor, in logical order. The line break between two paragraphs is a cluster of its own.
+    pub(crate) fn logical_clusters(&self, layout: &TextLayout) -> [Option<TextCluster>; 2] {
+        let Some(i) = layout.paragraph_index_at(self.index) else {
+            return [None, None];
+        };
+        let paragraph = &layout.paragraphs[i];
+        let [before, after] = paragraph.local_cursor(*self).logical_clusters(&paragraph.layout);
+        let mut clusters = [
+            before.map(|cluster| TextCluster::new(&cluster, paragraph.start)),
+            after.map(|cluster| TextCluster::new(&cluster, paragraph.start)),
+        ];
+        if clusters[0].is_none() && i > 0 {
+            clusters[0] = Some(TextCluster::line_break(paragraph.start - 1));
+        }
+        if clusters[1].is_none() && i + 1 < layout.paragraphs.len() {
+            clusters[1] = Some(TextCluster::line_break(paragraph.end()));
+        }
+        return clusters;
+    }
+
+    /// Returns the cursor at the end of the next word. At the end of a paragraph, it moves over the line break.
+    pub(crate) fn next_logical_word(&self, layout: &TextLayout) -> Self {
+        let Some(i) = layout.paragraph_index_at(self.index) else {
+            return *self;
+        };
+        let paragraph = &layout.paragraphs[i];
+        let cursor = paragraph.local_cursor(*self);
+        match layout.paragraphs.get(i + 1) {
+            Some(next) if cursor.index() >= paragraph.len => Self::from_byte_index(layout, next.start, Affinity::Downstream),
+            _ => Self::from_local(paragraph, cursor.next_logical_word(&paragraph.layout)),
+        }
+    }
+
+    /// Returns the cursor at the start of the previous word. At the start of a paragraph, it moves over the line break.
+    pub(crate) fn previous_logical_word(&self, layout: &TextLayout) -> Self {
+        let Some(i) = layout.paragraph_index_at(self.index) else {
+            return *self;
+        };
+        let paragraph = &layout.paragraphs[i];
+        let cursor = paragraph.local_cursor(*self);
+        if cursor.index() == 0 && i > 0 {
+            return Self::from_byte_index(layout, layout.paragraphs[i - 1].end(), Affinity::Downstream);
+        }
+        return Self::from_local(paragraph, cursor.previous_logical_word(&paragraph.layout));
+    }
+}
+
+/// A cluster of a [`TextLayout`], with its byte range in the text of the layout.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TextCluster {
+    range: Range<usize>,
+    is_hard_line_break: bool,
+    is_emoji: bool,
+}
+
+impl TextCluster {
+    fn new(cluster: &Cluster<'_, ColorBrush>, offset: usize) -> Self {
+        let range = cluster.text_range();
+        Self {
+            range: range.start + offset..range.end + offset,
+            is_hard_line_break: cluster.is_hard_line_break(),
+            is_emoji: cluster.is_emoji(),
+        }
+    }
+
+    /// The line break at `index`, which isn't in the layout of either paragraph.
+    fn line_break(index: usize) -> Self {
+        Self { range: index..index + 1, is_hard_line_break: true, is_emoji: false }
+    }
+
+    pub(crate) fn text_range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    pub(crate) fn is_hard_line_break(&self) -> bool {
+        self.is_hard_line_break
+    }
+
+    pub(crate) fn is_emoji(&self) -> bool {
+        self.is_emoji
+    }
+}
+
+/// What a selection grows by when its focus is dragged.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub(crate) enum TextAnchorBase {
+    #[default]
+    Cluster,
+    /// The selection started with a double click on the word between the two cursors.
+    Word(TextCursor, TextCursor),
+    /// The selection started with a triple click on the line between the two cursors.
+    Line(TextCursor, TextCursor),
+}
+
+/// A selection in a [`TextLayout`], from the anchor to the focus. If they're at the same index, the selection is a caret.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct TextSelection {
+    anchor: TextCursor,
+    focus: TextCursor,
+    anchor_base: TextAnchorBase,
+    /// Horizontal position kept by vertical movement, so that moving through a short line doesn't lose it.
+    h_pos: Option<f32>,
+}
+
+impl From<TextCursor> for TextSelection {
+    fn from(cursor: TextCursor) -> Self {
+        Self::new(cursor, cursor)
+    }
+}
+
+impl TextSelection {
+    pub(crate) fn new(anchor: TextCursor, focus: TextCursor) -> Self {
+        Self { anchor, focus, anchor_base: TextAnchorBase::Cluster, h_pos: None }
+    }
+
+    pub(crate) fn from_byte_index(layout: &TextLayout, index: usize, affinity: Affinity) -> Self {
+        TextCursor::from_byte_index(layout, index, affinity).into()
+    }
+
+    pub(crate) fn from_point(layout: &TextLayout, x: f32, y: f32) -> Self {
+        TextCursor::from_point(layout, x, y).into()
+    }
+
+    /// Selects the word at a point.
+    pub(crate) fn word_from_point(layout: &TextLayout, x: f32, y: f32) -> Self {
+        let Some(paragraph) = layout.paragraph_at_y(y) else {
+            return Self::default();
+        };
+        let selection = Selection::word_from_point(&paragraph.layout, x, y - paragraph.y);
+        let (start, end) = (TextCursor::from_local(paragraph, selection.anchor()), TextCursor::from_local(paragraph, selection.focus()));
+        return Self { anchor: start, focus: end, anchor_base: TextAnchorBase::Word(start, end), h_pos: None };
+    }
+
+    /// Selects the visual line at a point.
+    pub(crate) fn line_from_point(layout: &TextLayout, x: f32, y: f32) -> Self {
+        let Some(paragraph) = layout.paragraph_at_y(y) else {
+            return Self::default();
+        };
+        let selection = Selection::line_from_point(&paragraph.layout, x, y - paragraph.y);
+        let (start, end) = (TextCursor::from_local(paragraph, selection.anchor()), TextCursor::from_local(paragraph, selection.focus()));
+        return Self { anchor: start, focus: end, anchor_base: TextAnchorBase::Line(start, end), h_pos: None };
+    }
+
+    /// Returns the end of the selection that stays in place when it's extended.
+    pub fn anchor(&self) -> TextCursor {
+        self.anchor
+    }
+
+    /// Returns the end of the selection that moves when it's extended.
+    pub fn focus(&self) -> TextCursor {
+        self.focus
+    }
+
+    /// Returns `true` if the selection is a caret.
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor.index == self.focus.index
+    }
+
+    /// Returns the byte range covered by the selection.
+    pub fn text_range(&self) -> Range<usize> {
+        self.anchor.index.min(self.focus.index)..self.anchor.index.max(self.focus.index)
+    }
+
+    /// Returns a caret at the focus.
+    pub fn collapse(&self) -> Self {
+        Self { h_pos: self.h_pos, ..Self::from(self.focus) }
+    }
+
+    pub(crate) fn anchor_base(&self) -> TextAnchorBase {
+        self.anchor_base
+    }
+
+    /// Returns the selection with the focus moved, keeping the anchor if `extend` is `true`.
+    fn with_focus(&self, focus: TextCursor, extend: bool) -> Self {
+        if extend {
+            Self { focus, h_pos: None, ..*self }
+        } else {
+            focus.into()
+        }
+    }
+
+    /// Moves the focus to a point. After a double or triple click, the selection grows by whole words or lines and keeps covering the one it started on.
+    pub(crate) fn extend_to_point(&self, layout: &TextLayout, x: f32, y: f32) -> Self {
+        let (start, end, target) = match self.anchor_base {
+            TextAnchorBase::Cluster => return self.with_focus(TextCursor::from_point(layout, x, y), true),
+            TextAnchorBase::Word(start, end) => (start, end, Self::word_from_point(layout, x, y)),
+            TextAnchorBase::Line(start, end) => (start, end, Self::line_from_point(layout, x, y)),
+        };
+        let (target_start, target_end) = if target.anchor.index <= target.focus.index { (target.anchor, target.focus) } else { (target.focus, target.anchor) };
+        let (anchor, focus) = if target_start.index < start.index { (end, target_start) } else { (start, target_end) };
+        return Self { anchor, focus, anchor_base: self.anchor_base, h_pos: None };
+    }
+
+    /// Moves the focus with a parley movement in its paragraph. With `hop`, the focus moves to the next or the previous paragraph when it's at its edge and the movement doesn't move it anymore.
+    fn move_focus(
+        &self,
+        layout: &TextLayout,
+        extend: bool,
+        hop: Option<bool>,
+        step: impl FnOnce(Selection, &Layout<ColorBrush>) -> Selection,
+    ) -> Self {
+        if let (Some(right), false, false) = (hop, extend, self.is_collapsed()) {
+            let range = self.text_range();
+            let index = if right { range.end } else { range.start };
+            let cursor = if index == self.focus.index { self.focus } else { self.anchor };
+            return cursor.into();
+        }
+        let Some(i) = layout.paragraph_index_at(self.focus.index) else {
+            return *self;
+        };
+        let paragraph = &layout.paragraphs[i];
+        let cursor = paragraph.local_cursor(self.focus);
+        let moved = step(cursor.into(), &paragraph.layout).focus();
+        if (moved.index(), moved.affinity()) != (cursor.index(), cursor.affinity()) {
+            return self.with_focus(TextCursor::from_local(paragraph, moved), extend);
+        }
+        let Some(right) = hop else {
+            return self.with_focus(self.focus, extend);
+        };
+        // In a right-to-left paragraph, the start of the paragraph is on the right.
+        let forward = match (cursor.index() == 0, cursor.index() >= paragraph.len) {
+            (true, true) => right,
+            (false, true) => true,
+            (true, false) => false,
+            (false, false) => return self.with_focus(self.focus, extend),
+        };
+        let focus = if forward {
+            layout.paragraphs.get(i + 1).map(|next| TextCursor::from_byte_index(layout, next.start, Affinity::Downstream))
+        } else {
+            i.checked_sub(1).map(|previous| TextCursor::from_byte_index(layout, layout.paragraphs[previous].end(), Affinity::Downstream))
+        };
+        return self.with_focus(focus.unwrap_or(self.focus), extend);
+    }
+
+    pub(crate) fn next_visual(&self, layout: &TextLayout, extend: bool) -> Self {
+        self.move_focus(layout, extend, Some(true), |selection, layout| selection.next_visual(layout, true))
+    }
+
+    pub(crate) fn previous_visual(&self, layout: &TextLayout, extend: bool) -> Self {
+        self.move_focus(layout, extend, Some(false), |selection, layout| selection.previous_visual(layout, true))
+    }
+
+    pub(crate) fn next_visual_word(&self, layout: &TextLayout, extend: bool) -> Self {
+        self.move_focus(layout, extend, Some(true), |selection, layout| selection.next_visual_word(layout, true))
+    }
+
+    pub(crate) fn previous_visual_word(&self, layout: &TextLayout, extend: bool) -> Self {
+        self.move_focus(layout, extend, Some(false), |selection, layout| selection.previous_visual_word(layout, true))
+    }
+
+    pub(crate) fn line_start(&self, layout: &TextLayout, extend: bool) -> Self {
+        self.move_focus(layout, extend, None, |selection, layout| selection.line_start(layout, true))
+    }
+
+    pub(crate) fn line_end(&self, layout: &TextLayout, extend: bool) -> Self {
+        self.move_focus(layout, extend, None, |selection, layout| selection.line_end(layout, true))
+    }
+
+    /// Moves the focus by `delta` lines, keeping its horizontal position. `isize::MIN` and `isize::MAX` move it to the start and the end of the text.
+    pub(crate) fn move_lines(&self, layout: &TextLayout, delta: isize, extend: bool) -> Self {
+        let text_start = || TextCursor::from_byte_index(layout, 0, Affinity::Downstream);
+        let text_end = || TextCursor::from_byte_index(layout, layout.text_len(), Affinity::Upstream);
+        let (focus, h_pos) = match delta {
+            isize::MIN => (text_start(), None),
+            isize::MAX => (text_end(), None),
+            _ => {
+                let caret = self.focus.geometry(layout, 0.0);
+                let h_pos = self.h_pos.unwrap_or(caret.x0 as f32);
+                let line_i = layout.line_at_y(((caret.y0 + caret.y1) / 2.0) as f32) as isize + delta;
+                let focus = match usize::try_from(line_i).ok().and_then(|i| layout.get(i)) {
+                    Some(line) => {
+                        let metrics = line.metrics();
+                        TextCursor::from_point(layout, h_pos, metrics.baseline - (metrics.ascent - metrics.descent) / 2.0)
+                    }
+                    None if line_i < 0 => text_start(),
+                    None => text_end(),
+                };
+                (focus, Some(h_pos))
+            }
+        };
+        return Self { h_pos, ..self.with_focus(focus, extend) };
+    }
+
+    pub(crate) fn next_line(&self, layout: &TextLayout, extend: bool) -> Self {
+        self.move_lines(layout, 1, extend)
+    }
+
+    pub(crate) fn previous_line(&self, layout: &TextLayout, extend: bool) -> Self {
+        self.move_lines(layout, -1, extend)
+    }
+
+    /// Returns the selection with its cursors moved to valid positions in a new layout.
+    pub(crate) fn refresh(&self, layout: &TextLayout) -> Self {
+        Self {
+            anchor: TextCursor::from_byte_index(layout, self.anchor.index, self.anchor.affinity),
+            focus: TextCursor::from_byte_index(layout, self.focus.index, self.focus.affinity),
+            ..*self
+        }
+    }
+
+    /// Calls `f` with each rect covering the selection, and the index of its line.
+    pub fn geometry_with(&self, layout: &TextLayout, mut f: impl FnMut(BoundingBox, usize)) {
+        if self.is_collapsed() {
+            return;
+        }
+        let range = self.text_range();
+        let (start, end) = if self.anchor.index <= self.focus.index { (self.anchor, self.focus) } else { (self.focus, self.anchor) };
+        let first = layout.paragraph_index_at(range.start).unwrap_or(0);
+        for paragraph in &layout.paragraphs[first..] {
+            if paragraph.start >= range.end {
+                break;
+            }
+            let local_start = if start.index >= paragraph.start { paragraph.local_cursor(start) } else { Cursor::from_byte_index(&paragraph.layout, 0, Affinity::Downstream) };
+            let local_end = if end.index <= paragraph.end() { paragraph.local_cursor(end) } else { Cursor::from_byte_index(&paragraph.layout, paragraph.len, Affinity::Upstream) };
+            if local_start.index() != local_end.index() {
+                Selection::new(local_start, local_end).geometry_with(&paragraph.layout, |rect, line_i| {
+                    f(offset_rect(rect, paragraph.y), paragraph.first_line + line_i);
+                });
+            }
+
+            // The line break between two paragraphs isn't in either layout, but it's part of the selection.
+            if layout.split && range.end > paragraph.end() {
+                let caret = offset_rect(local_end.geometry(&paragraph.layout, 0.0), paragraph.y);
+                let width = (caret.y1 - caret.y0) * LINE_BREAK_SELECTION_WIDTH;
+                let (x0, x1) = if paragraph.is_rtl { (caret.x0 - width, caret.x0) } else { (caret.x0, caret.x0 + width) };
+                let last_line = paragraph.first_line + paragraph.layout.len().saturating_sub(1);
+                f(BoundingBox { x0, x1, ..caret }, last_line);
+            }
+        }
+    }
+
+    /// Converts an accesskit selection made on the single layout of the text.
+    #[cfg(feature = "accessibility")]
+    pub(crate) fn from_parley(selection: Selection) -> Self {
+        let cursor = |cursor: Cursor| TextCursor { index: cursor.index(), affinity: cursor.affinity() };
+        Self::new(cursor(selection.anchor()), cursor(selection.focus()))
+    }
+
+    /// Converts the selection for the single layout of the text, for accesskit.
+    #[cfg(feature = "accessibility")]
+    pub(crate) fn to_parley(&self, layout: &Layout<ColorBrush>) -> Selection {
+        let cursor = |cursor: TextCursor| Cursor::from_byte_index(layout, cursor.index, cursor.affinity);
+        Selection::new(cursor(self.anchor), cursor(self.focus))
+    }
+}
+
+/// Takes the paragraphs of `old` that were shaped with the same keys as the new paragraphs.
+///
+/// The paragraphs before and after an edit are matched in order, and the ones in between by key, so that repeated paragraphs like empty lines can't take the place of the ones around them.
+fn reuse_shaped_paragraphs(old: Vec<ParagraphLayout>, keys: &[u64]) -> Vec<Option<ParagraphLayout>> {
+    let prefix = old.iter().zip(keys).take_while(|(paragraph, key)| paragraph.shape_key == **key).count();
+    let suffix = old.iter().rev().zip(keys.iter().rev())
+        .take(old.len().min(keys.len()) - prefix)
+        .take_while(|(paragraph, key)| paragraph.shape_key == **key)
+        .count();
+
+    let mut reused: Vec<Option<ParagraphLayout>> = keys.iter().map(|_| None).collect();
+    let mut middle: HashMap<u64, Vec<ParagraphLayout>, BuildHasherDefault<FxHasher>> = HashMap::default();
+    let old_len = old.len();
+    for (i, paragraph) in old.into_iter().enumerate() {
+        if i < prefix {
+            reused[i] = Some(paragraph);
+        } else if i >= old_len - suffix {
+            reused[keys.len() - (old_len - i)] = Some(paragraph);
+        } else {
+            middle.entry(paragraph.shape_key).or_default().push(paragraph);
+        }
+    }
+    let edited = prefix..keys.len() - suffix;
+    for (slot, key) in reused[edited.clone()].iter_mut().zip(&keys[edited]) {
+        *slot = middle.get_mut(key).and_then(Vec::pop);
+    }
+    return reused;
+}
+
+/// Feeds formatted text into a hasher, for values that can be printed but not hashed.
+struct HashWriter<'a>(&'a mut AHasher);
+
+impl fmt::Write for HashWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+impl TextBox {
+    /// Returns `true` if the paragraphs of the box can be laid out on their own.
+    ///
+    /// Paragraph styles, exclusions and threads place lines depending on the lines before them, tab stops and font size fitting measure the whole text, and text on a path or in a vertical box is a single line or column.
+    pub(crate) fn can_split_paragraphs(&self, single_line: bool) -> bool {
+        // Accesskit builds its nodes from a single parley layout.
+        if cfg!(feature = "accessibility") {
+            return false;
+        }
+        return !single_line
+            && self.flattened_path.is_none()
+            && !self.is_vertical()
+            && !self.has_paragraph_styles()
+            && self.exclusions.is_empty()
+            && self.tab_stops.is_none()
+            && self.font_size_fit.is_none()
+            && self.continued_paragraph.is_none();
+    }
+
+    /// Lays out each paragraph of the text on its own. Paragraphs of `old` that were shaped from the same text, spans and inline boxes are taken instead of being shaped again.
+    pub(crate) fn build_paragraph_layout(&self, old: TextLayout, color_override: Option<ColorBrush>) -> TextLayout {
+        let LayoutInput { text, spans, inline_boxes, .. } = self.layout_input(None);
+
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for paragraph in text.split('\n') {
+            ranges.push(start..start + paragraph.len());
+            start += paragraph.len() + 1;
+        }
+        let paragraph_at = |index: usize| ranges.partition_point(|range| range.start <= index) - 1;
+
+        // Spans and inline boxes go to the paragraphs they're in, with their ranges moved to the start of the paragraph.
+        let mut paragraph_spans: Vec<Vec<TextSpan>> = vec![Vec::new(); ranges.len()];
+        for span in spans.iter() {
+            let valid = span.range.start < span.range.end && text.get(span.range.clone()).is_some();
+            if !valid {
+                continue;
+            }
+            for (i, range) in ranges.iter().enumerate().skip(paragraph_at(span.range.start)) {
+                if range.start >= span.range.end {
+                    break;
+                }
+                let (start, end) = (span.range.start.max(range.start), span.range.end.min(range.end));
+                if start < end {
+                    paragraph_spans[i].push(TextSpan { range: start - range.start..end - range.start, style: span.style.clone() });
+                }
+            }
+        }
+        let mut paragraph_boxes: Vec<Vec<InlineBox>> = vec![Vec::new(); ranges.len()];
+        for mut inline_box in inline_boxes {
+            let i = paragraph_at(inline_box.index);
+            inline_box.index -= ranges[i].start;
+            paragraph_boxes[i].push(inline_box);
+        }
+
+        let base_hasher = self.paragraph_base_hasher(color_override);
+        let keys: Vec<u64> = ranges.iter().enumerate()
+            .map(|(i, range)| self.paragraph_shape_key(base_hasher.clone(), &text[range.clone()], &paragraph_spans[i], &paragraph_boxes[i]))
+            .collect();
+        let old = if old.split { old.paragraphs } else { Vec::new() };
+        let reused = reuse_shaped_paragraphs(old, &keys);
+
+        let max_advance = self.max_advance;
+        let mut paragraphs = Vec::with_capacity(ranges.len());
+        for (i, (range, reused)) in ranges.iter().zip(reused).enumerate() {
+            let mut paragraph = reused.unwrap_or_else(|| ParagraphLayout {
+                layout: self.shape_text(&text[range.clone()], &paragraph_spans[i], &paragraph_boxes[i], color_override),
+                start: 0,
+                len: 0,
+                y: 0.0,
+                first_line: 0,
+                is_rtl: false,
+                shape_key: keys[i],
+                break_width: None,
+                aligned: None,
+                version: 0,
+            });
+            paragraph.start = range.start;
+            paragraph.len = range.len();
+            paragraph.is_rtl = self.is_rtl_at(self.layout_to_text_index(range.start));
+            if paragraph.break_width != Some(max_advance) {
+                paragraph.layout.break_all_lines(Some(max_advance));
+                paragraph.break_width = Some(max_advance);
+                paragraph.aligned = None;
+            }
+            paragraphs.push(paragraph);
+        }
+
+        let mut layout = TextLayout::from_paragraphs(paragraphs, true);
+        // When the box shrinks to fit, the lines are aligned in the shrunk width right away, so that the paragraphs that didn't change keep their alignment.
+        let align_width = self.shrunk_align_width(layout.width()).unwrap_or(max_advance);
+        layout.align(align_width, self.alignment);
+        return layout;
+    }
+
+    /// Starts the shape keys of the paragraphs with everything that applies to all of them.
+    fn paragraph_base_hasher(&self, color_override: Option<ColorBrush>) -> AHasher {
+        let mut hasher = AHasher::default();
+        self.style.key.hash(&mut hasher);
+        self.style_version.hash(&mut hasher);
+        (self.get_scale_factor() * self.font_fit_scale as f64).to_bits().hash(&mut hasher);
+        color_override.map(|color| color.0).hash(&mut hasher);
+        self.locale.hash(&mut hasher);
+        hasher
+    }
+
+    /// Hashes everything the shaping of a paragraph depends on: its text, its spans with their styles, and its inline boxes.
+    fn paragraph_shape_key(&self, mut hasher: AHasher, text: &str, spans: &[TextSpan], inline_boxes: &[InlineBox]) -> u64 {
+        text.hash(&mut hasher);
+        for span in spans {
+            span.range.hash(&mut hasher);
+            match &span.style {
+                SpanStyle::Style(handle) => {
+                    handle.key.hash(&mut hasher);
+                    self.shared().styles.get(handle.key).map(|style| style.version).hash(&mut hasher);
+                }
+                SpanStyle::Properties(properties) => {
+                    let _ = write!(HashWriter(&mut hasher), "{properties:?}");
+                }
+            }
+        }
+        for inline_box in inline_boxes {
+            inline_box.index.hash(&mut hasher);
+            inline_box.id.hash(&mut hasher);
+            inline_box.width.to_bits().hash(&mut hasher);
+            inline_box.height.to_bits().hash(&mut hasher);
+            self.inline_image_for_box_id(inline_box.id).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::NonNull;
+
+    use parley::StyleProperty;
+
+    use super::*;
+
+    const TEXT: &str = "one\ntwo\nthree";
+
+    fn text_edit(shared: &mut Shared, text: &str) -> TextEdit {
+        let default_style_key = shared.default_style_key;
+        let mut text_edit = TextEdit::new(text.to_string(), (0.0, 0.0), (300.0, 1000.0), 0.0, default_style_key, NonNull::from(shared));
+        text_edit.refresh_layout();
+        return text_edit;
+    }
+
+    fn versions(layout: &TextLayout) -> Vec<u64> {
+        layout.paragraphs().iter().map(|paragraph| paragraph.version).collect()
+    }
+
+    fn insert_at(text_edit: &mut TextEdit, index: usize, text: &str) {
+        let cursor = text_edit.text_box.cursor_at(index, Affinity::Downstream);
+        text_edit.text_box.set_selection(cursor.into());
+        text_edit.insert_or_replace_selection(text);
+        text_edit.refresh_layout();
+    }
+
+    fn paragraph(shape_key: u64, version: u64) -> ParagraphLayout {
+        ParagraphLayout {
+            layout: Layout::new(),
+            start: 0,
+            len: 0,
+            y: 0.0,
+            first_line: 0,
+            is_rtl: false,
+            shape_key,
+            break_width: None,
+            aligned: None,
+            version,
+        }
+    }
+
+    #[test]
+    fn editing_a_paragraph_only_lays_out_that_paragraph() {
+        let mut shared = Shared::new();
+        let mut text_edit = text_edit(&mut shared, "first\nsecond\nthird");
+        assert!(text_edit.text_box.layout.is_split());
+        let before = versions(&text_edit.text_box.layout);
+        assert_eq!(before.len(), 3);
+
+        insert_at(&mut text_edit, "first\nsecond".len(), " second");
+        assert_eq!(text_edit.raw_text(), "first\nsecond second\nthird");
+
+        let after = versions(&text_edit.text_box.layout);
+        assert_eq!(after[0], before[0]);
+        assert_ne!(after[1], before[1]);
+        assert_eq!(after[2], before[2]);
+        assert_eq!(text_edit.text_box.layout.paragraphs()[2].start, "first\nsecond second\n".len());
+    }
+
+    #[test]
+    fn repeated_paragraphs_keep_their_place() {
+        // An empty paragraph is inserted after the first one. The other empty paragraphs are matched in order instead of by key.
+        let old = [1, 0, 2, 0, 3].into_iter().enumerate().map(|(i, key)| paragraph(key, i as u64)).collect();
+        let reused = reuse_shaped_paragraphs(old, &[1, 0, 0, 2, 0, 3]);
+        let versions: Vec<Option<u64>> = reused.iter().map(|p| p.as_ref().map(|p| p.version)).collect();
+        assert_eq!(versions, [Some(0), Some(1), None, Some(2), Some(3), Some(4)]);
+    }
+
+    #[test]
+    fn lines_and_hit_testing_cross_paragraphs() {
+        let mut shared = Shared::new();
+        let text_edit = text_edit(&mut shared, TEXT);
+        let layout = &text_edit.text_box.layout;
+        assert_eq!(layout.len(), 3);
+
+        let lines: Vec<(usize, f32)> = layout.lines().map(|line| (line.text_range().start, line.metrics().baseline)).collect();
+        assert_eq!(lines.iter().map(|line| line.0).collect::<Vec<_>>(), [0, 4, 8]);
+        assert!(lines[0].1 < lines[1].1 && lines[1].1 < lines[2].1);
+        assert!(layout.height() > lines[2].1);
+
+        let y = lines[2].1 - 1.0;
+        assert_eq!(TextCursor::from_point(layout, 0.0, y).index(), 8);
+        assert_eq!(layout.cluster_range_at_point(1.0, y), Some(8..9));
+        let caret = TextCursor::from_byte_index(layout, 8, Affinity::Downstream).geometry(layout, 1.0);
+        assert!(caret.y0 < y as f64 && caret.y1 > y as f64);
+    }
+
+    #[test]
+    fn cursor_movement_crosses_paragraphs() {
+        let mut shared = Shared::new();
+        let mut text_edit = text_edit(&mut shared, TEXT);
+        let text_box = &mut text_edit.text_box;
+
+        text_box.set_selection(text_box.cursor_at(3, Affinity::Downstream).into());
+        text_box.move_right();
+        assert_eq!(text_box.selection().focus().index(), 4);
+        text_box.move_left();
+        assert_eq!(text_box.selection().focus().index(), 3);
+
+        text_box.move_to_text_start();
+        text_box.move_down();
+        assert_eq!(text_box.selection().focus().index(), 4);
+        text_box.move_down();
+        assert_eq!(text_box.selection().focus().index(), 8);
+        text_box.move_down();
+        assert_eq!(text_box.selection().focus().index(), TEXT.len());
+        // The horizontal position is kept through the move to the end of the text.
+        text_box.move_up();
+        assert_eq!(text_box.selection().focus().index(), 4);
+    }
+
+    #[test]
+    fn selection_covers_every_paragraph() {
+        let mut shared = Shared::new();
+        let mut text_edit = text_edit(&mut shared, TEXT);
+        text_edit.text_box.select_all();
+        assert_eq!(text_edit.selected_text(), Some(TEXT));
+
+        let layout = &text_edit.text_box.layout;
+        let mut rects = Vec::new();
+        text_edit.text_box.selection().geometry_with(layout, |rect, line_i| rects.push((rect, line_i)));
+        for line_i in 0..layout.len() {
+            let baseline = layout.get(line_i).unwrap().metrics().baseline as f64;
+            let line_rects: Vec<_> = rects.iter().filter(|(_, i)| *i == line_i).collect();
+            assert!(!line_rects.is_empty());
+            for (rect, _) in line_rects {
+                assert!(rect.y0 < baseline && rect.y1 > baseline);
+            }
+        }
+
+        // Deleting the line break joins two paragraphs.
+        text_edit.text_box.set_selection(text_edit.text_box.cursor_at(3, Affinity::Downstream).into());
+        text_edit.delete();
+        text_edit.refresh_layout();
+        assert_eq!(text_edit.raw_text(), "onetwo\nthree");
+        assert_eq!(text_edit.text_box.layout.paragraphs().len(), 2);
+    }
+
+    #[test]
+    fn quads_are_keyed_on_paragraph_layouts_with_spans() {
+        let mut shared = Shared::new();
+        let mut text_edit = text_edit(&mut shared, TEXT);
+        text_edit.add_span(8..13, SpanStyle::Properties(vec![StyleProperty::FontSize(30.0)]));
+        text_edit.refresh_layout();
+        assert!(text_edit.text_box.layout.is_split());
+
+        let keys = |text_edit: &mut TextEdit| {
+            text_edit.text_box.update_layout_paragraphs();
+            text_edit.text_box.render_data_info.layout_paragraphs.iter().map(|paragraph| paragraph.key).collect::<Vec<_>>()
+        };
+        let before = keys(&mut text_edit);
+        assert_eq!(before.len(), 3);
+
+        insert_at(&mut text_edit, 3, "!");
+        let after = keys(&mut text_edit);
+        assert_ne!(after[0], before[0]);
+        assert_eq!(after[1..], before[1..]);
+    }
+}
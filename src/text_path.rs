@@ -0,0 +1,180 @@
+use crate::*;
+
+/// A curve that the text of a box follows. See [`TextBox::set_text_path()`].
+///
+/// Points are in layout coordinates: the origin is the top-left corner of the content area of the box, inside the padding.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextPath {
+    /// Straight segments between consecutive points.
+    Polyline(Vec<(f32, f32)>),
+    /// A cubic bezier curve, as `[start, control1, control2, end]`.
+    CubicBezier([(f32, f32); 4]),
+}
+
+/// Number of straight segments that a bezier curve is split into.
+const BEZIER_SEGMENTS: usize = 64;
+
+/// Selection rects are split into pieces this wide, so that they can bend with the path.
+pub(crate) const PATH_RECT_PIECE_WIDTH: f32 = 4.0;
+
+/// A [`TextPath`] flattened into straight segments, with the distance along the path of each point.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FlattenedPath {
+    points: Vec<(f32, f32)>,
+    distances: Vec<f32>,
+}
+
+impl FlattenedPath {
+    pub(crate) fn new(path: &TextPath) -> Self {
+        let points: Vec<(f32, f32)> = match path {
+            TextPath::Polyline(points) => points.clone(),
+            TextPath::CubicBezier([p0, p1, p2, p3]) => (0..=BEZIER_SEGMENTS)
+                .map(|i| {
+                    let t = i as f32 / BEZIER_SEGMENTS as f32;
+                    let u = 1.0 - t;
+                    let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                    (
+                        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+                        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+                    )
+                })
+                .collect(),
+        };
+
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        for (i, point) in points.iter().enumerate() {
+            if let Some(previous) = i.checked_sub(1).map(|i| points[i]) {
+                total += (point.0 - previous.0).hypot(point.1 - previous.1);
+            }
+            distances.push(total);
+        }
+
+        Self { points, distances }
+    }
+
+    /// Total length of the path.
+    pub(crate) fn length(&self) -> f32 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// Returns the index of the segment that contains `distance`. Distances outside the path use the first or the last segment.
+    fn segment_at(&self, distance: f32) -> usize {
+        let after = self.distances.partition_point(|&d| d <= distance);
+        after.saturating_sub(1).min(self.points.len().saturating_sub(2))
+    }
+
+    /// Returns the point at `distance` along the path and the angle of the tangent there, in radians, clockwise from the x axis. Distances before the start or after the end continue in a straight line along the first or last segment.
+    pub(crate) fn sample(&self, distance: f32) -> ((f32, f32), f32) {
+        match self.points.len() {
+            0 => return ((distance, 0.0), 0.0),
+            1 => return ((self.points[0].0 + distance, self.points[0].1), 0.0),
+            _ => {}
+        }
+        let i = self.segment_at(distance);
+        let (a, b) = (self.points[i], self.points[i + 1]);
+        let angle = (b.1 - a.1).atan2(b.0 - a.0);
+        let along = distance - self.distances[i];
+        ((a.0 + along * angle.cos(), a.1 + along * angle.sin()), angle)
+    }
+
+    /// Returns the distance along the path of the closest point to `point`, and how far `point` is from the path. The second value is positive on the right side of the path, which is below it when the path goes to the right.
+    pub(crate) fn project(&self, point: (f32, f32)) -> (f32, f32) {
+        if self.points.len() < 2 {
+            let origin = self.points.first().copied().unwrap_or((0.0, 0.0));
+            return (point.0 - origin.0, point.1 - origin.1);
+        }
+
+        let mut best = (f32::MAX, 0.0, 0.0);
+        for i in 0..self.points.len() - 1 {
+            let (a, b) = (self.points[i], self.points[i + 1]);
+            let segment = (b.0 - a.0, b.1 - a.1);
+            let length = self.distances[i + 1] - self.distances[i];
+            if length <= 0.0 {
+                continue;
+            }
+            let direction = (segment.0 / length, segment.1 / length);
+            let relative = (point.0 - a.0, point.1 - a.1);
+            let mut along = relative.0 * direction.0 + relative.1 * direction.1;
+            // The first and the last segment extend past the ends, like in sample().
+            if i > 0 {
+                along = along.max(0.0);
+            }
+            if i < self.points.len() - 2 {
+                along = along.min(length);
+            }
+            let closest = (a.0 + along * direction.0, a.1 + along * direction.1);
+            let distance = (point.0 - closest.0).hypot(point.1 - closest.1);
+            if distance < best.0 {
+                let side = direction.0 * relative.1 - direction.1 * relative.0;
+                best = (distance, self.distances[i] + along, distance.copysign(side));
+            }
+        }
+        (best.1, best.2)
+    }
+
+    /// Returns the bounds of the path, grown by `margin` on all sides.
+    pub(crate) fn bounds(&self, margin: f32) -> BoundingBox {
+        let mut bounds = BoundingBox { x0: f64::MAX, y0: f64::MAX, x1: f64::MIN, y1: f64::MIN };
+        for point in &self.points {
+            bounds.x0 = bounds.x0.min((point.0 - margin) as f64);
+            bounds.y0 = bounds.y0.min((point.1 - margin) as f64);
+            bounds.x1 = bounds.x1.max((point.0 + margin) as f64);
+            bounds.y1 = bounds.y1.max((point.1 + margin) as f64);
+        }
+        bounds
+    }
+
+    /// Moves a quad from its position on a straight line with the given baseline to the path. The quad is placed so that the point at `anchor_x` on the baseline is on the path, and rotated around it to follow the tangent.
+    pub(crate) fn place_quad(&self, quad: &mut GlyphQuad, anchor_x: f32, baseline: f32) {
+        let ((x, y), angle) = self.sample(anchor_x);
+        let pivot = (x.round() as i32, y.round() as i32);
+        // The shader rotates around the pivot, so the quad is moved to be in the same place relative to the pivot as it was relative to the anchor.
+        let dx = pivot.0 - anchor_x.round() as i32;
+        let dy = pivot.1 - baseline.round() as i32;
+        offset_glyph_quads(std::slice::from_mut(quad), dx, dy);
+        set_quad_rotation(quad, angle, pivot);
+    }
+
+    /// Hashes the points of the path, for the keys of quads placed on it.
+    pub(crate) fn hash_points(&self, hasher: &mut impl std::hash::Hasher) {
+        for &(x, y) in &self.points {
+            hasher.write_u32(x.to_bits());
+            hasher.write_u32(y.to_bits());
+        }
+    }
+}
+
+impl TextBox {
+    /// Lays out the text along a path, or back on normal lines with `None`.
+    ///
+    /// The text is laid out on a single line, and each glyph is moved to the point of the path at its distance along the line, rotated to follow the tangent. The [`Alignment`] of the box aligns the text within the length of the path. The text continues in a straight line past the ends of the path.
+    ///
+    /// Selection, highlights and hit-testing follow the path. Underlines and other decorations are not drawn on a path.
+    pub fn set_text_path(&mut self, text_path: Option<TextPath>) {
+        if self.text_path == text_path {
+            return;
+        }
+        self.flattened_path = text_path.as_ref().map(FlattenedPath::new);
+        self.text_path = text_path;
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the path set with [`TextBox::set_text_path()`].
+    pub fn text_path(&self) -> Option<&TextPath> {
+        self.text_path.as_ref()
+    }
+
+    /// Returns the baseline of the text on a path, which is the line that is moved onto the path.
+    pub(crate) fn path_baseline(&self) -> f32 {
+        self.layout.lines().next().map_or(0.0, |line| line.metrics().baseline)
+    }
+
+    /// Converts a point relative to the content area to layout coordinates for text on a path: the distance along the path becomes x, and the distance from the path becomes the distance from the baseline.
+    pub(crate) fn path_point_to_layout(&self, path: &FlattenedPath, point: (f32, f32)) -> (f32, f32) {
+        let (along, across) = path.project(point);
+        (along, self.path_baseline() + across)
+    }
+}
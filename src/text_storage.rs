@@ -0,0 +1,350 @@
+use std::cell::OnceCell;
+use std::ops::{Deref, Range};
+
+use crate::*;
+
+/// Storage for the text of a [`TextEdit`].
+///
+/// By default, the text is kept in a `String`. For long documents, [`TextEdit::set_text_storage()`] can switch to a different storage, such as a [`PieceTable`], so that an edit doesn't have to move all the text after it.
+///
+/// Indices are byte offsets into the text returned by [`TextStorage::as_str()`], like for a `String`.
+pub trait TextStorage {
+    /// Returns the text as a single string.
+    fn as_str(&self) -> &str;
+
+    /// Replaces a byte range of the text. The range must start and end on char boundaries.
+    fn replace_range(&mut self, range: Range<usize>, text: &str);
+
+    /// Returns the text as a `String` that can be modified directly.
+    fn as_mut_string(&mut self) -> &mut String;
+
+    /// Returns the length of the text in bytes.
+    fn len(&self) -> usize {
+        self.as_str().len()
+    }
+
+    /// Returns `true` if the text is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl TextStorage for String {
+    fn as_str(&self) -> &str {
+        self
+    }
+
+    fn replace_range(&mut self, range: Range<usize>, text: &str) {
+        String::replace_range(self, range, text);
+    }
+
+    fn as_mut_string(&mut self) -> &mut String {
+        self
+    }
+}
+
+/// A [`TextStorage`] that records edits as a list of pieces instead of moving the text around.
+///
+/// Inserted text is appended to a separate buffer, and an edit only splits the pieces around it. The pieces are joined into a single string the next time the whole text is read, usually once per frame when the box is laid out. The joined string then becomes the base for the next edits.
+#[derive(Debug, Default)]
+pub struct PieceTable {
+    original: String,
+    added: String,
+    /// `None` if the text is all of `original`.
+    pieces: Option<Vec<Piece>>,
+    /// The length of the text when there are pieces.
+    len: usize,
+    joined: OnceCell<String>,
+}
+
+#[derive(Clone, Debug)]
+struct Piece {
+    added: bool,
+    range: Range<usize>,
+}
+
+impl PieceTable {
+    /// Creates a piece table holding `text`.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            original: text.into(),
+            ..Default::default()
+        }
+    }
+
+    fn join(&self, pieces: &[Piece]) -> String {
+        let mut joined = String::with_capacity(self.len);
+        for piece in pieces {
+            let buffer = if piece.added { &self.added } else { &self.original };
+            joined.push_str(&buffer[piece.range.clone()]);
+        }
+        return joined;
+    }
+
+    /// Makes the joined text the new base and drops the pieces.
+    fn compact(&mut self) {
+        let Some(pieces) = self.pieces.take() else {
+            return;
+        };
+        self.original = match self.joined.take() {
+            Some(joined) => joined,
+            None => self.join(&pieces),
+        };
+        self.added.clear();
+    }
+}
+
+impl TextStorage for PieceTable {
+    fn as_str(&self) -> &str {
+        match &self.pieces {
+            None => &self.original,
+            Some(pieces) => self.joined.get_or_init(|| self.join(pieces)),
+        }
+    }
+
+    fn replace_range(&mut self, range: Range<usize>, text: &str) {
+        let len = self.len();
+        assert!(range.start <= range.end && range.end <= len, "range {range:?} out of bounds for a text of length {len}");
+
+        // If the text was already joined for reading, making it the base is free and keeps the piece list short.
+        if self.joined.get().is_some() {
+            self.compact();
+        }
+        let pieces = match self.pieces.take() {
+            Some(pieces) => pieces,
+            None => vec![Piece { added: false, range: 0..self.original.len() }],
+        };
+
+        let mut new_pieces = Vec::with_capacity(pieces.len() + 2);
+        let mut after = Vec::new();
+        let mut offset = 0;
+        for piece in pieces {
+            let start = offset;
+            offset += piece.range.len();
+            if start < range.start {
+                let kept = offset.min(range.start) - start;
+                new_pieces.push(Piece { added: piece.added, range: piece.range.start..piece.range.start + kept });
+            }
+            if offset > range.end {
+                let skipped = range.end.saturating_sub(start);
+                after.push(Piece { added: piece.added, range: piece.range.start + skipped..piece.range.end });
+            }
+        }
+
+        if !text.is_empty() {
+            let start = self.added.len();
+            self.added.push_str(text);
+            match new_pieces.last_mut() {
+                // Typing keeps appending right after the previous insertion.
+                Some(last) if last.added && last.range.end == start => last.range.end = self.added.len(),
+                _ => new_pieces.push(Piece { added: true, range: start..self.added.len() }),
+            }
+        }
+        new_pieces.append(&mut after);
+
+        self.len = len - range.len() + text.len();
+        self.pieces = Some(new_pieces);
+        self.joined = OnceCell::new();
+    }
+
+    fn as_mut_string(&mut self) -> &mut String {
+        self.compact();
+        &mut self.original
+    }
+
+    fn len(&self) -> usize {
+        match &self.pieces {
+            None => self.original.len(),
+            Some(_) => self.len,
+        }
+    }
+}
+
+/// The text of a [`TextBox`]: either the default `String` or `&'static str`, or a custom [`TextStorage`].
+pub(crate) enum TextContent {
+    Cow(Cow<'static, str>),
+    Storage(Box<dyn TextStorage>),
+}
+
+impl TextContent {
+    pub(crate) fn set(&mut self, text: &str) {
+        match self {
+            TextContent::Cow(Cow::Owned(s)) => {
+                s.clear();
+                s.push_str(text);
+            }
+            TextContent::Cow(Cow::Borrowed(_)) => {
+                // We can't store &str references with arbitrary lifetimes, so if a text box that's currently holding a 'static str receives a non-'static string, we have to allocate a new string buffer.
+                *self = TextContent::Cow(Cow::Owned(text.to_string()));
+            }
+            TextContent::Storage(storage) => {
+                let len = storage.len();
+                storage.replace_range(0..len, text);
+            }
+        }
+    }
+
+    /// Sets a static string. A custom storage is kept, and the text is copied into it.
+    pub(crate) fn set_static(&mut self, text: &'static str) {
+        match self {
+            TextContent::Storage(_) => self.set(text),
+            TextContent::Cow(_) => *self = TextContent::Cow(Cow::Borrowed(text)),
+        }
+    }
+}
+
+impl TextStorage for TextContent {
+    fn as_str(&self) -> &str {
+        match self {
+            TextContent::Cow(text) => text.as_ref(),
+            TextContent::Storage(storage) => storage.as_str(),
+        }
+    }
+
+    fn replace_range(&mut self, range: Range<usize>, text: &str) {
+        match self {
+            TextContent::Cow(cow) => cow.to_mut().replace_range(range, text),
+            TextContent::Storage(storage) => storage.replace_range(range, text),
+        }
+    }
+
+    fn as_mut_string(&mut self) -> &mut String {
+        match self {
+            TextContent::Cow(cow) => cow.to_mut(),
+            TextContent::Storage(storage) => storage.as_mut_string(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            TextContent::Cow(text) => text.len(),
+            TextContent::Storage(storage) => storage.len(),
+        }
+    }
+}
+
+impl Deref for TextContent {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::NonNull;
+
+    use parley::Affinity;
+
+    use super::*;
+
+    fn piece_table_edit(shared: &mut Shared, text: &str) -> TextEdit {
+        let default_style_key = shared.default_style_key;
+        let mut text_edit = TextEdit::new(String::new(), (0.0, 0.0), (1000.0, 1000.0), 0.0, default_style_key, NonNull::from(shared));
+        text_edit.set_text_storage(PieceTable::new(text));
+        text_edit.refresh_layout();
+        return text_edit;
+    }
+
+    fn select(text_edit: &mut TextEdit, range: Range<usize>) {
+        let text_box = &mut text_edit.text_box;
+        let anchor = text_box.cursor_at(range.start, Affinity::Downstream);
+        let focus = text_box.cursor_at(range.end, Affinity::Upstream);
+        text_box.set_selection(TextSelection::new(anchor, focus));
+    }
+
+    fn is_piece_table(text_edit: &TextEdit) -> bool {
+        matches!(text_edit.text_box.text, TextContent::Storage(_))
+    }
+
+    #[test]
+    fn piece_table_edits_match_a_string() {
+        let mut table = PieceTable::new("hello world");
+        let mut string = String::from("hello world");
+        let edits: [(Range<usize>, &str); 7] = [
+            (5..5, ","),
+            (12..12, "!"),
+            (0..1, "H"),
+            (7..12, "wörld"),
+            (1..5, ""),
+            (2..2, "ab"),
+            (4..4, "c"),
+        ];
+        for (i, (range, text)) in edits.into_iter().enumerate() {
+            table.replace_range(range.clone(), text);
+            string.replace_range(range, text);
+            assert_eq!(table.len(), string.len());
+            // Read only after some of the edits, so that both the joined and the pending pieces are edited.
+            if i % 2 == 1 {
+                assert_eq!(table.as_str(), string);
+            }
+        }
+        assert_eq!(table.as_str(), string);
+
+        table.as_mut_string().push_str(" end");
+        string.push_str(" end");
+        assert_eq!(table.len(), string.len());
+        assert_eq!(table.as_str(), string);
+    }
+
+    #[test]
+    fn undo_and_redo_work_on_a_piece_table() {
+        let mut shared = Shared::new();
+        let mut text_edit = piece_table_edit(&mut shared, "hello world");
+
+        select(&mut text_edit, 5..5);
+        text_edit.insert_or_replace_selection(" there");
+        select(&mut text_edit, 0..5);
+        text_edit.insert_or_replace_selection("bye");
+        assert_eq!(text_edit.raw_text(), "bye there world");
+
+        text_edit.undo();
+        assert_eq!(text_edit.raw_text(), "hello there world");
+        assert_eq!(text_edit.text_box.selection_text_range(), 0..5);
+        text_edit.undo();
+        assert_eq!(text_edit.raw_text(), "hello world");
+
+        text_edit.redo();
+        assert_eq!(text_edit.raw_text(), "hello there world");
+        text_edit.redo();
+        assert_eq!(text_edit.raw_text(), "bye there world");
+        assert_eq!(text_edit.text_box.selection_text_range(), 3..3);
+        assert!(is_piece_table(&text_edit));
+    }
+
+    #[test]
+    fn compose_ranges_work_on_a_piece_table() {
+        let mut shared = Shared::new();
+        let mut text_edit = piece_table_edit(&mut shared, "ab");
+
+        select(&mut text_edit, 1..1);
+        text_edit.set_compose("k", Some((1, 1)));
+        text_edit.set_compose("か", Some((3, 3)));
+        assert_eq!(text_edit.raw_text(), "aかb");
+        assert_eq!(text_edit.compose, Some(1..4));
+        assert_eq!(text_edit.text_box.selection_text_range(), 4..4);
+
+        text_edit.clear_compose();
+        assert_eq!(text_edit.raw_text(), "ab");
+        assert_eq!(text_edit.text_box.selection_text_range(), 1..1);
+        assert!(is_piece_table(&text_edit));
+    }
+
+    #[test]
+    fn selection_byte_indices_work_on_a_piece_table() {
+        let mut shared = Shared::new();
+        let mut text_edit = piece_table_edit(&mut shared, "día uno\ndía dos");
+
+        select(&mut text_edit, 0..0);
+        text_edit.insert_or_replace_selection("¡");
+        select(&mut text_edit, "¡día uno\n".len().."¡día uno\ndía".len());
+        assert_eq!(text_edit.selected_text(), Some("día"));
+
+        text_edit.delete_selection();
+        assert_eq!(text_edit.raw_text(), "¡día uno\n dos");
+        let index = "¡día uno\n".len();
+        assert_eq!(text_edit.text_box.selection().focus().index(), index);
+        assert!(is_piece_table(&text_edit));
+    }
+}
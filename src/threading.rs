@@ -0,0 +1,280 @@
+use std::ops::Range;
+
+use slotmap::SlotMap;
+
+use crate::*;
+
+/// Handle for a text thread.
+///
+/// Obtained when creating a thread with [`Text::add_text_thread()`].
+#[derive(Debug)]
+pub struct TextThreadHandle {
+    pub(crate) key: DefaultKey,
+}
+
+#[cfg(feature = "panic_on_handle_drop")]
+impl Drop for TextThreadHandle {
+    fn drop(&mut self) {
+        panic!(
+            "TextThreadHandle was dropped without being consumed! \
+            This means that the corresponding text thread wasn't removed. To avoid leaking it, you should call Text::remove_text_thread(handle). \
+            If you're intentionally leaking this text thread, you can use \
+            std::mem::forget(handle) to skip the handle's drop() call and avoid this panic. \
+            You can also disable this check by disabling the \"panic_on_handle_drop\" feature in Cargo.toml."
+        );
+    }
+}
+
+/// A text that flows through a chain of [`TextBox`]es, like the linked text frames of a DTP program.
+pub(crate) struct TextThread {
+    pub(crate) text: String,
+    /// The boxes of the thread, in order.
+    pub(crate) boxes: Vec<DefaultKey>,
+    /// The range of the text shown by each box after the last flow.
+    pub(crate) ranges: Vec<Range<usize>>,
+    pub(crate) needs_flow: bool,
+}
+
+/// State of the paragraph that a box of a thread starts in the middle of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ContinuedParagraph {
+    /// The direction of the paragraph, from its first strong character in the text of the thread.
+    pub is_rtl: bool,
+}
+
+/// Length in bytes of the first piece of text that is laid out to find how much text fits in a box. See [`TextBox::fitting_text_len()`].
+const FIRST_FIT_PIECE_LEN: usize = 1024;
+
+impl TextBox {
+    /// Sets whether the box starts in the middle of a paragraph of its thread.
+    fn set_continued_paragraph(&mut self, continued_paragraph: Option<ContinuedParagraph>) {
+        if self.continued_paragraph == continued_paragraph {
+            return;
+        }
+        self.continued_paragraph = continued_paragraph;
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the length of the start of `text` that fits in the height of the box. The text of the box is replaced.
+    ///
+    /// Laying out all of `text` in every box would make flowing a long thread quadratic. Instead, the box lays out a piece from the start of `text` that doubles in length until it doesn't fit anymore, so the work is proportional to the text that fits.
+    fn fitting_text_len(&mut self, text: &str) -> usize {
+        let mut piece_len = FIRST_FIT_PIECE_LEN;
+        loop {
+            let mut end = piece_len.min(text.len());
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+            let is_whole = end == text.len();
+            self.set_text(&text[..end]);
+            self.refresh_layout();
+
+            let height = self.content_height();
+            let mut fitting_end = 0;
+            let mut overflowed = false;
+            for line in self.layout.lines() {
+                let metrics = line.metrics();
+                if metrics.baseline + metrics.descent > height {
+                    overflowed = true;
+                    break;
+                }
+                fitting_end = line.text_range().end;
+            }
+            // If the whole piece fits, its last line might continue after the end of the piece.
+            if overflowed || is_whole {
+                return self.layout_to_text_index(fitting_end);
+            }
+            piece_len *= 2;
+        }
+    }
+}
+
+impl Text {
+    /// Creates a text thread: a single text that flows through `boxes` in order.
+    ///
+    /// The text is laid out in the first box, and the lines that don't fit in its height continue in the next one. The last box gets all the remaining text, so its [`Overflow`] setting decides what happens to it.
+    ///
+    /// When a paragraph continues in the next box, the rest of it isn't treated as a new paragraph: it gets no first line indent, space before or list marker from the [`ParagraphStyle`] of that box, and with [`BaseDirection::Auto`] it keeps the direction of the whole paragraph.
+    ///
+    /// The boxes are linked with [`Text::link_text_boxes()`], so a selection continues from one box to the next, and [`Text::selected_text()`] copies it as a single text.
+    ///
+    /// The thread owns the text: the text of the boxes is replaced every time the thread flows. The text flows again when it changes with [`Text::set_thread_text()`], and when one of the boxes needs a new layout, for example after [`TextBox::set_size()`].
+    pub fn add_text_thread(&mut self, text: impl Into<String>, boxes: &[&TextBoxHandle]) -> TextThreadHandle {
+        let boxes: Vec<DefaultKey> = boxes.iter().map(|handle| handle.key).collect();
+        let key = self.text_threads.insert(TextThread {
+            text: text.into(),
+            boxes,
+            ranges: Vec::new(),
+            needs_flow: true,
+        });
+        self.link_thread_boxes(key);
+        TextThreadHandle { key }
+    }
+
+    /// Removes a text thread.
+    ///
+    /// The boxes are unlinked and keep the text that they were showing.
+    pub fn remove_text_thread(&mut self, handle: TextThreadHandle) {
+        let thread = self.text_threads.remove(handle.key).unwrap();
+        for key in thread.boxes {
+            if let Some(text_box) = self.text_boxes.get_mut(key) {
+                text_box.thread = None;
+                text_box.next_box = None;
+                text_box.prev_box = None;
+                text_box.set_continued_paragraph(None);
+            }
+        }
+        std::mem::forget(handle);
+    }
+
+    /// Sets the text of a thread.
+    pub fn set_thread_text(&mut self, handle: &TextThreadHandle, text: &str) {
+        let thread = &mut self.text_threads[handle.key];
+        if thread.text == text {
+            return;
+        }
+        thread.text.clear();
+        thread.text.push_str(text);
+        thread.needs_flow = true;
+    }
+
+    /// Returns the text of a thread.
+    pub fn thread_text(&self, handle: &TextThreadHandle) -> &str {
+        &self.text_threads[handle.key].text
+    }
+
+    /// Returns the range of the text of the thread shown by each box, in the order of the boxes. The thread flows first, if needed.
+    ///
+    /// Boxes that were removed don't have a range.
+    pub fn thread_ranges(&mut self, handle: &TextThreadHandle) -> &[Range<usize>] {
+        self.flow_text_thread(handle.key);
+        &self.text_threads[handle.key].ranges
+    }
+
+    /// Links the boxes of a thread in order, and points them back to the thread.
+    fn link_thread_boxes(&mut self, key: DefaultKey) {
+        self.text_threads[key].link_boxes(key, &mut self.text_boxes);
+    }
+
+    /// Removes a box that is being removed from its thread.
+    pub(crate) fn remove_box_from_thread(&mut self, box_key: DefaultKey, thread_key: DefaultKey) {
+        if let Some(thread) = self.text_threads.get_mut(thread_key) {
+            thread.boxes.retain(|&key| key != box_key);
+            thread.needs_flow = true;
+            self.link_thread_boxes(thread_key);
+        }
+    }
+
+    pub(crate) fn flow_text_threads(&mut self) {
+        let keys: Vec<DefaultKey> = self.text_threads.keys().collect();
+        for key in keys {
+            self.flow_text_thread(key);
+        }
+    }
+
+    /// Splits the text of a thread between its boxes, if the text or any of the boxes changed since the last time.
+    fn flow_text_thread(&mut self, key: DefaultKey) {
+        self.text_threads[key].flow(&mut self.text_boxes);
+    }
+}
+
+impl TextThread {
+    /// Links the boxes in order, and points them back to the thread, which has the key `key`.
+    fn link_boxes(&self, key: DefaultKey, text_boxes: &mut SlotMap<DefaultKey, TextBox>) {
+        for (i, &box_key) in self.boxes.iter().enumerate() {
+            if let Some(text_box) = text_boxes.get_mut(box_key) {
+                text_box.thread = Some(key);
+                text_box.prev_box = i.checked_sub(1).map(|i| self.boxes[i]);
+                text_box.next_box = self.boxes.get(i + 1).copied();
+            }
+        }
+    }
+
+    /// Splits the text between the boxes, if the text or any of the boxes changed since the last time.
+    fn flow(&mut self, text_boxes: &mut SlotMap<DefaultKey, TextBox>) {
+        let boxes_changed = self.boxes.iter().any(|&box_key| {
+            text_boxes.get(box_key).is_some_and(|text_box| text_box.needs_relayout || text_box.style_version_changed())
+        });
+        if !self.needs_flow && !boxes_changed {
+            return;
+        }
+
+        self.ranges.clear();
+        let box_keys: Vec<DefaultKey> = self.boxes.iter().copied()
+            .filter(|&box_key| text_boxes.contains_key(box_key))
+            .collect();
+        let mut start = 0;
+        for (i, &box_key) in box_keys.iter().enumerate() {
+            let continued_paragraph = (start > 0 && !self.text[..start].ends_with('\n'))
+                .then(|| ContinuedParagraph { is_rtl: paragraph_is_rtl(&self.text, start) });
+            let text_box = &mut text_boxes[box_key];
+            text_box.set_continued_paragraph(continued_paragraph);
+            let end = if i == box_keys.len() - 1 {
+                self.text.len()
+            } else {
+                start + text_box.fitting_text_len(&self.text[start..])
+            };
+            text_box.set_text(&self.text[start..end]);
+            text_box.refresh_layout();
+            self.ranges.push(start..end);
+            start = end;
+        }
+        self.needs_flow = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::NonNull;
+
+    use parley::Affinity;
+
+    use super::*;
+
+    const TEXT: &str = "one two three four five six seven eight nine ten eleven twelve thirteen fourteen fifteen sixteen seventeen eighteen nineteen twenty";
+
+    #[test]
+    fn resized_box_flows_again_and_selection_copies_across_boxes() {
+        let mut shared = Shared::new();
+        let default_style_key = shared.default_style_key;
+        let shared_backref = NonNull::from(&mut shared);
+        let mut text_boxes = SlotMap::new();
+        let first = text_boxes.insert(TextBox::new(String::new(), (0.0, 0.0), (120.0, 50.0), 0.0, default_style_key, shared_backref));
+        let second = text_boxes.insert(TextBox::new(String::new(), (0.0, 200.0), (120.0, 1000.0), 0.0, default_style_key, shared_backref));
+        let mut threads = SlotMap::new();
+        let key = threads.insert(TextThread { text: TEXT.to_string(), boxes: vec![first, second], ranges: Vec::new(), needs_flow: true });
+        threads[key].link_boxes(key, &mut text_boxes);
+
+        threads[key].flow(&mut text_boxes);
+        let old_ranges = threads[key].ranges.clone();
+        assert_eq!(old_ranges[0].start, 0);
+        assert!(0 < old_ranges[0].end && old_ranges[0].end < TEXT.len());
+        assert_eq!(old_ranges[1], old_ranges[0].end..TEXT.len());
+
+        // A taller first box takes more of the text, and the second box shows the rest.
+        text_boxes[first].set_size((120.0, 100.0));
+        threads[key].flow(&mut text_boxes);
+        let ranges = threads[key].ranges.clone();
+        assert!(ranges[0].end > old_ranges[0].end);
+        assert_eq!(ranges[1], ranges[0].end..TEXT.len());
+        assert_eq!(text_boxes[first].text, TEXT[ranges[0].clone()]);
+        assert_eq!(text_boxes[second].text, TEXT[ranges[1].clone()]);
+        assert!(!threads[key].needs_flow && !text_boxes[first].needs_relayout);
+
+        // A selection from the end of the first box to the start of the second one is copied as a single piece of the text.
+        let split = ranges[0].end;
+        let (copy_start, copy_end) = (split - 6, split + 5);
+        let first_box = &mut text_boxes[first];
+        let selection = TextSelection::new(first_box.cursor_at(copy_start, Affinity::Downstream), first_box.cursor_at(split, Affinity::Upstream));
+        first_box.set_selection(selection);
+        let second_box = &mut text_boxes[second];
+        let selection = TextSelection::new(second_box.cursor_at(0, Affinity::Downstream), second_box.cursor_at(copy_end - split, Affinity::Downstream));
+        second_box.set_selection(selection);
+
+        let mut copied = String::new();
+        push_selected_text(&mut copied, &text_boxes, &[first, second]);
+        assert_eq!(copied, TEXT[copy_start..copy_end]);
+    }
+}
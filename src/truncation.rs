@@ -0,0 +1,216 @@
+use std::ops::Range;
+
+use parley::InlineBox;
+
+use crate::*;
+
+/// How a [`TextBox`] handles text that doesn't fit.
+///
+/// The text doesn't fit if it has more lines than [`TextBox::set_max_lines()`] allows, if a word is wider than the box, or, when [`TextBox::set_auto_clip()`] is enabled, if it's taller than the box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// The text is laid out in full and clipped by the clip rect, if any. Lines past the max lines are removed.
+    #[default]
+    Clip,
+    /// The end of the text is replaced by an ellipsis.
+    EllipsisEnd,
+    /// The middle of the text is replaced by an ellipsis.
+    EllipsisMiddle,
+    /// The start of the text is replaced by an ellipsis.
+    EllipsisStart,
+}
+
+pub(crate) const ELLIPSIS: &str = "…";
+
+/// The part of the text that's hidden in the layout of a truncated box.
+///
+/// The layout shows the text before `removed`, then `inserted`, then the text after `removed`. Indices are mapped with the same functions used for edits, as if the hidden part was replaced by the ellipsis.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Truncation {
+    pub removed: Range<usize>,
+    pub inserted: &'static str,
+}
+
+impl Truncation {
+    /// Returns the text as shown in the layout.
+    pub(crate) fn apply(&self, text: &str) -> String {
+        let mut result = String::with_capacity(self.removed.start + self.inserted.len() + text.len() - self.removed.end);
+        result.push_str(&text[..self.removed.start]);
+        result.push_str(self.inserted);
+        result.push_str(&text[self.removed.end..]);
+        return result;
+    }
+
+    /// Maps the spans and the inline boxes to the shown text. Inline boxes in the hidden part are dropped.
+    pub(crate) fn map_spans_and_boxes(&self, spans: &[TextSpan], inline_boxes: Vec<InlineBox>) -> (Vec<TextSpan>, Vec<InlineBox>) {
+        let spans = spans.iter()
+            .map(|span| TextSpan {
+                range: map_range_for_replace(&span.range, &self.removed, self.inserted.len()),
+                style: span.style.clone(),
+            })
+            .filter(|span| !span.range.is_empty())
+            .collect();
+
+        let inline_boxes = inline_boxes.into_iter()
+            .filter(|b| b.index <= self.removed.start || b.index >= self.removed.end)
+            .map(|mut b| {
+                b.index = map_index_for_replace(b.index, &self.removed, self.inserted.len());
+                b
+            })
+            .collect();
+
+        return (spans, inline_boxes);
+    }
+}
+
+impl TextBox {
+    /// Sets how text that doesn't fit in the box is handled.
+    ///
+    /// The text returned by [`TextBox::text()`], the selection and copying all keep working on the full text. A selection that covers the ellipsis includes the hidden text.
+    pub fn set_overflow(&mut self, overflow: Overflow) {
+        if self.overflow == overflow {
+            return;
+        }
+        self.overflow = overflow;
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the overflow mode of the box.
+    pub fn overflow(&self) -> Overflow {
+        self.overflow
+    }
+
+    /// Sets the maximum number of lines shown. Lines after that are removed, or replaced by an ellipsis depending on the [`Overflow`] mode.
+    pub fn set_max_lines(&mut self, max_lines: Option<usize>) {
+        if self.max_lines == max_lines {
+            return;
+        }
+        self.max_lines = max_lines;
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the maximum number of lines shown.
+    pub fn max_lines(&self) -> Option<usize> {
+        self.max_lines
+    }
+
+    /// Returns `true` if some of the text is hidden because of the [`Overflow`] mode or the max lines.
+    pub fn is_truncated(&mut self) -> bool {
+        self.refresh_layout();
+        self.truncation.is_some()
+    }
+
+    /// Maps a byte range in the text to the layout.
+    pub(crate) fn text_to_layout_range(&self, range: &Range<usize>) -> Range<usize> {
+        let range = match &self.truncation {
+            Some(t) => map_range_for_replace(range, &t.removed, t.inserted.len()),
+            None => range.clone(),
+        };
+        if self.direction_marks.is_empty() {
+            return range;
+        }
+        index_with_direction_marks(&self.direction_marks, range.start)..index_with_direction_marks(&self.direction_marks, range.end)
+    }
+
+    /// Maps a byte range in the layout back to the text. A range that touches the ellipsis is extended over the hidden text.
+    pub(crate) fn layout_to_text_range(&self, range: Range<usize>) -> Range<usize> {
+        let range = if self.direction_marks.is_empty() {
+            range
+        } else {
+            index_without_direction_marks(&self.direction_marks, range.start)..index_without_direction_marks(&self.direction_marks, range.end)
+        };
+        let Some(t) = &self.truncation else {
+            return range;
+        };
+        let shown_end = t.removed.start + t.inserted.len();
+        let shift = |i: usize| i - shown_end + t.removed.end;
+
+        let start = if range.start < t.removed.start {
+            range.start
+        } else if range.start >= shown_end {
+            shift(range.start)
+        } else {
+            t.removed.start
+        };
+        let end = if range.end <= t.removed.start {
+            range.end
+        } else if range.end >= shown_end {
+            shift(range.end)
+        } else {
+            t.removed.end
+        };
+        return start..end.max(start);
+    }
+
+    /// Maps a byte index in the layout back to the text.
+    pub(crate) fn layout_to_text_index(&self, index: usize) -> usize {
+        self.layout_to_text_range(index..index).start
+    }
+
+    /// Decides which part of the text to hide, given the full layout.
+    ///
+    /// For the ellipsis modes, this does a binary search over the number of chars to keep, building a layout for each try.
+    pub(crate) fn find_truncation(&self, full_layout: &TextLayout, color_override: Option<ColorBrush>, single_line: bool) -> Option<Truncation> {
+        let max_lines = self.max_lines.unwrap_or(usize::MAX).max(1);
+        let ellipsis = self.overflow != Overflow::Clip;
+        let check_height = ellipsis && self.auto_clip;
+        let content_height = self.content_height();
+
+        let fits = |lines: usize, full_width: f32, height: f32| {
+            lines <= max_lines
+                && (!ellipsis || full_width <= self.max_advance + 0.5)
+                && (!check_height || lines <= 1 || height <= content_height + 0.5)
+        };
+
+        if fits(full_layout.len(), full_layout.full_width(), full_layout.height()) {
+            return None;
+        }
+
+        let text: &str = &self.text;
+        // The end of the last line allowed by the max lines.
+        let last_line_end = full_layout.get(max_lines - 1).map(|line| self.layout_to_text_index(line.text_range().end));
+
+        if !ellipsis {
+            let end = last_line_end?;
+            return Some(Truncation { removed: end..text.len(), inserted: "" });
+        }
+
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).chain(std::iter::once(text.len())).collect();
+        let n_chars = boundaries.len() - 1;
+
+        let truncation_keeping = |kept: usize| {
+            let (start, end) = match self.overflow {
+                Overflow::EllipsisEnd => (boundaries[kept], text.len()),
+                Overflow::EllipsisStart => (0, boundaries[n_chars - kept]),
+                Overflow::EllipsisMiddle => (boundaries[kept.div_ceil(2)], boundaries[n_chars - kept / 2]),
+                Overflow::Clip => unreachable!(),
+            };
+            // Don't leave spaces next to the ellipsis.
+            let start = text[..start].trim_end().len();
+            let end = text.len() - text[end..].trim_start().len();
+            Truncation { removed: start..end.max(start), inserted: ELLIPSIS }
+        };
+
+        // Find the largest number of chars that fits. All of them don't fit, and zero is accepted even if it doesn't.
+        let mut low = 0;
+        let mut high = n_chars;
+        if let (Overflow::EllipsisEnd, Some(end)) = (self.overflow, last_line_end) {
+            high = high.min(boundaries.partition_point(|&i| i <= end));
+        }
+        while high - low > 1 {
+            let mid = (low + high) / 2;
+            let layout = self.build_layout(Some(&truncation_keeping(mid)), color_override, single_line);
+            if fits(layout.len(), layout.full_width(), layout.height()) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        return Some(truncation_keeping(low));
+    }
+}
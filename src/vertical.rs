@@ -0,0 +1,171 @@
+use std::f32::consts::FRAC_PI_2;
+
+use parley::{Layout, StyleProperty};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::*;
+
+/// Font features for vertical text: vertical alternates for punctuation and brackets.
+pub(crate) const VERTICAL_FONT_FEATURES: &str = "\"vert\" on";
+
+/// Direction of the lines in a box. See [`TextBox::set_writing_mode()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum WritingMode {
+    /// Lines go left to right, and they're stacked top to bottom.
+    #[default]
+    Horizontal,
+    /// Lines are columns that go top to bottom, and they're stacked right to left, as in vertical Japanese or Chinese text.
+    VerticalRl,
+}
+
+impl TextBox {
+    /// Sets the writing mode of the box.
+    ///
+    /// In [`WritingMode::VerticalRl`], the text is laid out in columns that start from the right edge of the box. Ideographs, kana and hangul stay upright, and other scripts like Latin are rotated sideways, as in CSS `text-orientation: mixed`. The `vert` font feature is enabled, so that fonts can provide vertical forms of punctuation, and upright glyphs are spaced with the vertical advances of the font.
+    ///
+    /// The box is laid out as if it was a horizontal box rotated by 90 degrees clockwise, so the [`Padding`], the [`Alignment`] and the [`VerticalAlignment`] apply along the columns: `padding.left` is at the top and `padding.top` is on the right. The scroll offset is rotated in the same way. [`TextBox::set_size()`], [`TextBox::size()`] and the transform of the box are not rotated.
+    pub fn set_writing_mode(&mut self, writing_mode: WritingMode) {
+        if self.writing_mode == writing_mode {
+            return;
+        }
+        // The stored size is the size of the layout frame, which is rotated.
+        (self.width, self.height) = (self.height, self.width);
+        if let Some((max_width, max_height)) = self.shrink_to_fit {
+            self.shrink_to_fit = Some((max_height, max_width));
+        }
+        self.writing_mode = writing_mode;
+        self.scroll_offset = (0.0, 0.0);
+        self.update_max_advance();
+        self.mark_hit_bounds_dirty();
+        self.needs_relayout = true;
+        self.render_data_info.cache_generation = 0;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the writing mode of the box.
+    pub fn writing_mode(&self) -> WritingMode {
+        self.writing_mode
+    }
+
+    pub(crate) fn is_vertical(&self) -> bool {
+        self.writing_mode == WritingMode::VerticalRl
+    }
+
+    /// Swaps the two components of a size if the box is vertical. Converts between the sizes seen by the user and the sizes of the layout frame, in both directions.
+    pub(crate) fn frame_size(&self, size: (f32, f32)) -> (f32, f32) {
+        match self.writing_mode {
+            WritingMode::Horizontal => size,
+            WritingMode::VerticalRl => (size.1, size.0),
+        }
+    }
+
+    /// Returns the transform from the layout frame of the box to the screen.
+    ///
+    /// For vertical boxes, this is the transform of the box with a rotation by 90 degrees around its top-right corner. All the local coordinates used for hit-testing and rendering are in the layout frame, so the rest of the box works the same in both modes.
+    pub(crate) fn frame_transform(&self) -> Transform2D {
+        let transform = self.transform();
+        if !self.is_vertical() {
+            return transform;
+        }
+        // The frame is rotated and then moved right by the width of the box, which is the height of the frame.
+        let offset = transform.scale * self.height;
+        Transform2D {
+            translation: (
+                transform.translation.0 + offset * transform.rotation.cos(),
+                transform.translation.1 + offset * transform.rotation.sin(),
+            ),
+            rotation: transform.rotation + FRAC_PI_2,
+            scale: transform.scale,
+        }
+    }
+
+    /// Returns `true` if the glyphs of the run with this range in the layout are drawn upright in a vertical box.
+    pub(crate) fn is_run_upright(&self, layout_range: std::ops::Range<usize>) -> bool {
+        if !self.is_vertical() {
+            return false;
+        }
+        let range = self.layout_to_text_range(layout_range);
+        // Runs are split by script, so a single upright character means that the whole run is in an upright script.
+        self.text.get(range).is_some_and(|text| text.chars().any(is_upright_in_vertical))
+    }
+
+    /// Maps the arrow keys to the directions of the layout frame, so that they move the cursor in the direction of the arrow on the screen.
+    pub(crate) fn frame_arrow_key(&self, key: &Key) -> Key {
+        if !self.is_vertical() {
+            return key.clone();
+        }
+        match key {
+            Key::Named(NamedKey::ArrowDown) => Key::Named(NamedKey::ArrowRight),
+            Key::Named(NamedKey::ArrowUp) => Key::Named(NamedKey::ArrowLeft),
+            Key::Named(NamedKey::ArrowLeft) => Key::Named(NamedKey::ArrowDown),
+            Key::Named(NamedKey::ArrowRight) => Key::Named(NamedKey::ArrowUp),
+            _ => key.clone(),
+        }
+    }
+}
+
+/// Returns letter spacing spans that make the advance of each upright cluster in `layout` equal to its vertical advance, so that upright glyphs are spaced like in vertical text even though the layout is shaped horizontally.
+///
+/// The vertical advances come from the `vmtx` table of the font. Fonts without vertical metrics use the em height. `text` is the text of the layout, and the lines of the layout must be broken already. Returns no spans if all the advances already match, which is usual for CJK fonts.
+pub(crate) fn upright_spacing_spans(layout: &Layout<ColorBrush>, text: &str) -> Vec<TextSpan> {
+    let mut spans = Vec::new();
+    for line in layout.lines() {
+        for item in line.items() {
+            let PositionedLayoutItem::GlyphRun(glyph_run) = item else {
+                continue;
+            };
+            let run = glyph_run.run();
+            if !text.get(run.text_range()).is_some_and(|text| text.chars().any(is_upright_in_vertical)) {
+                continue;
+            }
+
+            let font = run.font();
+            let Some(font_ref) = FontRef::from_index(font.data.as_ref(), font.index as usize) else {
+                continue;
+            };
+            let coords = run.normalized_coords();
+            let font_size = run.font_size();
+            let has_vertical_metrics = font_ref.metrics(coords).has_vertical_metrics;
+            let glyph_metrics = font_ref.glyph_metrics(coords).scale(font_size);
+
+            for cluster in run.visual_clusters() {
+                let vertical_advance = if has_vertical_metrics {
+                    cluster.glyphs().map(|glyph| glyph_metrics.advance_height(glyph.id as u16)).sum()
+                } else {
+                    font_size
+                };
+                let difference = vertical_advance - cluster.advance();
+                if difference.abs() > 0.01 {
+                    spans.push(TextSpan {
+                        range: cluster.text_range(),
+                        // Style values are scaled by the layout.
+                        style: SpanStyle::Properties(vec![StyleProperty::LetterSpacing(difference / layout.scale())]),
+                    });
+                }
+            }
+        }
+    }
+    return spans;
+}
+
+/// Returns `true` for characters that stay upright in vertical text: the CJK scripts, their punctuation, full-width forms and emoji. This is an approximation of the Unicode `Vertical_Orientation` property.
+pub(crate) fn is_upright_in_vertical(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF // Hangul Jamo
+        | 0x2E80..=0x2FDF // CJK radicals, Kangxi radicals
+        | 0x3000..=0x303F // CJK symbols and punctuation
+        | 0x3040..=0x30FF // Hiragana, Katakana
+        | 0x3100..=0x31FF // Bopomofo, Hangul compatibility Jamo, Katakana extensions
+        | 0x3200..=0x33FF // Enclosed CJK, CJK compatibility
+        | 0x3400..=0x4DBF // CJK extension A
+        | 0x4E00..=0x9FFF // CJK unified ideographs
+        | 0xA960..=0xA97F // Hangul Jamo extended A
+        | 0xAC00..=0xD7FF // Hangul syllables, Jamo extended B
+        | 0xF900..=0xFAFF // CJK compatibility ideographs
+        | 0xFE30..=0xFE4F // CJK compatibility forms
+        | 0xFF01..=0xFF60 // Full-width forms
+        | 0xFFE0..=0xFFE6 // Full-width signs
+        | 0x1F000..=0x1FAFF // Emoji and other pictographs
+        | 0x20000..=0x3FFFF // CJK extensions B and later
+    )
+}
@@ -0,0 +1,311 @@
+use std::ops::Range;
+
+use parley::Affinity;
+
+use crate::*;
+
+/// Number of lines laid out above and below the visible ones in a virtual text box.
+const VIRTUAL_LINE_MARGIN: usize = 64;
+
+/// Number of lines laid out before the line height of a virtual text box is known.
+const INITIAL_VIRTUAL_LINES: usize = 128;
+
+/// A position in a virtual text box: a line and a byte offset in that line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct LinePosition {
+    /// Index of the line.
+    pub line: usize,
+    /// Byte offset in the line.
+    pub index: usize,
+}
+
+/// The lines of a virtual text box. See [`Text::add_virtual_text_box()`].
+pub(crate) struct VirtualLines {
+    pub line_count: usize,
+    pub get_line: Box<dyn Fn(usize, &mut String)>,
+    /// Lines currently in the text of the box.
+    pub window: Range<usize>,
+    /// Byte offset of each line of the window in the text of the box.
+    pub line_starts: Vec<usize>,
+    /// Height of a line, measured from the layout. Zero until the first layout.
+    pub line_height: f32,
+    /// Scroll from the top of the first line.
+    pub scroll: f32,
+    /// Anchor and focus of the selection.
+    pub selection: Option<(LinePosition, LinePosition)>,
+    /// Anchor and focus indices of the selection that was last put in the box, to tell if it was changed since.
+    pub applied_selection: (usize, usize),
+    /// Whether the anchor of the selection was inside the window when it was put in the box. If not, it was moved to the edge of the window.
+    pub anchor_in_window: bool,
+    pub needs_fetch: bool,
+}
+
+impl VirtualLines {
+    /// Returns the lines that should be in the window to cover the view with some margin, or `None` if the current window already covers it.
+    fn window_for_view(&self, view_height: f32) -> Option<Range<usize>> {
+        if self.line_height <= 0.0 {
+            let wanted = 0..self.line_count.min(INITIAL_VIRTUAL_LINES);
+            return (self.needs_fetch || wanted != self.window).then_some(wanted);
+        }
+        let first_visible = (self.scroll / self.line_height).floor() as usize;
+        let visible_count = (view_height / self.line_height).ceil() as usize + 1;
+        let last_visible = (first_visible + visible_count).min(self.line_count);
+
+        let covered = self.window.start <= first_visible && last_visible <= self.window.end;
+        if covered && !self.needs_fetch {
+            return None;
+        }
+        let start = first_visible.saturating_sub(VIRTUAL_LINE_MARGIN);
+        let end = (last_visible + VIRTUAL_LINE_MARGIN).min(self.line_count);
+        Some(start.min(end)..end)
+    }
+
+    /// Converts a byte index in the text of the box to a position.
+    fn position_at(&self, index: usize) -> LinePosition {
+        let i = self.line_starts.partition_point(|&start| start <= index).saturating_sub(1);
+        LinePosition {
+            line: self.window.start + i,
+            index: index - self.line_starts.get(i).copied().unwrap_or(0),
+        }
+    }
+
+    /// Converts a position to a byte index in the text of the box. Positions outside of the window are moved to its edges.
+    fn index_of(&self, position: LinePosition, text: &str) -> usize {
+        if position.line < self.window.start {
+            return 0;
+        }
+        if position.line >= self.window.end {
+            return text.len();
+        }
+        let i = position.line - self.window.start;
+        let start = self.line_starts[i];
+        let end = self.line_starts.get(i + 1).map_or(text.len(), |next| next - 1);
+        (start + position.index).min(end)
+    }
+}
+
+impl Text {
+    /// Adds a virtual text box, for very long texts like logs.
+    ///
+    /// The box doesn't hold the whole text. Instead, `get_line` is called with the index of a line and a buffer, and it should push the text of that line into the buffer, without a newline. Only the lines in view and a margin around them are requested, laid out and rendered, and they're requested again when the box is scrolled far enough.
+    ///
+    /// Lines aren't wrapped, and they should all have the same height. The box scrolls with the mouse wheel. The selection can span lines that are out of view, and copying it requests the lines that it covers.
+    ///
+    /// [`TextBox::text()`] only returns the lines that are currently laid out.
+    pub fn add_virtual_text_box(
+        &mut self,
+        line_count: usize,
+        get_line: impl Fn(usize, &mut String) + 'static,
+        pos: (f64, f64),
+        size: (f32, f32),
+        depth: f32,
+    ) -> TextBoxHandle {
+        let handle = self.add_text_box("", pos, size, depth);
+        let text_box = self.get_text_box_mut(&handle);
+        text_box.virtual_lines = Some(Box::new(VirtualLines {
+            line_count,
+            get_line: Box::new(get_line),
+            window: 0..0,
+            line_starts: Vec::new(),
+            line_height: 0.0,
+            scroll: 0.0,
+            selection: None,
+            applied_selection: (0, 0),
+            anchor_in_window: true,
+            needs_fetch: true,
+        }));
+        text_box.auto_clip = true;
+        text_box.needs_relayout = true;
+        handle
+    }
+}
+
+impl TextBox {
+    /// Returns `true` if the box was created with [`Text::add_virtual_text_box()`].
+    pub fn is_virtual(&self) -> bool {
+        self.virtual_lines.is_some()
+    }
+
+    /// Sets the number of lines of a virtual text box, for example when lines are added to a log. Does nothing for other boxes.
+    pub fn set_virtual_line_count(&mut self, line_count: usize) {
+        let Some(lines) = &mut self.virtual_lines else {
+            return;
+        };
+        if lines.line_count == line_count {
+            return;
+        }
+        lines.line_count = line_count;
+        lines.needs_fetch = true;
+        self.needs_relayout = true;
+        self.shared_mut().rebuild_glyph_quad_buffer = true;
+    }
+
+    /// Returns the number of lines of a virtual text box, or `None` for other boxes.
+    pub fn virtual_line_count(&self) -> Option<usize> {
+        self.virtual_lines.as_ref().map(|lines| lines.line_count)
+    }
+
+    /// Requests the lines of a virtual text box again, after their content changed.
+    pub fn reload_virtual_lines(&mut self) {
+        if let Some(lines) = &mut self.virtual_lines {
+            lines.needs_fetch = true;
+            self.needs_relayout = true;
+            self.shared_mut().rebuild_glyph_quad_buffer = true;
+        }
+    }
+
+    /// Scrolls a virtual text box so that `line` is at the top.
+    pub fn scroll_to_virtual_line(&mut self, line: usize) {
+        let Some(lines) = &self.virtual_lines else {
+            return;
+        };
+        let scroll = line as f32 * lines.line_height;
+        self.set_virtual_scroll(scroll);
+    }
+
+    /// Returns the anchor and the focus of the selection of a virtual text box.
+    pub fn virtual_selection(&self) -> Option<(LinePosition, LinePosition)> {
+        self.virtual_lines.as_ref()?.selection
+    }
+
+    /// Returns the selected text of a virtual text box, with the lines that it covers joined by newlines.
+    pub(crate) fn virtual_selected_text(&self) -> Option<String> {
+        let lines = self.virtual_lines.as_ref()?;
+        let (anchor, focus) = lines.selection?;
+        let (start, end) = (anchor.min(focus), anchor.max(focus));
+        if start == end {
+            return None;
+        }
+
+        let mut result = String::new();
+        let mut line_text = String::new();
+        for line in start.line..=end.line.min(lines.line_count.saturating_sub(1)) {
+            line_text.clear();
+            (lines.get_line)(line, &mut line_text);
+            let from = if line == start.line { start.index.min(line_text.len()) } else { 0 };
+            let to = if line == end.line { end.index.min(line_text.len()) } else { line_text.len() };
+            if line != start.line {
+                result.push('\n');
+            }
+            result.push_str(line_text.get(from..to).unwrap_or(""));
+        }
+        Some(result)
+    }
+
+    /// Scrolls a virtual text box by `delta`. Returns `true` if it scrolled.
+    pub(crate) fn scroll_virtual_lines_by(&mut self, delta: f32) -> bool {
+        let Some(lines) = &self.virtual_lines else {
+            return false;
+        };
+        let scroll = lines.scroll + delta;
+        self.set_virtual_scroll(scroll)
+    }
+
+    fn set_virtual_scroll(&mut self, scroll: f32) -> bool {
+        let content_height = self.content_height();
+        let Some(lines) = &mut self.virtual_lines else {
+            return false;
+        };
+        let max_scroll = (lines.line_count as f32 * lines.line_height - content_height).max(0.0).round();
+        let scroll = scroll.clamp(0.0, max_scroll).round();
+        if (scroll - lines.scroll).abs() < 0.1 {
+            return false;
+        }
+        lines.scroll = scroll;
+
+        if lines.window_for_view(content_height).is_some() {
+            // New lines have to be requested, and the scroll offset is set after the layout.
+            self.needs_relayout = true;
+            self.render_data_info.cache_generation = 0;
+            self.shared_mut().rebuild_glyph_quad_buffer = true;
+        } else {
+            self.scroll_offset.1 = scroll - lines.window.start as f32 * lines.line_height;
+            self.shared_mut().scrolled = true;
+        }
+        true
+    }
+
+    /// Updates the selection of a virtual text box from the selection in its text, if it was changed.
+    pub(crate) fn sync_virtual_selection(&mut self) {
+        let selection = self.selection;
+        let (anchor_index, focus_index) = (selection.anchor().index(), selection.focus().index());
+        let anchor_text_index = self.layout_to_text_index(anchor_index);
+        let focus_text_index = self.layout_to_text_index(focus_index);
+        let Some(lines) = &mut self.virtual_lines else {
+            return;
+        };
+        if (anchor_index, focus_index) == lines.applied_selection {
+            return;
+        }
+
+        let focus = lines.position_at(focus_text_index);
+        let anchor = match lines.selection {
+            // The anchor didn't move, but it was moved to the edge of the window, so the old one is still the real one.
+            Some((anchor, _)) if anchor_index == lines.applied_selection.0 && !lines.anchor_in_window => anchor,
+            _ => lines.position_at(anchor_text_index),
+        };
+        lines.selection = Some((anchor, focus));
+        lines.applied_selection = (anchor_index, focus_index);
+        lines.anchor_in_window = true;
+    }
+
+    /// Puts the lines around the view into the text of a virtual text box, if they aren't already there.
+    pub(crate) fn update_virtual_window(&mut self) {
+        let content_height = self.content_height();
+        let Some(window) = self.virtual_lines.as_ref().and_then(|lines| lines.window_for_view(content_height)) else {
+            return;
+        };
+        self.sync_virtual_selection();
+
+        let lines = self.virtual_lines.as_mut().unwrap();
+        let mut text = String::new();
+        lines.line_starts.clear();
+        for line in window.clone() {
+            if line != window.start {
+                text.push('\n');
+            }
+            lines.line_starts.push(text.len());
+            (lines.get_line)(line, &mut text);
+        }
+        lines.window = window;
+        lines.needs_fetch = false;
+        self.set_text(&text);
+    }
+
+    /// Measures the line height of a virtual text box, and puts its scroll and selection in the new layout.
+    ///
+    /// Returns `true` if the line height changed so much that the box needs different lines, and has to be laid out again.
+    pub(crate) fn apply_virtual_layout(&mut self) -> bool {
+        let content_height = self.content_height();
+        let line_height = self.layout.lines().next().map(|line| line.metrics().line_height);
+        let Some(lines) = &mut self.virtual_lines else {
+            return false;
+        };
+        if let Some(line_height) = line_height {
+            if line_height != lines.line_height {
+                // The first layout is done without knowing the line height, so the window might not cover the view.
+                lines.line_height = line_height;
+                if lines.window_for_view(content_height).is_some() {
+                    return true;
+                }
+            }
+        }
+        self.scroll_offset.1 = lines.scroll - lines.window.start as f32 * lines.line_height;
+
+        let Some((anchor, focus)) = lines.selection else {
+            return false;
+        };
+        lines.anchor_in_window = lines.window.contains(&anchor.line);
+        let anchor_index = lines.index_of(anchor, &self.text);
+        let focus_index = lines.index_of(focus, &self.text);
+        let selection = TextSelection::new(
+            self.cursor_at(anchor_index, Affinity::Downstream),
+            self.cursor_at(focus_index, Affinity::Downstream),
+        );
+        self.selection = selection;
+        if let Some(lines) = &mut self.virtual_lines {
+            lines.applied_selection = (selection.anchor().index(), selection.focus().index());
+        }
+        false
+    }
+}